@@ -1,7 +1,8 @@
 import { useState, useEffect, useCallback } from "react";
 import { useAnchor } from "./useAnchor";
 import { toast } from "sonner";
-import { PublicKey } from "@solana/web3.js";
+import { LAMPORTS_PER_SOL, ParsedAccountData, PublicKey } from "@solana/web3.js";
+import { findProductPDA, findUserProfilePDA } from "../utils/pdaHelpers";
 
 export interface PurchaseItem {
   name: string;
@@ -28,7 +29,7 @@ export interface Purchase {
 }
 
 export const usePurchaseHistory = () => {
-  const { program, walletAddress, connection } = useAnchor();
+  const { program, walletAddress } = useAnchor();
   const [purchases, setPurchases] = useState<Purchase[]>([]);
   const [isLoading, setIsLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -89,107 +90,96 @@ export const usePurchaseHistory = () => {
         setPurchases([]);
       }
       
-      // Try to fetch from blockchain if wallet is connected
-      if (connection && walletAddress) {
+      // Read history from the buyer's on-chain profile if wallet is connected
+      if (program && walletAddress) {
         try {
-          console.log('Fetching purchases from blockchain for wallet:', walletAddress.toString());
-          
-          // Get recent signatures for the wallet - increase limit to 30 for more history
           const walletPublicKey = new PublicKey(walletAddress);
-          const signatures = await connection.getSignaturesForAddress(
-            walletPublicKey,
-            { limit: 30 } // Increased from 10 to 30
+          console.log('Fetching purchase history from chain for wallet:', walletPublicKey.toString());
+
+          // The profile keeps a ring buffer of the latest checkouts
+          const profile = await program.account.userProfile.fetchNullable(
+            findUserProfilePDA(walletPublicKey)
           );
-          
-          if (signatures.length > 0) {
-            console.log(`Found ${signatures.length} signatures`);
-            
-            // Process each signature to get transaction details
-            const fetchedPurchases: Purchase[] = [];
-            
-            for (const sig of signatures) {
-              try {
-                if (!sig.signature || !sig.blockTime) continue;
-                
-                const tx = await connection.getTransaction(sig.signature, {
-                  maxSupportedTransactionVersion: 0
-                });
-                
-                if (!tx || !tx.meta) continue;
-                
-                // More lenient check for purchase transactions
-                // In a real app, you would check for specific program IDs
-                const isPurchase = tx.meta.logMessages?.some(
-                  msg => {
-                    const msgLower = msg.toLowerCase();
-                    return msgLower.includes("purchase") || 
-                           msgLower.includes("payment") || 
-                           msgLower.includes("buy") || 
-                           msgLower.includes("transaction") ||
-                           msgLower.includes("sodap");
-                  }
-                );
-                
-                if (!isPurchase) continue;
-                
-                // Create a purchase object from transaction data
-                const purchase: Purchase = {
-                  id: `purchase_${sig.signature.substring(0, 8)}`,
-                  storeName: "SoDap Store", // This would come from transaction data in a real app
-                  date: new Date(sig.blockTime * 1000).toISOString(),
-                  items: [
-                    // This would come from transaction data in a real app
-                    { name: "Digital Item", price: 0.5, quantity: 1 }
-                  ],
-                  totalAmount: 0.5, // This would be calculated from transaction data
-                  transactionSignature: sig.signature,
-                  receiptAddress: "receipt_address", // This would come from transaction data
-                  storeAddress: "store_address", // This would come from transaction data
-                  buyerAddress: walletAddress.toString(),
-                  purchaseTimestamp: sig.blockTime
-                };
-                
-                fetchedPurchases.push(purchase);
-              } catch (err) {
-                console.error(`Error processing transaction ${sig.signature}:`, err);
+          const records = profile?.purchaseHistory ?? [];
+
+          // Amounts are in the store's payment mint when it has one, else lamports
+          const mintScales = new Map<string, number>();
+          const amountScale = async (mint: PublicKey | null) => {
+            if (!mint) return LAMPORTS_PER_SOL;
+            const key = mint.toBase58();
+            if (!mintScales.has(key)) {
+              const info = await program.provider.connection.getParsedAccountInfo(mint);
+              const decimals = (info.value?.data as ParsedAccountData | undefined)?.parsed
+                ?.info?.decimals;
+              if (typeof decimals !== "number") {
+                throw new Error(`Could not read decimals for mint ${key}`);
               }
+              mintScales.set(key, 10 ** decimals);
             }
-            
-            if (fetchedPurchases.length > 0) {
-              console.log(`Processed ${fetchedPurchases.length} purchases from blockchain`);
-              
-              // Combine with cached purchases, avoiding duplicates
-              const allPurchases = [...fetchedPurchases];
-              
-              // Add existing purchases that aren't duplicates
-              cachedPurchases.forEach(existing => {
-                if (!allPurchases.some(p => p.id === existing.id)) {
-                  allPurchases.push(existing);
-                }
+            return mintScales.get(key)!;
+          };
+
+          const fetchedPurchases: Purchase[] = [];
+          for (const record of records) {
+            try {
+              const receipt = await program.account.purchase.fetchNullable(record.transactionId);
+              if (!receipt) continue;
+              const store = await program.account.store.fetchNullable(record.storeId);
+              const scale = await amountScale(store?.paymentMint ?? null);
+
+              // Name each line from its product account
+              const products = await program.account.product.fetchMultiple(
+                receipt.productUuids.map((uuid) =>
+                  findProductPDA(record.storeId, Uint8Array.from(uuid))
+                )
+              );
+              const items = receipt.quantities.map((quantity, i) => ({
+                name: products[i]?.name ?? "Item",
+                price: receipt.unitPrices[i].toNumber() / scale,
+                quantity: quantity.toNumber(),
+              }));
+
+              const timestamp = record.timestamp.toNumber();
+              fetchedPurchases.push({
+                id: record.transactionId.toString(),
+                storeName: store?.name ?? "SoDap Store",
+                date: new Date(timestamp * 1000).toISOString(),
+                items,
+                totalAmount: record.amount.toNumber() / scale,
+                transactionSignature: cachedPurchases.find(
+                  (p) => p.receiptAddress === record.transactionId.toString()
+                )?.transactionSignature ?? "",
+                receiptAddress: record.transactionId.toString(),
+                storeAddress: record.storeId.toString(),
+                buyerAddress: walletPublicKey.toString(),
+                purchaseTimestamp: timestamp,
+                isReturned: "refunded" in receipt.status,
               });
-              
-              // We only want to show real purchases, no demo data
-              
-              // Update state with all purchases
-              setPurchases(allPurchases);
-              
-              // Save to localStorage
-              localStorage.setItem("sodap-purchases", JSON.stringify(allPurchases));
-              console.log('Saved purchases to localStorage');
-              
-              setIsLoading(false);
-              return;
-            } else {
-              // If no purchases were found in blockchain transactions
-              console.log('No purchases found in blockchain');
-              // Keep using cached purchases if available, otherwise show empty state
-              if (cachedPurchases.length === 0) {
-                setPurchases([]);
-              }
+            } catch (err) {
+              console.error(`Error loading receipt ${record.transactionId.toString()}:`, err);
             }
+          }
+
+          if (fetchedPurchases.length > 0) {
+            console.log(`Loaded ${fetchedPurchases.length} purchases from chain`);
+
+            // Newest first, then any cached purchases older than the ring buffer
+            fetchedPurchases.sort((a, b) => b.purchaseTimestamp - a.purchaseTimestamp);
+            const allPurchases = [...fetchedPurchases];
+            cachedPurchases.forEach(existing => {
+              if (!allPurchases.some(p => p.receiptAddress === existing.receiptAddress)) {
+                allPurchases.push(existing);
+              }
+            });
+
+            setPurchases(allPurchases);
+            localStorage.setItem("sodap-purchases", JSON.stringify(allPurchases));
+            console.log('Saved purchases to localStorage');
+
+            setIsLoading(false);
+            return;
           } else {
-            // If no signatures were found
-            console.log('No signatures found for wallet');
+            console.log('No on-chain purchase history found');
             // Keep using cached purchases if available, otherwise show empty state
             if (cachedPurchases.length === 0) {
               setPurchases([]);
@@ -197,7 +187,7 @@ export const usePurchaseHistory = () => {
           }
         } catch (err) {
           console.error('Error fetching from blockchain:', err);
-          // Continue to use cached or demo data
+          // Continue to use cached data
           if (cachedPurchases.length === 0) {
             setPurchases([]);
           }
@@ -218,7 +208,7 @@ export const usePurchaseHistory = () => {
     } finally {
       setIsLoading(false);
     }
-  }, [connection, walletAddress, generateDemoPurchases]);
+  }, [program, walletAddress, generateDemoPurchases]);
 
   // Fetch purchases on mount and when wallet changes
   useEffect(() => {
@@ -52,7 +52,30 @@ export function findReceiptPDA(
   return pda;
 }
 
+/**
+ * Find a Product PDA from its 16-byte UUID
+ */
+export function findProductPDA(
+  storePubkey: PublicKey,
+  productUuid: Uint8Array
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("product"), storePubkey.toBuffer(), productUuid],
+    PROGRAM_ID
+  );
+  return pda;
+}
 
+/**
+ * Find the canonical UserProfile PDA, which holds the on-chain purchase history
+ */
+export function findUserProfilePDA(authorityPubkey: PublicKey): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("user_profile"), authorityPubkey.toBuffer()],
+    PROGRAM_ID
+  );
+  return pda;
+}
 
 /**
  * Convert lamports to SOL (as a number)
@@ -42,6 +42,20 @@ export const testInitializeTransaction = async (
   }
 };
 
+/**
+ * Per-wallet salt for profile commitments, kept in localStorage
+ */
+const getProfileSalt = (wallet: PublicKey): Uint8Array => {
+  const key = `sodap-profile-salt-${wallet.toBase58()}`;
+  const stored = localStorage.getItem(key);
+  if (stored) {
+    return Uint8Array.from(JSON.parse(stored));
+  }
+  const salt = crypto.getRandomValues(new Uint8Array(32));
+  localStorage.setItem(key, JSON.stringify(Array.from(salt)));
+  return salt;
+};
+
 /**
  * Test user profile creation
  * This calls the createOrUpdateUserProfile instruction
@@ -55,17 +69,32 @@ export const testCreateUserProfile = async (
   try {
     console.log("Creating user profile transaction...");
 
+    // Contact fields go on chain as sha256(salt || value); the salt stays
+    // in this browser so the user can later prove what they committed to
+    const salt = getProfileSalt(wallet);
+    const commit = async (value: string) =>
+      Array.from(
+        new Uint8Array(
+          await crypto.subtle.digest(
+            "SHA-256",
+            new Uint8Array([...salt, ...new TextEncoder().encode(value)])
+          )
+        )
+      );
+
     // Create the transaction
     const tx = await program.methods
       .createOrUpdateUserProfile(
         null, // userId (optional)
-        name,
-        email,
-        null // phone (optional)
+        await commit(name),
+        await commit(email),
+        null, // phone (optional)
+        null, // delivery address (optional)
+        null // preferred store (optional)
       )
       // Use the new accounts() builder that only requires non-resolvable accounts
       .accounts({
-        payer: wallet
+        authority: wallet
       })
       .rpc();
 
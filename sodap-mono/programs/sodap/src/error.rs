@@ -76,4 +76,68 @@ pub enum CustomError {
     InvalidLoyaltyPoints,
     #[msg("Transfer hook error")]
     TransferHookError,
+    #[msg("Product name is empty or too long")]
+    InvalidProductName,
+    #[msg("Description too long")]
+    DescriptionTooLong,
+    #[msg("Too many product attributes")]
+    TooManyAttributes,
+    #[msg("Invalid product attribute")]
+    InvalidAttribute,
+    #[msg("Payment exceeds cart total")]
+    Overpayment,
+    #[msg("Insufficient escrow balance")]
+    InsufficientEscrowBalance,
+    #[msg("Refund exceeds the amount paid for this purchase")]
+    RefundExceedsPayment,
+    #[msg("Refund exceeds the quantity bought on this line")]
+    RefundExceedsQuantity,
+    #[msg("Return request is not in a valid state for this action")]
+    InvalidReturnStatus,
+    #[msg("Payout account is not the store's payout address")]
+    InvalidPayoutAddress,
+    #[msg("No escrowed funds are releasable for this purchase yet")]
+    NothingToRelease,
+    #[msg("Token account mint does not match the store's payment mint")]
+    InvalidPaymentMint,
+    #[msg("Token accounts are required for stores priced in an SPL mint")]
+    TokenAccountsRequired,
+    #[msg("Token account is not owned by the expected wallet")]
+    InvalidTokenAccount,
+    #[msg("Escrow must be empty before changing the payment mint")]
+    EscrowNotEmpty,
+    #[msg("Token mint does not match this product or receipt line")]
+    InvalidProductToken,
+    #[msg("Token accounts are required for NonFungible and Fungible products")]
+    ProductTokenAccountsRequired,
+    #[msg("Product tokens must be returned to escrow before this line is refunded")]
+    ProductTokensNotReturned,
+    #[msg("Product is not a Fungible tokenized product")]
+    NotFungibleProduct,
+    #[msg("Purchase is flagged and held until a store admin clears it")]
+    PurchaseOnHold,
+    #[msg("Profile field is empty or too long")]
+    InvalidProfileField,
+    #[msg("Cart session has expired")]
+    CartSessionExpired,
+    #[msg("Barcode must be 8 to 14 digits")]
+    InvalidBarcode,
+    #[msg("Another product in this store already uses this barcode")]
+    DuplicateBarcode,
+    #[msg("SKU index account does not match the product's barcode")]
+    InvalidSkuIndex,
+    #[msg("Reservation does not belong to this buyer's cart")]
+    InvalidReservation,
+    #[msg("Refund exceeds the store's limit for this admin")]
+    RefundLimitExceeded,
+    #[msg("Store role does not exist")]
+    RoleNotFound,
+    #[msg("Too many store roles")]
+    TooManyRoles,
+    #[msg("Purchase counter account required to return redeemed loyalty points")]
+    PurchaseCounterRequired,
+    #[msg("Reservation has expired; release it before reserving again")]
+    ReservationExpired,
+    #[msg("Reservation would exceed the per-buyer limit for this product")]
+    ReservationLimitExceeded,
 }
@@ -0,0 +1,180 @@
+use crate::error::CustomError;
+use crate::instructions::product::complete_purchase;
+use crate::instructions::reservation::reserved_for_buyer;
+use crate::state::cart::{
+    CartItemRemoved, CartItemScanned, CartSession, CartSessionClosed, CheckoutCartSession,
+    CloseCartSession, RemoveCartItem, ScanCartItem,
+};
+use crate::state::product::Purchase;
+use crate::types::TokenizedType;
+use anchor_lang::prelude::*;
+
+/// Add `quantity` units of a scanned product to the buyer's cart session,
+/// opening the session on the first scan. A line's price is locked when it
+/// is first scanned. Every scan pushes the session's expiry back, up to the
+/// session's maximum lifetime.
+pub fn scan_cart_item(
+    ctx: Context<ScanCartItem>,
+    product_uuid: [u8; 16],
+    quantity: u64,
+) -> Result<()> {
+    let store = &ctx.accounts.store;
+    let product = &ctx.accounts.product;
+    require!(store.is_active, CustomError::StoreInactive);
+    require!(product.is_active, CustomError::ProductNotFound);
+    require!(quantity > 0, CustomError::InvalidCart);
+
+    let now = Clock::get()?.unix_timestamp;
+    let session = &mut ctx.accounts.cart_session;
+    if session.is_expired(now) {
+        // New, or abandoned and reopened
+        session.clear();
+        session.store = store.key();
+        session.buyer = ctx.accounts.buyer.key();
+        session.bump = ctx.bumps.cart_session;
+        session.created_at = now;
+    }
+
+    let line = match session.product_uuids.iter().position(|uuid| *uuid == product_uuid) {
+        Some(line) => line,
+        None => {
+            require!(
+                session.product_uuids.len() < Purchase::MAX_PRODUCTS,
+                CustomError::CartTooLarge
+            );
+            session.product_uuids.push(product_uuid);
+            session.quantities.push(0);
+            session.unit_prices.push(product.price);
+            session.product_uuids.len() - 1
+        }
+    };
+    let line_quantity = session.quantities[line]
+        .checked_add(quantity)
+        .ok_or(CustomError::ArithmeticError)?;
+    // Each certificate is 1-of-1, so NonFungible lines carry a single unit
+    require!(
+        product.tokenized_type != TokenizedType::NonFungible || line_quantity == 1,
+        CustomError::InvalidCart
+    );
+    let available = product
+        .stock
+        .checked_add(reserved_for_buyer(&ctx.accounts.reservation)?)
+        .ok_or(CustomError::ArithmeticError)?;
+    require!(available >= line_quantity, CustomError::InsufficientStock);
+    session.quantities[line] = line_quantity;
+    let ends_at = session
+        .created_at
+        .checked_add(CartSession::MAX_LIFETIME)
+        .ok_or(CustomError::ArithmeticError)?;
+    session.expires_at = now
+        .checked_add(CartSession::TTL)
+        .ok_or(CustomError::ArithmeticError)?
+        .min(ends_at);
+
+    emit!(CartItemScanned {
+        store_id: session.store,
+        buyer_id: session.buyer,
+        product_uuid,
+        quantity: line_quantity,
+        unit_price: session.unit_prices[line],
+        cart_total: session.total()?,
+        expires_at: session.expires_at,
+    });
+
+    Ok(())
+}
+
+/// Take `quantity` units of a product back out of the cart session, dropping
+/// the line once it is empty
+pub fn remove_cart_item(
+    ctx: Context<RemoveCartItem>,
+    product_uuid: [u8; 16],
+    quantity: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let session = &mut ctx.accounts.cart_session;
+    require!(!session.is_expired(now), CustomError::CartSessionExpired);
+    require!(quantity > 0, CustomError::InvalidCart);
+
+    let line = session
+        .product_uuids
+        .iter()
+        .position(|uuid| *uuid == product_uuid)
+        .ok_or(CustomError::ProductNotFound)?;
+    let remaining = session.quantities[line]
+        .checked_sub(quantity)
+        .ok_or(CustomError::InvalidCart)?;
+    if remaining == 0 {
+        session.product_uuids.remove(line);
+        session.quantities.remove(line);
+        session.unit_prices.remove(line);
+    } else {
+        session.quantities[line] = remaining;
+    }
+
+    emit!(CartItemRemoved {
+        store_id: session.store,
+        buyer_id: session.buyer,
+        product_uuid,
+        quantity: remaining,
+        cart_total: session.total()?,
+    });
+
+    Ok(())
+}
+
+/// Pay for the cart session at its locked prices and close it. Remaining
+/// accounts are laid out as for `purchase_cart`, in session line order.
+pub fn checkout_cart_session<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CheckoutCartSession<'info>>,
+    total_amount_paid: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let session = &ctx.accounts.cart_session;
+    require!(!session.is_expired(now), CustomError::CartSessionExpired);
+    require!(!session.product_uuids.is_empty(), CustomError::InvalidCart);
+
+    let product_uuids = session.product_uuids.clone();
+    let quantities = session.quantities.clone();
+    let unit_prices = session.unit_prices.clone();
+    complete_purchase(
+        &mut ctx.accounts.purchase,
+        &ctx.bumps.purchase,
+        ctx.remaining_accounts,
+        product_uuids,
+        quantities,
+        Some(&unit_prices),
+        total_amount_paid,
+    )?;
+
+    emit!(CartSessionClosed {
+        store_id: session.store,
+        buyer_id: session.buyer,
+        expired: false,
+        timestamp: now,
+    });
+
+    let buyer = ctx.accounts.purchase.buyer.to_account_info();
+    ctx.accounts.cart_session.close(buyer)
+}
+
+/// Close a cart session and return its rent to the buyer. The buyer can
+/// abandon their session at any time; anyone can close it once expired.
+pub fn close_cart_session(ctx: Context<CloseCartSession>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let session = &ctx.accounts.cart_session;
+    let expired = session.is_expired(now);
+    require!(
+        expired || ctx.accounts.closer.key() == session.buyer,
+        CustomError::Unauthorized
+    );
+
+    emit!(CartSessionClosed {
+        store_id: session.store,
+        buyer_id: session.buyer,
+        expired,
+        timestamp: now,
+    });
+
+    Ok(())
+}
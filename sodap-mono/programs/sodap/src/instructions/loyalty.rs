@@ -0,0 +1,162 @@
+use crate::error::CustomError;
+pub use crate::state::loyalty::{
+    InitializeLoyalty, LoyaltyConfig, LoyaltyConfigUpdated, LoyaltyPointsRedeemed,
+    RedeemLoyaltyPoints, UpdateLoyaltyConfig,
+};
+use crate::types::Capabilities;
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Burn, Mint, MintTo, TokenAccount, TokenInterface};
+
+/// Start a store's loyalty program and create its points mint
+pub fn initialize_loyalty(ctx: Context<InitializeLoyalty>, earn_rate_bps: u16) -> Result<()> {
+    require!(
+        ctx.accounts.store.can(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_STORE,
+        ),
+        CustomError::Unauthorized
+    );
+    require!(
+        earn_rate_bps <= LoyaltyConfig::MAX_EARN_RATE_BPS,
+        CustomError::InvalidLoyaltyConfig
+    );
+
+    let config = &mut ctx.accounts.loyalty_config;
+    config.store = ctx.accounts.store.key();
+    config.mint = ctx.accounts.loyalty_mint.key();
+    config.earn_rate_bps = earn_rate_bps;
+    config.is_active = true;
+    config.bump = ctx.bumps.loyalty_config;
+
+    emit!(LoyaltyConfigUpdated {
+        store_id: config.store,
+        mint: config.mint,
+        earn_rate_bps,
+        is_active: true,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+pub fn update_loyalty_config(
+    ctx: Context<UpdateLoyaltyConfig>,
+    earn_rate_bps: Option<u16>,
+    is_active: Option<bool>,
+) -> Result<()> {
+    require!(
+        ctx.accounts.store.can(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_STORE,
+        ),
+        CustomError::Unauthorized
+    );
+    let config = &mut ctx.accounts.loyalty_config;
+
+    if let Some(earn_rate_bps) = earn_rate_bps {
+        require!(
+            earn_rate_bps <= LoyaltyConfig::MAX_EARN_RATE_BPS,
+            CustomError::InvalidLoyaltyConfig
+        );
+        config.earn_rate_bps = earn_rate_bps;
+    }
+    if let Some(is_active) = is_active {
+        config.is_active = is_active;
+    }
+
+    emit!(LoyaltyConfigUpdated {
+        store_id: config.store,
+        mint: config.mint,
+        earn_rate_bps: config.earn_rate_bps,
+        is_active: config.is_active,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Burn points for a discount on the buyer's next checkout at this store.
+/// The discount is held on the buyer's purchase counter until it is used.
+pub fn redeem_loyalty_points(ctx: Context<RedeemLoyaltyPoints>, points: u64) -> Result<()> {
+    require!(points > 0, CustomError::InvalidLoyaltyPoints);
+    require!(
+        ctx.accounts.loyalty_config.is_active,
+        CustomError::LoyaltyProgramInactive
+    );
+    require!(
+        ctx.accounts.buyer_loyalty_account.amount >= points,
+        CustomError::InsufficientLoyaltyPoints
+    );
+
+    token_interface::burn(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.loyalty_mint.to_account_info(),
+                from: ctx.accounts.buyer_loyalty_account.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            },
+        ),
+        points,
+    )?;
+
+    let store_key = ctx.accounts.store.key();
+    let counter = &mut ctx.accounts.purchase_counter;
+    counter.store = store_key;
+    counter.buyer = ctx.accounts.buyer.key();
+    counter.bump = ctx.bumps.purchase_counter;
+    counter.loyalty_discount = counter
+        .loyalty_discount
+        .checked_add(points)
+        .ok_or(CustomError::ArithmeticError)?;
+
+    emit!(LoyaltyPointsRedeemed {
+        store_id: store_key,
+        buyer: counter.buyer,
+        points,
+        pending_discount: counter.loyalty_discount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Mint the points a buyer earned on `amount_paid`. Returns the points
+/// minted, which is zero when the program is inactive or the rate rounds down.
+pub fn award_points<'info>(
+    config: &Account<'info, LoyaltyConfig>,
+    mint: &InterfaceAccount<'info, Mint>,
+    destination: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+    buyer: &Pubkey,
+    amount_paid: u64,
+) -> Result<u64> {
+    require_keys_eq!(mint.key(), config.mint, CustomError::LoyaltyMintNotFound);
+    require_keys_eq!(destination.mint, config.mint, CustomError::LoyaltyMintNotFound);
+    require_keys_eq!(destination.owner, *buyer, CustomError::InvalidTokenAccount);
+
+    if !config.is_active {
+        return Ok(0);
+    }
+    let points = config.points_for(amount_paid)?;
+    if points == 0 {
+        return Ok(0);
+    }
+
+    let store = config.store;
+    let signer_seeds: &[&[&[u8]]] = &[&[b"loyalty_config", store.as_ref(), &[config.bump]]];
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: mint.to_account_info(),
+                to: destination.to_account_info(),
+                authority: config.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        points,
+    )?;
+    Ok(points)
+}
@@ -1,13 +1,27 @@
 // Submodules for instruction handlers
 pub mod admin;
+pub mod cart;
+pub mod loyalty;
 pub mod product;
+pub mod reservation;
+pub mod returns;
+pub mod risk;
+pub mod roles;
 pub mod store;
 pub mod user;
 pub mod user_wallet;
+pub mod voucher;
 
 // Re-export for easier use in lib.rs
 pub use admin::*;
+pub use cart::*;
+pub use loyalty::*;
 pub use product::*;
+pub use reservation::*;
+pub use returns::*;
+pub use risk::*;
+pub use roles::*;
 pub use store::*;
 pub use user::*;
 pub use user_wallet::*;
+pub use voucher::*;
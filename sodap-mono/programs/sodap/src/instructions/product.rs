@@ -1,19 +1,36 @@
 use crate::error::CustomError;
 pub use crate::state::product::{
-    CartPurchased, DeactivateProduct, Product, PurchaseCart, RegisterProduct, UpdateProduct,
+    CartPurchased, DeactivateProduct, EscrowReleased, Product, ProductRegistered, Purchase,
+    PurchaseCart, PurchaseCartBumps, PurchaseRefunded, RegisterProduct, SkuIndex,
+    SkuIndexUpdated, UpdateProduct,
 };
 use crate::state::store::Store;
-use crate::state::{Escrow, RefundEscrow, ReleaseEscrow};
-use crate::types::{TokenizedType, TransactionStatus};
+use crate::state::{
+    CrankBudgetFunded, Escrow, FundCrankBudget, PurchaseFlagged, PurchasesSettled,
+    RefundPurchase, ReleaseEscrow, SettleMaturedPurchases,
+};
+use crate::utils::certificate::{mint_certificate, ProductTokenPrograms};
+use crate::utils::escrow::{
+    burn_returned_tokens, pay_crank_tip, pay_into_escrow, pay_out_of_escrow, TokenPayment,
+};
+use crate::instructions::loyalty::award_points;
+use crate::instructions::reservation::take_stock;
+use crate::instructions::risk::evaluate_purchase_risk;
+use crate::instructions::user::record_purchase_history;
+use crate::instructions::voucher::mint_vouchers;
+use crate::state::user::PurchaseRecord;
+use crate::types::{
+    AnomalyFlag, Capabilities, ProductAttribute, TokenizedType, TransactionStatus,
+};
 use anchor_lang::prelude::*;
-use anchor_lang::system_program::{self, Transfer};
+use anchor_lang::system_program;
 
 #[derive(Accounts)]
 pub struct StoreEscrow<'info> {
     #[account(
         init_if_needed,
         payer = payer,
-        space = 8 + 32 + 8, // store pubkey + balance
+        space = Escrow::LEN,
         seeds = [b"escrow", store.key().as_ref()],
         bump
     )]
@@ -25,14 +42,35 @@ pub struct StoreEscrow<'info> {
 }
 
 // Product instructions
+#[allow(clippy::too_many_arguments)]
 pub fn register_product(
     ctx: Context<RegisterProduct>,
     product_uuid: [u8; 16],
+    name: String,
+    description: String,
+    image_uri: String,
     price: u64,
     stock: u64,
     tokenized_type: TokenizedType,
     metadata_uri: String,
+    attributes: Vec<ProductAttribute>,
+    barcode: Option<String>,
 ) -> Result<()> {
+    let store = &ctx.accounts.store;
+    let authority = &ctx.accounts.authority;
+
+    require!(
+        store.can(
+            &authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_PRODUCTS,
+        ),
+        CustomError::Unauthorized
+    );
+    require!(store.is_active, CustomError::StoreInactive);
+    require!(price > 0, CustomError::InvalidPrice);
+
     let product = &mut ctx.accounts.product;
     product.uuid = product_uuid;
     product.price = price;
@@ -40,222 +78,872 @@ pub fn register_product(
     product.tokenized_type = tokenized_type;
     product.metadata_uri = metadata_uri;
     product.is_active = true;
-    product.store = ctx.accounts.store.key();
-    product.authority = ctx.accounts.authority.key();
+    product.store = store.key();
+    product.authority = authority.key();
+    product.name = name;
+    product.description = description;
+    product.image_uri = image_uri;
+    product.attributes = attributes;
+    product.bump = ctx.bumps.product;
+    product.certificates_minted = 0;
+    product.barcode = barcode.unwrap_or_default();
+    product.reserved = 0;
+    product.validate()?;
+
+    let now = Clock::get()?.unix_timestamp;
+    emit!(ProductRegistered {
+        store_id: product.store,
+        product: product.key(),
+        product_uuid,
+        price,
+        stock,
+        registered_by: product.authority,
+        created_at: now,
+    });
 
-    Ok(())
+    index_barcode(
+        product,
+        ctx.accounts.sku_index.as_mut(),
+        ctx.bumps.sku_index,
+        now,
+    )
 }
 
+/// Update a product's listing. Only the fields passed are changed. Changing
+/// the barcode moves its SkuIndex: the old one is closed and a new one opened.
+#[allow(clippy::too_many_arguments)]
 pub fn update_product(
     ctx: Context<UpdateProduct>,
     _product_uuid: [u8; 16],
-    new_price: Option<u64>,
-    new_stock: Option<u64>,
-    new_metadata_uri: Option<String>,
-    new_tokenized_type: Option<TokenizedType>,
+    name: Option<String>,
+    description: Option<String>,
+    image_uri: Option<String>,
+    price: Option<u64>,
+    stock: Option<u64>,
+    attributes: Option<Vec<ProductAttribute>>,
+    barcode: Option<String>,
 ) -> Result<()> {
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::MANAGE_PRODUCTS,
+            ),
+        CustomError::Unauthorized
+    );
     let product = &mut ctx.accounts.product;
 
-    if let Some(price) = new_price {
+    if let Some(name) = name {
+        product.name = name;
+    }
+    if let Some(description) = description {
+        product.description = description;
+    }
+    if let Some(image_uri) = image_uri {
+        product.image_uri = image_uri;
+    }
+    if let Some(price) = price {
+        require!(price > 0, CustomError::InvalidPrice);
         product.price = price;
     }
-
-    if let Some(stock) = new_stock {
+    if let Some(stock) = stock {
         product.stock = stock;
     }
-
-    if let Some(metadata_uri) = new_metadata_uri {
-        product.metadata_uri = metadata_uri;
-    }
-
-    if let Some(tokenized_type) = new_tokenized_type {
-        product.tokenized_type = tokenized_type;
+    if let Some(attributes) = attributes {
+        product.attributes = attributes;
     }
 
-    Ok(())
+    // Index accounts are closed or initialized on exit, so they may only be
+    // passed when the barcode actually changes
+    let barcode = barcode.filter(|barcode| *barcode != product.barcode);
+    let Some(barcode) = barcode else {
+        require!(
+            ctx.accounts.sku_index.is_none() && ctx.accounts.new_sku_index.is_none(),
+            CustomError::InvalidSkuIndex
+        );
+        return product.validate();
+    };
+    require!(
+        ctx.accounts.sku_index.is_some() != product.barcode.is_empty(),
+        CustomError::InvalidSkuIndex
+    );
+    product.barcode = barcode;
+    product.validate()?;
+
+    index_barcode(
+        product,
+        ctx.accounts.new_sku_index.as_mut(),
+        ctx.bumps.new_sku_index,
+        Clock::get()?.unix_timestamp,
+    )
 }
 
+/// Take a product off sale, closing its SkuIndex so the barcode can be reused
 pub fn deactivate_product(ctx: Context<DeactivateProduct>, _product_uuid: [u8; 16]) -> Result<()> {
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::MANAGE_PRODUCTS,
+            ),
+        CustomError::Unauthorized
+    );
     let product = &mut ctx.accounts.product;
+    require!(
+        ctx.accounts.sku_index.is_some() != product.barcode.is_empty(),
+        CustomError::InvalidSkuIndex
+    );
     product.is_active = false;
+
+    if !product.barcode.is_empty() {
+        product.barcode.clear();
+        emit!(SkuIndexUpdated {
+            store_id: product.store,
+            product: product.key(),
+            barcode: String::new(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+    Ok(())
+}
+
+/// Point the SkuIndex for `product.barcode` at the product. The index must be
+/// passed exactly when the product has a barcode, and must not already
+/// belong to another product.
+fn index_barcode(
+    product: &Account<Product>,
+    sku_index: Option<&mut Account<SkuIndex>>,
+    bump: Option<u8>,
+    now: i64,
+) -> Result<()> {
+    let (Some(sku_index), Some(bump)) = (sku_index, bump) else {
+        require!(product.barcode.is_empty(), CustomError::InvalidSkuIndex);
+        return Ok(());
+    };
+    require!(!product.barcode.is_empty(), CustomError::InvalidSkuIndex);
+    require!(
+        sku_index.product == Pubkey::default(),
+        CustomError::DuplicateBarcode
+    );
+
+    sku_index.store = product.store;
+    sku_index.product = product.key();
+    sku_index.product_uuid = product.uuid;
+    sku_index.bump = bump;
+
+    emit!(SkuIndexUpdated {
+        store_id: product.store,
+        product: product.key(),
+        barcode: product.barcode.clone(),
+        timestamp: now,
+    });
     Ok(())
 }
 
-/// Validate product cart items against remaining accounts and calculate total
-fn validate_cart_and_payment<'a, 'b>(
-    product_uuids: &'a [[u8; 16]],
-    quantities: &'a [u64],
-    remaining_accounts: &'b [AccountInfo<'b>],
+/// Load a product passed in remaining accounts, checking that it is this
+/// store's canonical `[b"product", store, uuid]` PDA and that it is writable.
+fn load_store_product<'info>(
+    store: &Pubkey,
+    acc_info: &'info AccountInfo<'info>,
+    product_uuid: &[u8; 16],
+) -> Result<Account<'info, Product>> {
+    require!(acc_info.is_writable, CustomError::InvalidParameters);
+    let product = Account::<Product>::try_from(acc_info)?;
+    require!(product.uuid == *product_uuid, CustomError::ProductNotFound);
+    require_keys_eq!(product.store, *store, CustomError::InvalidStore);
+
+    let expected = Pubkey::create_program_address(
+        &[
+            b"product",
+            store.as_ref(),
+            product.uuid.as_ref(),
+            &[product.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| CustomError::ProductNotFound)?;
+    require_keys_eq!(acc_info.key(), expected, CustomError::ProductNotFound);
+
+    Ok(product)
+}
+
+/// A cart priced by `validate_cart_and_payment`
+struct PricedCart<'info> {
+    total_price: u64,
+    discount: u64,                          // applied, at most total_price
+    products: Vec<Account<'info, Product>>, // in cart order
+    unit_prices: Vec<u64>,                  // charged per line
+}
+
+/// Validate product cart items against remaining accounts and calculate total.
+fn validate_cart_and_payment<'info>(
+    store: &Pubkey,
+    product_uuids: &[[u8; 16]],
+    quantities: &[u64],
+    locked_prices: Option<&[u64]>,
+    remaining_accounts: &'info [AccountInfo<'info>],
     total_amount_paid: u64,
-) -> Result<u64> {
+    discount: u64,
+) -> Result<PricedCart<'info>> {
     require!(
         product_uuids.len() == quantities.len() && !product_uuids.is_empty(),
         CustomError::InvalidCart
     );
+    require!(
+        product_uuids.len() <= Purchase::MAX_PRODUCTS,
+        CustomError::CartTooLarge
+    );
+    require!(
+        remaining_accounts.len() >= product_uuids.len(),
+        CustomError::ProductNotFound
+    );
 
     let mut total_price = 0u64;
+    let mut products = Vec::with_capacity(product_uuids.len());
+    let mut unit_prices = Vec::with_capacity(product_uuids.len());
     let mut i = 0;
     while i < product_uuids.len() {
-        let acc_info = &remaining_accounts[i];
-        let product = Account::<Product>::try_from(acc_info)?;
+        // The same product twice would be decremented from a stale copy
         require!(
-            product.uuid == product_uuids[i],
-            CustomError::ProductNotFound
+            !product_uuids[..i].contains(&product_uuids[i]),
+            CustomError::InvalidCart
         );
+        require!(quantities[i] > 0, CustomError::InvalidCart);
+
+        let product = load_store_product(store, &remaining_accounts[i], &product_uuids[i])?;
         require!(product.is_active, CustomError::ProductNotFound);
+        // Each certificate is 1-of-1, so NonFungible lines carry a single unit
         require!(
-            product.stock >= quantities[i],
-            CustomError::InsufficientStock
+            product.tokenized_type != TokenizedType::NonFungible || quantities[i] == 1,
+            CustomError::InvalidCart
         );
-
         // Calculate price for this item
-        let item_total = product
-            .price
+        let unit_price = locked_prices.map_or(product.price, |prices| prices[i]);
+        let item_total = unit_price
             .checked_mul(quantities[i])
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or(CustomError::PriceOverflow)?;
         total_price = total_price
             .checked_add(item_total)
-            .ok_or(CustomError::ArithmeticError)?;
+            .ok_or(CustomError::PriceOverflow)?;
 
+        products.push(product);
+        unit_prices.push(unit_price);
         i += 1;
     }
 
-    // Verify payment amount matches cart total
+    // Verify payment matches the cart total, less any discount, exactly
+    let applied_discount = discount.min(total_price);
+    let amount_due = total_price - applied_discount;
     require!(
-        total_amount_paid >= total_price,
+        total_amount_paid >= amount_due,
         CustomError::InsufficientPayment
     );
-
-    Ok(total_price)
+    require!(total_amount_paid == amount_due, CustomError::Overpayment);
+
+    Ok(PricedCart {
+        total_price,
+        discount: applied_discount,
+        products,
+        unit_prices,
+    })
 }
 
 pub fn purchase_cart<'info>(
-    ctx: Context<'_, '_, 'info, 'info, PurchaseCart>,
+    ctx: Context<'_, '_, 'info, 'info, PurchaseCart<'info>>,
     product_uuids: Vec<[u8; 16]>,
     quantities: Vec<u64>,
     total_amount_paid: u64,
-    gas_fee: u64,
-    status: TransactionStatus,
 ) -> Result<()> {
-    let remaining_accounts: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+    complete_purchase(
+        ctx.accounts,
+        &ctx.bumps,
+        ctx.remaining_accounts,
+        product_uuids,
+        quantities,
+        None,
+        total_amount_paid,
+    )
+}
 
-    // Validate cart and get total price
-    let total_price = validate_cart_and_payment(
+/// Price, pay for and record a cart. `locked_prices` holds per-line unit
+/// prices fixed earlier, by a cart session; without them the products'
+/// current prices are charged.
+pub(crate) fn complete_purchase<'info>(
+    accounts: &mut PurchaseCart<'info>,
+    bumps: &PurchaseCartBumps,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    product_uuids: Vec<[u8; 16]>,
+    quantities: Vec<u64>,
+    locked_prices: Option<&[u64]>,
+    total_amount_paid: u64,
+) -> Result<()> {
+    let store_key = accounts.store.key();
+    require!(accounts.store.is_active, CustomError::StoreInactive);
+
+    // Price the cart from the on-chain products, never from the client
+    // Redeemed loyalty points are applied before payment
+    let PricedCart {
+        total_price,
+        discount: loyalty_discount,
+        mut products,
+        unit_prices,
+    } = validate_cart_and_payment(
+        &store_key,
         &product_uuids,
         &quantities,
+        locked_prices,
         remaining_accounts,
         total_amount_paid,
+        accounts.purchase_counter.loyalty_discount,
     )?;
-
-    // Transfer payment from buyer to escrow account
-    let escrow_seeds = &[
-        b"escrow",
-        ctx.accounts.store.to_account_info().key.as_ref(),
-        &[ctx.bumps.escrow_account],
-    ];
-    let signer_seeds = &[&escrow_seeds[..]];
-
-    let transfer_to_escrow = CpiContext::new(
-        ctx.accounts.system_program.to_account_info(),
-        system_program::Transfer {
-            from: ctx.accounts.buyer.to_account_info(),
-            to: ctx.accounts.escrow_account.to_account_info(),
+    let amount_due = total_price - loyalty_discount;
+
+    // Transfer payment from buyer to escrow, in SOL or the store's mint
+    // The receipt records what escrow received, net of any transfer fee
+    accounts.escrow_account.store = store_key;
+    let received = pay_into_escrow(
+        &mut accounts.escrow_account,
+        accounts.store.payment_mint,
+        &accounts.buyer,
+        &accounts.system_program,
+        &TokenPayment {
+            mint: accounts.mint.as_ref(),
+            escrow_vault: accounts.escrow_vault.as_ref(),
+            wallet_token_account: accounts.buyer_token_account.as_ref(),
+            token_program: accounts.token_program.as_ref(),
+            extra_accounts: remaining_accounts,
         },
-    );
-    system_program::transfer(transfer_to_escrow, total_price)?;
-
-    // Update escrow balance
-    let escrow = &mut ctx.accounts.escrow_account;
-    escrow.balance = escrow
-        .balance
-        .checked_add(total_price)
-        .ok_or(CustomError::ArithmeticError)?;
+        amount_due,
+    )?;
 
-    // Update product stocks
-    let mut i = 0;
-    while i < product_uuids.len() {
-        let product_account = &mut Account::<Product>::try_from(&remaining_accounts[i])?;
-        product_account.stock = product_account
-            .stock
-            .checked_sub(quantities[i])
-            .ok_or(CustomError::StockUnderflow)?;
-        i += 1;
+    // Mint a certificate for each NonFungible line and vouchers for each
+    // Fungible line. Their accounts follow the products in remaining
+    // accounts, as [mint, buyer token account] pairs
+    let mut token_mints = vec![Pubkey::default(); products.len()];
+    let mut next = products.len();
+    for (line, product) in products.iter_mut().enumerate() {
+        if product.tokenized_type == TokenizedType::None {
+            continue;
+        }
+        let (Some(token_program), Some(associated_token_program)) = (
+            accounts.product_token_program.as_ref(),
+            accounts.associated_token_program.as_ref(),
+        ) else {
+            return err!(CustomError::ProductTokenAccountsRequired);
+        };
+        require!(
+            remaining_accounts.len() >= next + 2,
+            CustomError::ProductTokenAccountsRequired
+        );
+        let programs = ProductTokenPrograms {
+            token_program,
+            associated_token_program,
+            system_program: &accounts.system_program,
+        };
+        let mint = &remaining_accounts[next];
+        let buyer_account = &remaining_accounts[next + 1];
+        token_mints[line] = if product.tokenized_type == TokenizedType::NonFungible {
+            mint_certificate(product, mint, buyer_account, &accounts.buyer, &programs)?
+        } else {
+            mint_vouchers(
+                product,
+                mint,
+                buyer_account,
+                &accounts.buyer,
+                &programs,
+                quantities[line],
+            )?
+        };
+        next += 2;
     }
 
+    // Take the units from the buyer's reservations, then stock. Reservations
+    // are our accounts after the token pairs; other accounts there are
+    // transfer-hook extras
+    let reservations: Vec<_> = remaining_accounts[next..]
+        .iter()
+        .filter(|info| info.owner == &crate::ID)
+        .collect();
+    take_stock(
+        &mut products,
+        &quantities,
+        &reservations,
+        &accounts.buyer.to_account_info(),
+    )?;
+
+    // Claim the next receipt index for this buyer
+    let now = Clock::get()?.unix_timestamp;
+    let counter = &mut accounts.purchase_counter;
+    counter.store = store_key;
+    counter.buyer = accounts.buyer.key();
+    counter.bump = bumps.purchase_counter;
+    counter.loyalty_discount -= loyalty_discount;
+    let purchase_index = counter.count;
+    counter.count = counter
+        .count
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticError)?;
+    let purchases_this_hour = counter.record_purchase_in_window(now);
+
+    // Check the purchase against the store's risk rules, if it has any
+    let (reasons, held) = evaluate_purchase_risk(
+        &accounts.risk_config,
+        &accounts.buyer_profile,
+        total_price,
+        purchases_this_hour,
+        now,
+    )?;
+
     // Create receipt
-    let receipt = &mut ctx.accounts.receipt;
+    let receipt = &mut accounts.receipt;
     receipt.product_uuids = product_uuids.clone();
     receipt.quantities = quantities.clone();
-    receipt.total_paid = total_price;
-    receipt.gas_fee = gas_fee;
-    receipt.status = status;
-    receipt.store = ctx.accounts.store.key();
-    receipt.buyer = ctx.accounts.buyer.key();
-    receipt.ts = Clock::get()?.unix_timestamp;
+    receipt.unit_prices = unit_prices;
+    receipt.refunded_quantities = vec![0; quantities.len()];
+    receipt.token_mints = token_mints;
+    receipt.total_paid = received;
+    receipt.gas_fee = 0;
+    receipt.status = TransactionStatus::Completed;
+    receipt.anomaly = if reasons == 0 {
+        AnomalyFlag::None
+    } else {
+        AnomalyFlag::Suspicious
+    };
+    receipt.held = held;
+    receipt.store = store_key;
+    receipt.buyer = accounts.buyer.key();
+    receipt.ts = now;
+    receipt.loyalty_discount = loyalty_discount;
+    receipt.matures_at = now
+        .checked_add(accounts.store.dispute_window)
+        .ok_or(CustomError::ArithmeticError)?;
+    receipt.purchase_index = purchase_index;
+    receipt.bump = bumps.receipt;
+
+    // Earn points on what was actually paid, if the client opted in
+    let loyalty_points_earned = match (
+        &accounts.loyalty_config,
+        &accounts.loyalty_mint,
+        &accounts.buyer_loyalty_account,
+        &accounts.loyalty_token_program,
+    ) {
+        (Some(config), Some(mint), Some(destination), Some(token_program)) => {
+            require_keys_eq!(config.store, store_key, CustomError::InvalidLoyaltyConfig);
+            award_points(config, mint, destination, token_program, &receipt.buyer, received)?
+        }
+        (None, None, None, None) => 0,
+        _ => return err!(CustomError::InvalidLoyaltyConfig),
+    };
+
+    // Append to the buyer's on-chain purchase history
+    record_purchase_history(
+        &accounts.buyer_profile,
+        PurchaseRecord {
+            store_id: store_key,
+            transaction_id: receipt.key(),
+            amount: received,
+            loyalty_earned: loyalty_points_earned,
+            timestamp: now,
+        },
+    )?;
 
     emit!(CartPurchased {
-        store_id: ctx.accounts.store.key(),
-        buyer_id: ctx.accounts.buyer.key(),
+        store_id: store_key,
+        buyer_id: receipt.buyer,
+        receipt: receipt.key(),
+        purchase_index,
         product_uuids,
         quantities,
-        total_paid: total_price,
-        gas_fee,
+        total_paid: received,
+        transfer_fee: amount_due - received,
+        loyalty_discount,
+        loyalty_points_earned,
+        gas_fee: 0,
         timestamp: receipt.ts,
     });
 
+    if reasons != 0 {
+        emit!(PurchaseFlagged {
+            store_id: store_key,
+            receipt: receipt.key(),
+            buyer_id: receipt.buyer,
+            reasons,
+            held,
+            timestamp: now,
+        });
+    }
+
     Ok(())
 }
 
-pub fn release_escrow(ctx: Context<ReleaseEscrow>, amount: u64) -> Result<()> {
-    let escrow_info = ctx.accounts.escrow_account.to_account_info();
-    let escrow = &mut ctx.accounts.escrow_account;
+/// Release a purchase's matured escrow funds to the store's payout address.
+/// Funds unlock once the store's dispute window has passed since the purchase,
+/// and amounts held by open return requests stay in escrow.
+pub fn release_escrow<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ReleaseEscrow<'info>>,
+) -> Result<()> {
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::RELEASE_ESCROW,
+            ),
+        CustomError::Unauthorized
+    );
+    let now = Clock::get()?.unix_timestamp;
+    require!(!ctx.accounts.receipt.held, CustomError::PurchaseOnHold);
+    let amount = settle_receipt(&mut ctx.accounts.store, &mut ctx.accounts.receipt, now)?;
+    require!(amount > 0, CustomError::NothingToRelease);
+
+    pay_out_of_escrow(
+        &mut ctx.accounts.escrow_account,
+        ctx.bumps.escrow_account,
+        ctx.accounts.store.payment_mint,
+        &ctx.accounts.payout,
+        &TokenPayment {
+            mint: ctx.accounts.mint.as_ref(),
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            wallet_token_account: ctx.accounts.payout_token_account.as_ref(),
+            token_program: ctx.accounts.token_program.as_ref(),
+            extra_accounts: ctx.remaining_accounts,
+        },
+        amount,
+    )
+}
 
-    let cpi_accounts = Transfer {
-        from: escrow_info,
-        to: ctx.accounts.store_owner.to_account_info(),
-    };
+/// Settle whatever has matured on a receipt: mark it settled, book it as store
+/// revenue and emit `EscrowReleased`. Returns the amount to pay out, which is
+/// zero while the receipt is still inside the dispute window.
+fn settle_receipt(
+    store: &mut Account<Store>,
+    receipt: &mut Account<Purchase>,
+    now: i64,
+) -> Result<u64> {
+    let amount = receipt.releasable_amount(now)?;
+    if amount == 0 {
+        return Ok(0);
+    }
 
-    let cpi_program = ctx.accounts.system_program.to_account_info();
-    let store_key = ctx.accounts.store.key();
-    let seeds = &[
-        b"escrow".as_ref(),
-        store_key.as_ref(),
-        &[ctx.bumps.escrow_account],
-    ];
-    let signer_seeds = &[&seeds[..]];
+    receipt.settled_amount = receipt
+        .settled_amount
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticError)?;
+    store.revenue = store
+        .revenue
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticError)?;
 
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+    emit!(EscrowReleased {
+        store_id: store.key(),
+        receipt: receipt.key(),
+        payout_address: store.payout_address,
+        amount,
+        total_settled: receipt.settled_amount,
+        timestamp: now,
+    });
 
-    system_program::transfer(cpi_ctx, amount)?;
+    Ok(amount)
+}
+
+/// Permissionless crank. Remaining accounts are receipts of this store,
+/// followed by any transfer-hook extra accounts; the receipt list ends at the
+/// first account not owned by this program. Receipts with nothing matured are
+/// skipped so a batch never fails on one early entry. The caller is tipped `crank_tip` per settled receipt, paid
+/// from the store's crank budget only.
+pub fn settle_matured_purchases<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SettleMaturedPurchases<'info>>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let store = &mut ctx.accounts.store;
+    let remaining = ctx.remaining_accounts;
+    require!(!remaining.is_empty(), CustomError::InvalidParameters);
+
+    let mut total: u64 = 0;
+    let mut settled: u32 = 0;
+    let mut i = 0;
+    while i < remaining.len() && remaining[i].owner == &crate::ID {
+        let info = &remaining[i];
+        require!(info.is_writable, CustomError::InvalidParameters);
+        let mut receipt = Account::<Purchase>::try_from(info)?;
+        require!(receipt.store == store.key(), CustomError::InvalidStore);
+
+        let amount = settle_receipt(store, &mut receipt, now)?;
+        if amount > 0 {
+            receipt.exit(&crate::ID)?;
+            total = total
+                .checked_add(amount)
+                .ok_or(CustomError::ArithmeticError)?;
+            settled += 1;
+        }
+        i += 1;
+    }
+    require!(total > 0, CustomError::NothingToRelease);
+
+    pay_out_of_escrow(
+        &mut ctx.accounts.escrow_account,
+        ctx.bumps.escrow_account,
+        store.payment_mint,
+        &ctx.accounts.payout,
+        &TokenPayment {
+            mint: ctx.accounts.mint.as_ref(),
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            wallet_token_account: ctx.accounts.payout_token_account.as_ref(),
+            token_program: ctx.accounts.token_program.as_ref(),
+            extra_accounts: ctx.remaining_accounts,
+        },
+        total,
+    )?;
+
+    let tip = pay_crank_tip(
+        &mut ctx.accounts.escrow_account,
+        &ctx.accounts.cranker.to_account_info(),
+        store.crank_tip.saturating_mul(settled as u64),
+    )?;
 
-    escrow.balance = escrow.balance.checked_sub(amount).unwrap();
+    emit!(PurchasesSettled {
+        store_id: store.key(),
+        cranker: ctx.accounts.cranker.key(),
+        receipts_settled: settled,
+        amount: total,
+        tip,
+        timestamp: now,
+    });
 
     Ok(())
 }
 
-pub fn refund_from_escrow(ctx: Context<RefundEscrow>, amount: u64) -> Result<()> {
-    let escrow_info = ctx.accounts.escrow_account.to_account_info();
+/// Top up the store's crank budget, which pays settlement crank tips
+pub fn fund_crank_budget(ctx: Context<FundCrankBudget>, amount: u64) -> Result<()> {
+    require!(
+        ctx.accounts.store.can(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_STORE,
+        ),
+        CustomError::Unauthorized
+    );
+    require!(amount > 0, CustomError::InvalidParameters);
+
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.authority.to_account_info(),
+                to: ctx.accounts.escrow_account.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
     let escrow = &mut ctx.accounts.escrow_account;
+    escrow.crank_budget = escrow
+        .crank_budget
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticError)?;
 
-    let cpi_accounts = Transfer {
-        from: escrow_info,
-        to: ctx.accounts.buyer.to_account_info(),
-    };
+    emit!(CrankBudgetFunded {
+        store_id: ctx.accounts.store.key(),
+        amount,
+        crank_budget: escrow.crank_budget,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn refund_purchase<'info>(
+    receipt: &mut Account<'info, Purchase>,
+    escrow: &mut Account<'info, Escrow>,
+    escrow_bump: u8,
+    payment_mint: Option<Pubkey>,
+    buyer: &AccountInfo<'info>,
+    tokens: &TokenPayment<'_, 'info>,
+    amount: u64,
+) -> Result<()> {
+    receipt.record_refund(amount)?;
+    if amount > 0 {
+        pay_out_of_escrow(escrow, escrow_bump, payment_mint, buyer, tokens, amount)?;
+    }
+
+    emit!(PurchaseRefunded {
+        store_id: receipt.store,
+        receipt: receipt.key(),
+        buyer_id: receipt.buyer,
+        amount,
+        total_refunded: receipt.refunded_amount,
+        status: receipt.status,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn refund_from_escrow<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RefundPurchase<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, CustomError::InvalidParameters);
+    ctx.accounts
+        .store
+        .check_refund(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            amount,
+        )?;
+    refund_purchase(
+        &mut ctx.accounts.receipt,
+        &mut ctx.accounts.escrow_account,
+        ctx.bumps.escrow_account,
+        ctx.accounts.store.payment_mint,
+        &ctx.accounts.buyer,
+        &TokenPayment {
+            mint: ctx.accounts.mint.as_ref(),
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            wallet_token_account: ctx.accounts.buyer_token_account.as_ref(),
+            token_program: ctx.accounts.token_program.as_ref(),
+            extra_accounts: ctx.remaining_accounts,
+        },
+        amount,
+    )
+}
+
+/// Refund individual receipt lines at the unit price paid and put the
+/// returned units back into stock. Remaining accounts hold the writable
+/// Product PDA for each entry of `line_indices`, in the same order, then a
+/// writable [mint, escrow token account] pair for each refunded line that
+/// minted a certificate or vouchers. The buyer must have transferred the
+/// returned units' tokens to the escrow PDA beforehand; they are burned here.
+/// Loyalty points redeemed against the refunded lines go back to the buyer as
+/// a pending discount, and lines paid for entirely in points are restocked
+/// with nothing to pay out.
+pub fn refund_line_items<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RefundPurchase<'info>>,
+    line_indices: Vec<u8>,
+    quantities: Vec<u64>,
+) -> Result<()> {
+    require!(
+        line_indices.len() == quantities.len() && !line_indices.is_empty(),
+        CustomError::InvalidParameters
+    );
+    require!(
+        ctx.remaining_accounts.len() >= line_indices.len(),
+        CustomError::ProductNotFound
+    );
 
-    let cpi_program = ctx.accounts.system_program.to_account_info();
     let store_key = ctx.accounts.store.key();
-    let seeds = &[
-        b"escrow".as_ref(),
-        store_key.as_ref(),
-        &[ctx.bumps.escrow_account],
-    ];
-    let signer_seeds = &[&seeds[..]];
+    let receipt = &mut ctx.accounts.receipt;
+
+    let mut amount = 0u64;
+    let mut next_token = line_indices.len();
+    let mut i = 0;
+    while i < line_indices.len() {
+        let line = line_indices[i] as usize;
+        let quantity = quantities[i];
+        require!(
+            line < receipt.product_uuids.len() && !line_indices[..i].contains(&line_indices[i]),
+            CustomError::InvalidParameters
+        );
+        require!(quantity > 0, CustomError::InvalidParameters);
 
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        // Never return more units than were bought on this line
+        let returned = receipt.refunded_quantities[line]
+            .checked_add(quantity)
+            .ok_or(CustomError::ArithmeticError)?;
+        require!(
+            returned <= receipt.quantities[line],
+            CustomError::RefundExceedsQuantity
+        );
+        receipt.refunded_quantities[line] = returned;
 
-    system_program::transfer(cpi_ctx, amount)?;
+        let line_refund = receipt.unit_prices[line]
+            .checked_mul(quantity)
+            .ok_or(CustomError::ArithmeticError)?;
+        amount = amount
+            .checked_add(line_refund)
+            .ok_or(CustomError::ArithmeticError)?;
 
-    escrow.balance = escrow.balance.checked_sub(amount).unwrap();
+        // Restock the returned units
+        let mut product = load_store_product(
+            &store_key,
+            &ctx.remaining_accounts[i],
+            &receipt.product_uuids[line],
+        )?;
+        product.stock = product
+            .stock
+            .checked_add(quantity)
+            .ok_or(CustomError::ArithmeticError)?;
+        product.exit(&crate::ID)?;
+
+        // A tokenized line is only refunded once its tokens are back and burned
+        let token_mint = receipt.token_mints[line];
+        if token_mint != Pubkey::default() {
+            let Some(token_program) = ctx.accounts.product_token_program.as_ref() else {
+                return err!(CustomError::ProductTokenAccountsRequired);
+            };
+            require!(
+                ctx.remaining_accounts.len() >= next_token + 2,
+                CustomError::ProductTokenAccountsRequired
+            );
+            let mint = &ctx.remaining_accounts[next_token];
+            require_keys_eq!(mint.key(), token_mint, CustomError::InvalidProductToken);
+            burn_returned_tokens(
+                &ctx.accounts.escrow_account,
+                ctx.bumps.escrow_account,
+                mint,
+                &ctx.remaining_accounts[next_token + 1],
+                token_program,
+                quantity,
+            )?;
+            next_token += 2;
+        }
 
-    Ok(())
+        i += 1;
+    }
+    // Lines are priced at list price; scale down for discounts and transfer fees
+    let points = receipt.discount_share(amount)?;
+    let amount = receipt.escrowed_share(amount)?;
+    ctx.accounts
+        .store
+        .check_refund(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            amount,
+        )?;
+
+    if points > 0 {
+        let Some(counter) = ctx.accounts.purchase_counter.as_mut() else {
+            return err!(CustomError::PurchaseCounterRequired);
+        };
+        counter.loyalty_discount = counter
+            .loyalty_discount
+            .checked_add(points)
+            .ok_or(CustomError::ArithmeticError)?;
+    }
+    refund_purchase(
+        receipt,
+        &mut ctx.accounts.escrow_account,
+        ctx.bumps.escrow_account,
+        ctx.accounts.store.payment_mint,
+        &ctx.accounts.buyer,
+        &TokenPayment {
+            mint: ctx.accounts.mint.as_ref(),
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            wallet_token_account: ctx.accounts.buyer_token_account.as_ref(),
+            token_program: ctx.accounts.token_program.as_ref(),
+            extra_accounts: ctx.remaining_accounts,
+        },
+        amount,
+    )
 }
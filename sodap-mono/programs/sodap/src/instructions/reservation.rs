@@ -0,0 +1,153 @@
+use crate::error::CustomError;
+use crate::state::product::Product;
+use crate::state::reservation::{
+    ReleaseReservation, Reservation, ReservationReleased, ReserveStock, StockReserved,
+};
+use anchor_lang::prelude::*;
+
+/// Move `quantity` units of a product from stock into the buyer's
+/// reservation. Reserving again adds to a live reservation and restarts its
+/// expiry, up to the reservation's maximum lifetime.
+pub fn reserve_stock(ctx: Context<ReserveStock>, _product_uuid: [u8; 16], quantity: u64) -> Result<()> {
+    require!(ctx.accounts.store.is_active, CustomError::StoreInactive);
+    let product = &mut ctx.accounts.product;
+    require!(product.is_active, CustomError::ProductNotFound);
+    require!(quantity > 0, CustomError::InvalidParameters);
+
+    let now = Clock::get()?.unix_timestamp;
+    let reservation = &mut ctx.accounts.reservation;
+    let is_new = reservation.quantity == 0;
+    // An expired reservation has to be released before reserving again
+    require!(
+        is_new || !reservation.is_expired(now),
+        CustomError::ReservationExpired
+    );
+    let reserved = reservation
+        .quantity
+        .checked_add(quantity)
+        .ok_or(CustomError::ArithmeticError)?;
+    require!(
+        reserved <= Reservation::MAX_QUANTITY,
+        CustomError::ReservationLimitExceeded
+    );
+    require!(product.stock >= quantity, CustomError::InsufficientStock);
+
+    product.stock -= quantity;
+    product.reserved = product
+        .reserved
+        .checked_add(quantity)
+        .ok_or(CustomError::ArithmeticError)?;
+
+    if is_new {
+        reservation.created_at = now;
+    }
+    reservation.store = product.store;
+    reservation.product = product.key();
+    reservation.buyer = ctx.accounts.buyer.key();
+    reservation.quantity = reserved;
+    let ends_at = reservation
+        .created_at
+        .checked_add(Reservation::MAX_LIFETIME)
+        .ok_or(CustomError::ArithmeticError)?;
+    reservation.expires_at = now
+        .checked_add(Reservation::TTL)
+        .ok_or(CustomError::ArithmeticError)?
+        .min(ends_at);
+    reservation.bump = ctx.bumps.reservation;
+
+    emit!(StockReserved {
+        store_id: reservation.store,
+        product: reservation.product,
+        buyer_id: reservation.buyer,
+        quantity: reservation.quantity,
+        expires_at: reservation.expires_at,
+    });
+
+    Ok(())
+}
+
+/// Return a reservation's units to stock and close it. The buyer can cancel
+/// their reservation at any time; anyone can release it once expired.
+pub fn release_reservation(ctx: Context<ReleaseReservation>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let reservation = &ctx.accounts.reservation;
+    let expired = reservation.is_expired(now);
+    require!(
+        expired || ctx.accounts.closer.key() == reservation.buyer,
+        CustomError::Unauthorized
+    );
+
+    let product = &mut ctx.accounts.product;
+    product.reserved = product
+        .reserved
+        .checked_sub(reservation.quantity)
+        .ok_or(CustomError::StockUnderflow)?;
+    product.stock = product
+        .stock
+        .checked_add(reservation.quantity)
+        .ok_or(CustomError::ArithmeticError)?;
+
+    emit!(ReservationReleased {
+        store_id: reservation.store,
+        product: reservation.product,
+        buyer_id: reservation.buyer,
+        quantity: reservation.quantity,
+        expired,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+/// Units the buyer holds in the reservation at `info`, the buyer's
+/// `[b"reservation", product, buyer]` PDA; zero if it does not exist
+pub fn reserved_for_buyer(info: &AccountInfo) -> Result<u64> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(0);
+    }
+    let reservation = Reservation::try_deserialize(&mut &info.data.borrow()[..])?;
+    Ok(reservation.quantity)
+}
+
+/// Take each cart line's units out of the products, drawing on the buyer's
+/// reservations first and stock for the rest, and persist the products.
+/// Every reservation passed must match a cart line. It is consumed whole:
+/// units beyond the line's quantity go back to stock, and it is closed.
+pub(crate) fn take_stock<'info>(
+    products: &mut [Account<'info, Product>],
+    quantities: &[u64],
+    reservations: &[&'info AccountInfo<'info>],
+    buyer: &AccountInfo<'info>,
+) -> Result<()> {
+    let mut reserved = vec![0u64; products.len()];
+    for info in reservations {
+        require!(info.is_writable, CustomError::InvalidParameters);
+        let reservation = Account::<Reservation>::try_from(info)?;
+        require_keys_eq!(reservation.buyer, buyer.key(), CustomError::InvalidReservation);
+        let line = products
+            .iter()
+            .position(|product| product.key() == reservation.product)
+            .ok_or(CustomError::InvalidReservation)?;
+        require!(reserved[line] == 0, CustomError::InvalidReservation);
+        reserved[line] = reservation.quantity;
+        reservation.close(buyer.clone())?;
+    }
+
+    // Remaining accounts are not written back by Anchor, so persist products
+    for ((product, quantity), reserved) in products.iter_mut().zip(quantities).zip(reserved) {
+        let from_reservation = reserved.min(*quantity);
+        let from_stock = quantity - from_reservation;
+        product.reserved = product
+            .reserved
+            .checked_sub(reserved)
+            .ok_or(CustomError::StockUnderflow)?;
+        product.stock = product
+            .stock
+            .checked_add(reserved - from_reservation)
+            .ok_or(CustomError::ArithmeticError)?;
+        require!(product.stock >= from_stock, CustomError::InsufficientStock);
+        product.stock -= from_stock;
+        product.exit(&crate::ID)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use crate::error::CustomError;
+use crate::instructions::product::refund_purchase;
+use crate::state::product::Purchase;
+pub use crate::state::returns::{
+    PayReturn, RequestReturn, ReturnRequest, ReturnRequestUpdated, ReturnRequested, ReviewReturn,
+};
+use crate::types::{Capabilities, ReturnRequestStatus};
+use crate::utils::escrow::TokenPayment;
+use anchor_lang::prelude::*;
+
+/// Buyer opens a return request against one of their receipts
+pub fn request_return(
+    ctx: Context<RequestReturn>,
+    reason_code: u8,
+    evidence_uri: String,
+    amount: u64,
+) -> Result<()> {
+    let receipt = &mut ctx.accounts.receipt;
+    require!(
+        evidence_uri.len() <= ReturnRequest::MAX_EVIDENCE_URI_LEN,
+        CustomError::InvalidMetadataUri
+    );
+    require!(
+        amount > 0 && amount <= receipt.unsettled_amount()?,
+        CustomError::RefundExceedsPayment
+    );
+
+    // Hold the disputed amount back from escrow release
+    receipt.disputed_amount = receipt
+        .disputed_amount
+        .checked_add(amount)
+        .ok_or(CustomError::ArithmeticError)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let return_request = &mut ctx.accounts.return_request;
+    return_request.receipt = receipt.key();
+    return_request.store = receipt.store;
+    return_request.buyer = receipt.buyer;
+    return_request.reason_code = reason_code;
+    return_request.evidence_uri = evidence_uri;
+    return_request.amount = amount;
+    return_request.status = ReturnRequestStatus::Pending;
+    return_request.created_at = now;
+    return_request.updated_at = now;
+    return_request.bump = ctx.bumps.return_request;
+
+    emit!(ReturnRequested {
+        return_request: return_request.key(),
+        receipt: return_request.receipt,
+        store_id: return_request.store,
+        buyer_id: return_request.buyer,
+        reason_code,
+        amount,
+        created_at: now,
+    });
+
+    Ok(())
+}
+
+pub fn approve_return(ctx: Context<ReviewReturn>) -> Result<()> {
+    let authority = &ctx.accounts.authority;
+    ctx.accounts
+        .store
+        .check_refund(
+            &authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            ctx.accounts.return_request.amount,
+        )?;
+
+    let return_request = &mut ctx.accounts.return_request;
+    require!(
+        return_request.status == ReturnRequestStatus::Pending,
+        CustomError::InvalidReturnStatus
+    );
+
+    set_status(return_request, ReturnRequestStatus::Approved, authority.key())
+}
+
+pub fn reject_return(ctx: Context<ReviewReturn>) -> Result<()> {
+    let authority = &ctx.accounts.authority;
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::ISSUE_REFUNDS,
+            ),
+        CustomError::Unauthorized
+    );
+
+    let return_request = &mut ctx.accounts.return_request;
+    require!(
+        matches!(
+            return_request.status,
+            ReturnRequestStatus::Pending | ReturnRequestStatus::Approved
+        ),
+        CustomError::InvalidReturnStatus
+    );
+
+    release_dispute(&mut ctx.accounts.receipt, return_request.amount)?;
+    set_status(return_request, ReturnRequestStatus::Rejected, authority.key())
+}
+
+pub fn pay_return<'info>(ctx: Context<'_, '_, 'info, 'info, PayReturn<'info>>) -> Result<()> {
+    let authority = &ctx.accounts.authority;
+    ctx.accounts
+        .store
+        .check_refund(
+            &authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            ctx.accounts.return_request.amount,
+        )?;
+    require!(
+        ctx.accounts.return_request.status == ReturnRequestStatus::Approved,
+        CustomError::InvalidReturnStatus
+    );
+
+    let amount = ctx.accounts.return_request.amount;
+    release_dispute(&mut ctx.accounts.receipt, amount)?;
+    refund_purchase(
+        &mut ctx.accounts.receipt,
+        &mut ctx.accounts.escrow_account,
+        ctx.bumps.escrow_account,
+        ctx.accounts.store.payment_mint,
+        &ctx.accounts.buyer,
+        &TokenPayment {
+            mint: ctx.accounts.mint.as_ref(),
+            escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+            wallet_token_account: ctx.accounts.buyer_token_account.as_ref(),
+            token_program: ctx.accounts.token_program.as_ref(),
+            extra_accounts: ctx.remaining_accounts,
+        },
+        amount,
+    )?;
+
+    set_status(
+        &mut ctx.accounts.return_request,
+        ReturnRequestStatus::Paid,
+        authority.key(),
+    )
+}
+
+fn release_dispute(receipt: &mut Account<Purchase>, amount: u64) -> Result<()> {
+    receipt.disputed_amount = receipt
+        .disputed_amount
+        .checked_sub(amount)
+        .ok_or(CustomError::ArithmeticError)?;
+    Ok(())
+}
+
+fn set_status(
+    return_request: &mut Account<ReturnRequest>,
+    status: ReturnRequestStatus,
+    updated_by: Pubkey,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    return_request.status = status;
+    return_request.updated_at = now;
+
+    emit!(ReturnRequestUpdated {
+        return_request: return_request.key(),
+        receipt: return_request.receipt,
+        store_id: return_request.store,
+        status,
+        updated_by,
+        updated_at: now,
+    });
+
+    Ok(())
+}
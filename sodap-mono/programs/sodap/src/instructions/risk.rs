@@ -0,0 +1,114 @@
+use crate::error::CustomError;
+pub use crate::state::risk::{
+    AnomalyCleared, ClearAnomaly, RiskConfig, RiskConfigUpdated, UpdateRiskConfig,
+};
+use crate::state::UserProfile;
+use crate::types::{AnomalyFlag, Capabilities};
+use anchor_lang::prelude::*;
+
+/// Create or update a store's purchase risk rules
+pub fn update_risk_config(
+    ctx: Context<UpdateRiskConfig>,
+    max_cart_value: Option<u64>,
+    max_purchases_per_hour: Option<u16>,
+    min_account_age: Option<i64>,
+    hold_flagged: Option<bool>,
+) -> Result<()> {
+    require!(
+        ctx.accounts.store.can(
+            &ctx.accounts.authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_STORE,
+        ),
+        CustomError::Unauthorized
+    );
+    let config = &mut ctx.accounts.risk_config;
+    config.store = ctx.accounts.store.key();
+    config.bump = ctx.bumps.risk_config;
+
+    if let Some(max_cart_value) = max_cart_value {
+        config.max_cart_value = max_cart_value;
+    }
+    if let Some(max_purchases_per_hour) = max_purchases_per_hour {
+        config.max_purchases_per_hour = max_purchases_per_hour;
+    }
+    if let Some(min_account_age) = min_account_age {
+        require!(min_account_age >= 0, CustomError::InvalidParameters);
+        config.min_account_age = min_account_age;
+    }
+    if let Some(hold_flagged) = hold_flagged {
+        config.hold_flagged = hold_flagged;
+    }
+
+    emit!(RiskConfigUpdated {
+        store_id: config.store,
+        max_cart_value: config.max_cart_value,
+        max_purchases_per_hour: config.max_purchases_per_hour,
+        min_account_age: config.min_account_age,
+        hold_flagged: config.hold_flagged,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Clear a receipt's anomaly flag after review, releasing any settlement hold
+pub fn clear_anomaly(ctx: Context<ClearAnomaly>) -> Result<()> {
+    let authority = &ctx.accounts.authority;
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::RELEASE_ESCROW,
+            ),
+        CustomError::Unauthorized
+    );
+
+    let receipt = &mut ctx.accounts.receipt;
+    require!(
+        receipt.anomaly == AnomalyFlag::Suspicious,
+        CustomError::InvalidParameters
+    );
+    receipt.anomaly = AnomalyFlag::None;
+    receipt.held = false;
+
+    emit!(AnomalyCleared {
+        store_id: receipt.store,
+        receipt: receipt.key(),
+        cleared_by: authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Evaluate a purchase against the store's risk rules. Returns the broken
+/// rules as a bitmask and whether the receipt should be held. Either account
+/// may be uninitialized: a store without a RiskConfig has no rules, and a
+/// buyer without a UserProfile has no account age.
+pub fn evaluate_purchase_risk(
+    risk_config: &AccountInfo,
+    buyer_profile: &AccountInfo,
+    cart_value: u64,
+    purchases_this_hour: u16,
+    now: i64,
+) -> Result<(u8, bool)> {
+    let Some(config) = load_initialized::<RiskConfig>(risk_config)? else {
+        return Ok((0, false));
+    };
+    let account_age = load_initialized::<UserProfile>(buyer_profile)?
+        .map(|profile| now.saturating_sub(profile.created_at));
+
+    let reasons = config.evaluate(cart_value, purchases_this_hour, account_age);
+    Ok((reasons, reasons != 0 && config.hold_flagged))
+}
+
+fn load_initialized<T: AccountDeserialize>(info: &AccountInfo) -> Result<Option<T>> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    let data = info.try_borrow_data()?;
+    Ok(Some(T::try_deserialize(&mut &data[..])?))
+}
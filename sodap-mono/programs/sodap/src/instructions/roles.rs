@@ -0,0 +1,99 @@
+use crate::error::CustomError;
+use crate::state::roles::{
+    CreateStoreRole, EditStoreRole, RoleDefinition, StoreRoleDefined, StoreRoleRevoked,
+    StoreRoles,
+};
+use crate::types::Capabilities;
+use anchor_lang::prelude::*;
+
+/// Define a new custom role for the store. The role ID is assigned here and
+/// reported in `StoreRoleDefined`.
+pub fn create_store_role(
+    ctx: Context<CreateStoreRole>,
+    name: String,
+    capabilities: u16,
+) -> Result<()> {
+    validate_role(&name, capabilities)?;
+
+    let store_roles = &mut ctx.accounts.store_roles;
+    store_roles.store = ctx.accounts.store.key();
+    store_roles.bump = ctx.bumps.store_roles;
+    require!(
+        store_roles.roles.len() < StoreRoles::MAX_ROLES,
+        CustomError::TooManyRoles
+    );
+
+    let role_id = store_roles.next_role_id;
+    store_roles.next_role_id = role_id
+        .checked_add(1)
+        .ok_or(CustomError::TooManyRoles)?;
+    store_roles.roles.push(RoleDefinition {
+        role_id,
+        name: name.clone(),
+        capabilities,
+    });
+
+    emit!(StoreRoleDefined {
+        store_id: store_roles.store,
+        role_id,
+        name,
+        capabilities,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Rename a custom role or change its capabilities. Takes effect for every
+/// admin holding the role on their next instruction.
+pub fn update_store_role(
+    ctx: Context<EditStoreRole>,
+    role_id: u8,
+    name: Option<String>,
+    capabilities: Option<u16>,
+) -> Result<()> {
+    let store_id = ctx.accounts.store.key();
+    let role = ctx.accounts.store_roles.find_mut(role_id)?;
+    if let Some(name) = name {
+        role.name = name;
+    }
+    if let Some(capabilities) = capabilities {
+        role.capabilities = capabilities;
+    }
+    validate_role(&role.name, role.capabilities)?;
+
+    emit!(StoreRoleDefined {
+        store_id,
+        role_id,
+        name: role.name.clone(),
+        capabilities: role.capabilities,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Delete a custom role. Admins assigned it keep their entry but hold no
+/// capabilities through it.
+pub fn revoke_store_role(ctx: Context<EditStoreRole>, role_id: u8) -> Result<()> {
+    let store_roles = &mut ctx.accounts.store_roles;
+    store_roles.find_mut(role_id)?;
+    store_roles.roles.retain(|role| role.role_id != role_id);
+
+    emit!(StoreRoleRevoked {
+        store_id: store_roles.store,
+        role_id,
+        revoked_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+fn validate_role(name: &str, capabilities: u16) -> Result<()> {
+    require!(
+        !name.is_empty() && name.len() <= StoreRoles::MAX_ROLE_NAME_LEN,
+        CustomError::InvalidParameters
+    );
+    require!(
+        capabilities != 0 && capabilities & !Capabilities::ALL == 0,
+        CustomError::InvalidParameters
+    );
+    Ok(())
+}
@@ -1,31 +1,13 @@
 use crate::error::CustomError;
-use crate::state::store::{AdminRole, Store};
-use crate::types::AdminRoleType;
+use crate::state::roles::StoreRoles;
+use crate::state::staff::{RemoveExpiredStaff, StaffMember};
+use crate::state::store::{
+    AdminAdded, AdminRemoved, PaymentMintUpdated, SetPaymentMint, SettlementConfigUpdated, Store,
+    StoreUpdated, SuspendAdmin, UpdateSettlementConfig,
+};
+use crate::types::{AdminRoleType, Capabilities};
 use anchor_lang::prelude::*;
 
-/// Instruction to register a new store
-pub fn register_store(
-    ctx: Context<RegisterStore>,
-    name: String,
-    description: String,
-    logo_uri: String,
-) -> Result<()> {
-    let store = &mut ctx.accounts.store;
-    let owner = &ctx.accounts.owner;
-
-    store.owner = owner.key();
-    store.name = name;
-    store.description = description;
-    store.logo_uri = logo_uri;
-    store.is_active = true;
-    store.revenue = 0;
-    store.admin_roles = vec![];
-    store.bump = ctx.bumps.store;
-    store.escrow_bump = ctx.bumps.escrow;
-
-    Ok(())
-}
-
 /// Instruction to update a store's metadata
 pub fn update_store(
     ctx: Context<UpdateStore>,
@@ -35,11 +17,16 @@ pub fn update_store(
     logo_uri: Option<String>,
 ) -> Result<()> {
     let store = &mut ctx.accounts.store;
-    let authority = &ctx.accounts.owner;
-
-    // Only the owner can update
-    require!(authority.key() == store.owner, CustomError::Unauthorized);
-    require!(authority.is_signer, CustomError::Unauthorized);
+    let authority = &ctx.accounts.authority;
+    require!(
+        store.can(
+            &authority.key(),
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            Capabilities::MANAGE_STORE,
+        ),
+        CustomError::Unauthorized
+    );
 
     if let Some(name) = name {
         store.name = name;
@@ -51,147 +38,316 @@ pub fn update_store(
         store.logo_uri = logo_uri;
     }
 
+    emit!(StoreUpdated {
+        store_id: store.key(),
+        updated_by: authority.key(),
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+
     Ok(())
 }
 
-/// Instruction to add an admin to a store
+/// Instruction to configure how a store's escrowed funds are settled. A new
+/// dispute window only applies to later purchases; each receipt keeps the one
+/// it was bought under.
+pub fn update_settlement_config(
+    ctx: Context<UpdateSettlementConfig>,
+    dispute_window: Option<i64>,
+    payout_address: Option<Pubkey>,
+    crank_tip: Option<u64>,
+    refund_limit: Option<u64>,
+) -> Result<()> {
+    let store = &mut ctx.accounts.store;
+
+    if let Some(dispute_window) = dispute_window {
+        require!(
+            (0..=Store::MAX_DISPUTE_WINDOW).contains(&dispute_window),
+            CustomError::InvalidParameters
+        );
+        store.dispute_window = dispute_window;
+    }
+    if let Some(payout_address) = payout_address {
+        store.payout_address = payout_address;
+    }
+    if let Some(crank_tip) = crank_tip {
+        require!(
+            crank_tip <= Store::MAX_CRANK_TIP,
+            CustomError::InvalidParameters
+        );
+        store.crank_tip = crank_tip;
+    }
+    if let Some(refund_limit) = refund_limit {
+        store.refund_limit = refund_limit;
+    }
+
+    emit!(SettlementConfigUpdated {
+        store_id: store.key(),
+        dispute_window: store.dispute_window,
+        payout_address: store.payout_address,
+        crank_tip: store.crank_tip,
+        refund_limit: store.refund_limit,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Instruction to price a store in an SPL mint, or in SOL again when no mint
+/// is passed. Buyer payments are then held in the escrow PDA's token account
+/// for that mint, which is created here. The escrow must be empty so funds
+/// already held can still be paid out.
+pub fn set_payment_mint(ctx: Context<SetPaymentMint>) -> Result<()> {
+    let escrow = &mut ctx.accounts.escrow_account;
+    require!(escrow.balance == 0, CustomError::EscrowNotEmpty);
+    escrow.store = ctx.accounts.store.key();
+
+    let payment_mint = ctx.accounts.mint.as_ref().map(|mint| mint.key());
+    let escrow_vault = ctx.accounts.escrow_vault.as_ref().map(|vault| vault.key());
+    require!(
+        payment_mint.is_some() == escrow_vault.is_some(),
+        CustomError::TokenAccountsRequired
+    );
+    let store = &mut ctx.accounts.store;
+    store.payment_mint = payment_mint;
+
+    emit!(PaymentMintUpdated {
+        store_id: store.key(),
+        payment_mint,
+        escrow_vault,
+        updated_at: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Instruction to add an admin to a store by creating their staff record
 pub fn add_admin(
     ctx: Context<AddAdmin>,
     _store_id: Pubkey,
     admin_pubkey: Pubkey,
     role_type: AdminRoleType,
+    label: String,
+    valid_from: Option<i64>,
+    valid_until: Option<i64>,
 ) -> Result<()> {
-    let store = &mut ctx.accounts.store;
-    let authority = &ctx.accounts.owner;
-
-    // Only the owner can add admins
-    require!(authority.key() == store.owner, CustomError::Unauthorized);
-    require!(authority.is_signer, CustomError::Unauthorized);
-
-    if store
-        .admin_roles
-        .iter()
-        .any(|r| r.admin_pubkey == admin_pubkey)
-    {
-        return Err(CustomError::AdminAlreadyExists.into());
+    let store = &ctx.accounts.store;
+    let now = Clock::get()?.unix_timestamp;
+    let roles = ctx.accounts.store_roles.as_deref();
+    require!(
+        label.len() <= StaffMember::MAX_LABEL_LEN,
+        CustomError::InvalidParameters
+    );
+    // A grant must still have time left to run, and start before it ends
+    if let Some(until) = valid_until {
+        require!(
+            until > now && !valid_from.is_some_and(|from| from >= until),
+            CustomError::InvalidParameters
+        );
+    }
+    if let AdminRoleType::Custom { role_id } = role_type {
+        require!(
+            roles.is_some_and(|roles| roles.find(role_id).is_some()),
+            CustomError::RoleNotFound
+        );
     }
+    check_manages_role(
+        store,
+        &ctx.accounts.authority.key(),
+        ctx.accounts.staff.as_deref(),
+        roles,
+        role_type,
+    )?;
+
+    let staff_member = &mut ctx.accounts.staff_member;
+    require!(
+        staff_member.store == Pubkey::default() && admin_pubkey != store.owner,
+        CustomError::AdminAlreadyExists
+    );
+    staff_member.store = store.key();
+    staff_member.staff = admin_pubkey;
+    staff_member.role = role_type;
+    staff_member.label = label.clone();
+    staff_member.is_active = true;
+    staff_member.added_at = now;
+    staff_member.valid_from = valid_from;
+    staff_member.valid_until = valid_until;
+    staff_member.bump = ctx.bumps.staff_member;
 
-    store.admin_roles.push(AdminRole {
+    emit!(AdminAdded {
+        store_id: store.key(),
         admin_pubkey,
         role_type,
+        label,
+        valid_from,
+        valid_until,
+        added_at: now,
     });
     Ok(())
 }
 
-/// Instruction to remove an admin from a store
+/// Instruction to remove an admin from a store, closing their staff record
+/// and returning its rent to the store owner. Staff may always remove
+/// themselves; the store owner can never be removed, and other holders of the
+/// owner role only by the store owner.
 pub fn remove_admin(
     ctx: Context<RemoveAdmin>,
     _store_id: Pubkey,
     admin_pubkey: Pubkey,
 ) -> Result<()> {
-    let store = &mut ctx.accounts.store;
-    let authority = &ctx.accounts.owner;
+    let store = &ctx.accounts.store;
+    let authority = ctx.accounts.authority.key();
+    require!(admin_pubkey != store.owner, CustomError::CannotRemoveOwner);
 
-    // Only the owner can remove admins
-    require!(authority.key() == store.owner, CustomError::Unauthorized);
-    require!(authority.is_signer, CustomError::Unauthorized);
+    let info = &ctx.accounts.staff_member;
+    require!(
+        info.owner == &crate::ID && !info.data_is_empty(),
+        CustomError::AdminNotFound
+    );
+    let role = StaffMember::try_deserialize(&mut &info.data.borrow()[..])?.role;
 
-    store.admin_roles.retain(|r| r.admin_pubkey != admin_pubkey);
-    Ok(())
-}
+    if role == AdminRoleType::Owner {
+        require!(
+            authority == store.owner || authority == admin_pubkey,
+            CustomError::CannotRemoveOwner
+        );
+    }
+    if authority != admin_pubkey {
+        check_manages_role(
+            store,
+            &authority,
+            ctx.accounts.staff.as_deref(),
+            ctx.accounts.store_roles.as_deref(),
+            role,
+        )?;
+    }
 
-pub fn release_escrow(ctx: Context<ReleaseEscrow>, amount: u64) -> Result<()> {
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.system_program.to_account_info(),
-        anchor_lang::system_program::Transfer {
-            from: ctx.accounts.escrow.to_account_info(),
-            to: ctx.accounts.owner.to_account_info(),
-        },
-    );
-    anchor_lang::system_program::transfer(cpi_ctx, amount)?;
-    ctx.accounts.store.revenue = ctx.accounts.store.revenue.checked_add(amount).unwrap();
-    Ok(())
-}
+    // The record is taken unchecked so a missing admin fails with
+    // AdminNotFound, so it is closed here rather than by Anchor
+    let rent = info.lamports();
+    info.sub_lamports(rent)?;
+    ctx.accounts.owner.add_lamports(rent)?;
+    info.assign(&System::id());
+    info.realloc(0, false)?;
 
-// 3) refund_escrow: send from escrow PDA to buyer
-pub fn refund_escrow(ctx: Context<RefundEscrow>, amount: u64) -> Result<()> {
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.system_program.to_account_info(),
-        anchor_lang::system_program::Transfer {
-            from: ctx.accounts.escrow.to_account_info(),
-            to: ctx.accounts.buyer.to_account_info(),
-        },
-    );
-    anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+    emit!(AdminRemoved {
+        store_id: store.key(),
+        admin_pubkey,
+        suspended: false,
+        removed_at: Clock::get()?.unix_timestamp,
+    });
     Ok(())
 }
-#[derive(Accounts)]
-#[instruction()]
-pub struct RegisterStore<'info> {
-    #[account(
-        init,
-        seeds = [b"store", owner.key().as_ref()],
-        bump,
-        payer = payer,
-        space = Store::LEN,
-    )]
-    pub store: Account<'info, Store>,
-
-    #[account(
-        init,
-        seeds = [b"escrow", store.key().as_ref()],
-        bump,
-        payer = payer,
-        space = 0,
-    )]
-    /// CHECK: Escrow account to hold payments
-    pub escrow: AccountInfo<'info>,
-
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub owner: Signer<'info>,
-
-    pub system_program: Program<'info, System>,
-}
 
-#[derive(Accounts)]
-pub struct ReleaseEscrow<'info> {
-    #[account(mut, has_one = owner)]
-    pub store: Account<'info, Store>,
-
-    #[account(
-        mut,
-        seeds = [b"escrow", store.key().as_ref()],
-        bump = store.escrow_bump,
-        seeds::program = system_program.key()
-    )]
-    /// CHECK: escrow vault PDA
-    pub escrow: AccountInfo<'info>,
+/// Instruction to suspend an admin, or reinstate a suspended one. Suspended
+/// staff keep their record but hold no capabilities.
+pub fn suspend_admin(
+    ctx: Context<SuspendAdmin>,
+    _store_id: Pubkey,
+    admin_pubkey: Pubkey,
+    suspended: bool,
+) -> Result<()> {
+    check_manages_role(
+        &ctx.accounts.store,
+        &ctx.accounts.authority.key(),
+        ctx.accounts.staff.as_deref(),
+        ctx.accounts.store_roles.as_deref(),
+        ctx.accounts.staff_member.role,
+    )?;
 
-    #[account(mut, address = store.owner)]
-    pub owner: Signer<'info>,
+    let store_id = ctx.accounts.store.key();
+    let staff_member = &mut ctx.accounts.staff_member;
+    staff_member.is_active = !suspended;
 
-    pub system_program: Program<'info, System>,
+    let now = Clock::get()?.unix_timestamp;
+    if suspended {
+        emit!(AdminRemoved {
+            store_id,
+            admin_pubkey,
+            suspended,
+            removed_at: now,
+        });
+    } else {
+        emit!(AdminAdded {
+            store_id,
+            admin_pubkey,
+            role_type: staff_member.role,
+            label: staff_member.label.clone(),
+            valid_from: staff_member.valid_from,
+            valid_until: staff_member.valid_until,
+            added_at: now,
+        });
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct RefundEscrow<'info> {
-    #[account(mut)]
-    pub store: Account<'info, Store>,
+/// Permissionless cleanup. Remaining accounts are staff record PDAs of this
+/// store; those whose grant has lapsed are closed. Their rent always goes to
+/// the store owner, whoever paid for the record, and never to the caller.
+/// Records still in force are skipped so a batch never fails on one late
+/// entry.
+pub fn remove_expired_staff<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RemoveExpiredStaff<'info>>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let store_id = ctx.accounts.store.key();
+    require!(!ctx.remaining_accounts.is_empty(), CustomError::InvalidParameters);
 
-    #[account(
-        mut,
-        seeds = [b"escrow", store.key().as_ref()],
-        bump = store.escrow_bump,
-    )]
-    pub escrow: AccountInfo<'info>,
+    for info in ctx.remaining_accounts {
+        require!(info.is_writable, CustomError::InvalidParameters);
+        let staff_member = Account::<StaffMember>::try_from(info)?;
+        require_keys_eq!(staff_member.store, store_id, CustomError::InvalidStore);
+        let expected = Pubkey::create_program_address(
+            &[
+                b"staff",
+                store_id.as_ref(),
+                staff_member.staff.as_ref(),
+                &[staff_member.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| CustomError::AdminNotFound)?;
+        require_keys_eq!(info.key(), expected, CustomError::AdminNotFound);
+        if !staff_member.is_expired(now) {
+            continue;
+        }
 
-    #[account(mut)]
-    pub buyer: Signer<'info>,
+        let admin_pubkey = staff_member.staff;
+        staff_member.close(ctx.accounts.owner.to_account_info())?;
+        emit!(AdminRemoved {
+            store_id,
+            admin_pubkey,
+            suspended: false,
+            removed_at: now,
+        });
+    }
+    Ok(())
+}
 
-    pub system_program: Program<'info, System>,
+/// Staff managers can only grant, suspend or remove roles whose capabilities
+/// they hold themselves, so only owners manage owners. A custom role that
+/// can't be resolved, because the role definitions were left out or the role
+/// was revoked, counts as holding every capability.
+fn check_manages_role(
+    store: &Store,
+    authority: &Pubkey,
+    staff: Option<&StaffMember>,
+    roles: Option<&StoreRoles>,
+    role_type: AdminRoleType,
+) -> Result<()> {
+    let granter_capabilities = store.capabilities_of(authority, staff, roles);
+    let target_capabilities = match role_type {
+        AdminRoleType::Custom { role_id } => roles
+            .and_then(|roles| roles.find(role_id))
+            .map_or(Capabilities::ALL, |role| role.capabilities),
+        built_in => built_in.capabilities(),
+    };
+    require!(
+        granter_capabilities & Capabilities::MANAGE_STAFF != 0
+            && target_capabilities & !granter_capabilities == 0,
+        CustomError::Unauthorized
+    );
+    Ok(())
 }
 
 // Re-export contexts from state
 pub use crate::state::store::{AddAdmin, RemoveAdmin, UpdateStore};
-// pub use self::register_store;
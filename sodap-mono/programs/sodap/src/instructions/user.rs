@@ -1,66 +1,71 @@
 // User profile-related instructions, events, and accounts will be placed here.
 
 use crate::error::CustomError;
-use crate::state::user::UserProfileUpdated;
-pub use crate::state::user::{CreateOrUpdateUserProfile, ScanAndPurchase};
+use crate::state::user::{PurchaseRecord, UserProfile, UserProfileUpdated};
+pub use crate::state::user::CreateOrUpdateUserProfile;
 use crate::types::*;
 use anchor_lang::prelude::*;
 
+/// Create the caller's profile, or update it. Only the fields passed are
+/// changed; contact details are salted commitments, and a zero commitment
+/// clears the field. Emits one `UserProfileUpdated` per call.
 pub fn create_or_update_user_profile(
     ctx: Context<CreateOrUpdateUserProfile>,
     user_id: Option<String>,
-    delivery_address: Option<String>,
+    name_commitment: Option<[u8; 32]>,
+    email_commitment: Option<[u8; 32]>,
+    phone_commitment: Option<[u8; 32]>,
+    delivery_address_commitment: Option<[u8; 32]>,
     preferred_store: Option<Pubkey>,
 ) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
     let user_profile = &mut ctx.accounts.user_profile;
-    user_profile.authority = ctx.accounts.authority.key();
+    if user_profile.created_at == 0 {
+        user_profile.init(ctx.accounts.authority.key(), now);
+    }
 
     if let Some(user_id) = user_id {
-        user_profile.user_id = user_id.clone();
-        emit!(UserProfileUpdated {
-            wallet_address: ctx.accounts.authority.key(),
-            user_id,
-            updated_at: Clock::get()?.unix_timestamp,
-        });
+        require!(
+            !user_id.is_empty() && user_id.len() <= UserProfile::MAX_USER_ID_LEN,
+            CustomError::InvalidProfileField
+        );
+        user_profile.user_id = user_id;
     }
-
-    if let Some(delivery_address) = delivery_address {
-        user_profile.delivery_address = delivery_address;
-        emit!(UserProfileUpdated {
-            wallet_address: ctx.accounts.authority.key(),
-            user_id: user_profile.user_id.clone(),
-            updated_at: Clock::get()?.unix_timestamp,
-        });
+    if let Some(name_commitment) = name_commitment {
+        user_profile.name_commitment = name_commitment;
+    }
+    if let Some(email_commitment) = email_commitment {
+        user_profile.email_commitment = email_commitment;
+    }
+    if let Some(phone_commitment) = phone_commitment {
+        user_profile.phone_commitment = phone_commitment;
+    }
+    if let Some(delivery_address_commitment) = delivery_address_commitment {
+        user_profile.delivery_address_commitment = delivery_address_commitment;
     }
-
     if let Some(preferred_store) = preferred_store {
         user_profile.preferred_store = preferred_store;
-        emit!(UserProfileUpdated {
-            wallet_address: ctx.accounts.authority.key(),
-            user_id: user_profile.user_id.clone(),
-            updated_at: Clock::get()?.unix_timestamp,
-        });
     }
+    user_profile.updated_at = now;
 
-    Ok(())
-}
-
-pub fn scan_and_purchase(
-    ctx: Context<ScanAndPurchase>,
-    product_uuids: Vec<[u8; 16]>,
-    quantities: Vec<u64>,
-    _store_id: Pubkey,
-) -> Result<()> {
-    require!(
-        product_uuids.len() == quantities.len(),
-        CustomError::InvalidCart
-    );
-    let user_profile = &mut ctx.accounts.user_profile;
-    user_profile.total_purchases += 1;
     emit!(UserProfileUpdated {
-        wallet_address: ctx.accounts.authority.key(),
+        wallet_address: user_profile.authority,
         user_id: user_profile.user_id.clone(),
-        updated_at: Clock::get()?.unix_timestamp,
+        updated_at: now,
     });
+
+    Ok(())
+}
+
+/// Record a checkout on the buyer's profile, if they have one. The profile is
+/// passed unchecked so buyers without one can still check out.
+pub fn record_purchase_history(profile: &AccountInfo, record: PurchaseRecord) -> Result<()> {
+    if profile.owner != &crate::ID || profile.data_is_empty() {
+        return Ok(());
+    }
+    let mut data = profile.try_borrow_mut_data()?;
+    let mut user_profile = UserProfile::try_deserialize(&mut &data[..])?;
+    user_profile.record_purchase(record);
+    user_profile.try_serialize(&mut &mut data[..])?;
     Ok(())
 }
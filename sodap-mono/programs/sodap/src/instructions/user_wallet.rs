@@ -5,11 +5,7 @@ pub fn create_user_wallet(ctx: Context<CreateUserWallet>) -> Result<()> {
     let user_profile = &mut ctx.accounts.user_profile;
     let authority = &ctx.accounts.authority;
 
-    user_profile.authority = authority.key();
-    user_profile.user_id = authority.key().to_string();
-    user_profile.delivery_address = String::new();
-    user_profile.preferred_store = Pubkey::default();
-    user_profile.total_purchases = 0;
+    user_profile.init(authority.key(), Clock::get()?.unix_timestamp);
 
     msg!("User wallet created with address: {}", authority.key());
     Ok(())
@@ -19,10 +15,10 @@ pub fn create_user_wallet(ctx: Context<CreateUserWallet>) -> Result<()> {
 pub struct CreateUserWallet<'info> {
     #[account(
         init,
-        seeds = [b"user_wallet", authority.key().as_ref()],
+        seeds = [b"user_profile", authority.key().as_ref()],
         bump,
         payer = authority,
-        space = 8 + UserProfile::LEN,
+        space = UserProfile::LEN,
     )]
     pub user_profile: Account<'info, UserProfile>,
 
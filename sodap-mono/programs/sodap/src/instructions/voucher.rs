@@ -0,0 +1,130 @@
+use crate::error::CustomError;
+pub use crate::state::voucher::{
+    InitializeVoucherMint, RedeemVouchers, VoucherMintInitialized, VouchersRedeemed,
+};
+use crate::state::Product;
+use crate::types::Capabilities;
+use crate::utils::certificate::ProductTokenPrograms;
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token;
+use anchor_spl::token_2022;
+
+/// Create the voucher mint for a Fungible product
+pub fn initialize_voucher_mint(
+    ctx: Context<InitializeVoucherMint>,
+    _product_uuid: [u8; 16],
+) -> Result<()> {
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &ctx.accounts.authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::MANAGE_PRODUCTS,
+            ),
+        CustomError::Unauthorized
+    );
+
+    emit!(VoucherMintInitialized {
+        store_id: ctx.accounts.store.key(),
+        product: ctx.accounts.product.key(),
+        mint: ctx.accounts.voucher_mint.key(),
+        created_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Burn vouchers as the goods are handed over. The holder signs for their
+/// tokens and a store admin who manages products signs for the handover.
+pub fn redeem_vouchers(
+    ctx: Context<RedeemVouchers>,
+    _product_uuid: [u8; 16],
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, CustomError::InvalidParameters);
+    let authority = &ctx.accounts.authority;
+    require!(
+        ctx.accounts
+            .store
+            .can(
+                &authority.key(),
+                ctx.accounts.staff.as_deref(),
+                ctx.accounts.store_roles.as_deref(),
+                Capabilities::MANAGE_PRODUCTS,
+            ),
+        CustomError::Unauthorized
+    );
+
+    token_2022::burn(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token_2022::Burn {
+                mint: ctx.accounts.voucher_mint.to_account_info(),
+                from: ctx.accounts.holder_voucher_account.to_account_info(),
+                authority: ctx.accounts.holder.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    emit!(VouchersRedeemed {
+        store_id: ctx.accounts.store.key(),
+        product: ctx.accounts.product.key(),
+        holder: ctx.accounts.holder.key(),
+        amount,
+        redeemed_by: authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Mint `amount` vouchers for a Fungible cart line to the buyer's associated
+/// token account, creating it if needed. Returns the voucher mint.
+pub fn mint_vouchers<'info>(
+    product: &Account<'info, Product>,
+    mint: &AccountInfo<'info>,
+    buyer_account: &AccountInfo<'info>,
+    buyer: &Signer<'info>,
+    programs: &ProductTokenPrograms<'_, 'info>,
+    amount: u64,
+) -> Result<Pubkey> {
+    let product_key = product.key();
+    let (expected, _) =
+        Pubkey::find_program_address(&[b"voucher_mint", product_key.as_ref()], &crate::ID);
+    require_keys_eq!(mint.key(), expected, CustomError::InvalidProductToken);
+
+    let token_program = programs.token_program.to_account_info();
+    associated_token::create_idempotent(CpiContext::new(
+        programs.associated_token_program.to_account_info(),
+        associated_token::Create {
+            payer: buyer.to_account_info(),
+            associated_token: buyer_account.clone(),
+            authority: buyer.to_account_info(),
+            mint: mint.clone(),
+            system_program: programs.system_program.to_account_info(),
+            token_program: token_program.clone(),
+        },
+    ))?;
+
+    let store = product.store;
+    let product_seeds: &[&[u8]] = &[
+        b"product",
+        store.as_ref(),
+        product.uuid.as_ref(),
+        &[product.bump],
+    ];
+    token_2022::mint_to(
+        CpiContext::new_with_signer(
+            token_program,
+            token_2022::MintTo {
+                mint: mint.clone(),
+                to: buyer_account.clone(),
+                authority: product.to_account_info(),
+            },
+            &[product_seeds],
+        ),
+        amount,
+    )?;
+    Ok(mint.key())
+}
@@ -22,13 +22,6 @@ use error::*;
 // Re-export types module
 use types::*;
 
-// Define ProductAttribute type since it's not found in types.rs
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct ProductAttribute {
-    pub name: String,
-    pub value: String,
-}
-
 // Custom error types for validation
 #[error_code]
 pub enum CustomError {
@@ -54,36 +47,13 @@ pub enum CustomError {
 use state::store::Store;
 use state::Escrow;
 
-// Define Purchase struct for storing purchase records
-#[derive(Debug)]
-#[account]
-pub struct Purchase {
-    pub product_ids: Vec<Pubkey>,
-    pub quantities: Vec<u64>,
-    pub total_paid: u64,
-    pub gas_fee: u64,
-    pub store: Pubkey,
-    pub buyer: Pubkey,
-    pub timestamp: i64,
-}
-
-impl Purchase {
-    pub const MAX_PRODUCTS: usize = 10;
-
-    pub fn validate_products(&self) -> anchor_lang::Result<()> {
-        anchor_lang::require!(self.product_ids.len() <= Self::MAX_PRODUCTS, CustomError::TooManyProducts);
-        anchor_lang::require!(self.product_ids.len() == self.quantities.len(), CustomError::InvalidPurchase);
-        Ok(())
-    }
-}
-
 // Declare a struct here to avoid using one from a module
 #[derive(Accounts)]
 pub struct RegisterStoreAccounts<'info> {
     #[account(
         init_if_needed, 
         payer = payer, 
-        space = 8 + 32 + 32 + 100 + 200 + 100 + 1 + 8 + 500, 
+        space = Store::LEN, 
         seeds = [b"store", authority.key().as_ref()], 
         bump
     )]
@@ -94,173 +64,6 @@ pub struct RegisterStoreAccounts<'info> {
     pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct UpdateStoreAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct CreateOrUpdateUserProfileAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct ScanAndPurchaseAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct RegisterProductAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct UpdateProductAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct DeactivateProductAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-// Add a new struct for emitting purchase events
-#[event]
-pub struct PurchaseCompleted {
-    pub store: Pubkey,
-    pub buyer: Pubkey,
-    pub total_amount: u64,
-    pub timestamp: i64,
-}
-
-#[derive(Accounts)]
-pub struct PurchaseCartAccounts<'info> {
-    // Store information
-    #[account(mut)]
-    pub store: Account<'info, Store>,
-
-    // Receipt/Purchase record
-    #[account(
-        init,
-        payer = buyer,
-        space = 8 + // discriminator
-            (4 + Purchase::MAX_PRODUCTS * 32) + // product_ids Vec<Pubkey>
-            (4 + Purchase::MAX_PRODUCTS * 8) + // quantities Vec<u64>
-            8 + // total_paid
-            8 + // gas_fee
-            32 + // store
-            32 + // buyer
-            8 // timestamp
-    )]
-    pub receipt: Account<'info, Purchase>,
-
-    // The buyer who is paying for the cart
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-
-    // The store owner who will receive payment
-    #[account(mut)]
-    /// CHECK: This is not dangerous because we're only using it for payment
-    pub store_owner: AccountInfo<'info>,
-
-    // The escrow account that holds funds during the purchase
-    #[account(
-        init_if_needed,
-        payer = buyer,
-        space = 8 + 32 + 8,
-        seeds = [b"escrow", store.key().as_ref()],
-        bump
-    )]
-    pub escrow_account: Account<'info, Escrow>,
-
-    // Required programs
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct AddPlatformAdminAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct RemovePlatformAdminAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct AddStoreAdminAccounts<'info> {
-    #[account(mut)]
-    pub store: Account<'info, Store>,
-    pub authority: Signer<'info>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct RemoveStoreAdminAccounts<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-
-#[derive(Accounts)]
-pub struct ReleaseEscrowAccounts<'info> {
-    #[account(mut)]
-    pub store: Account<'info, Store>,
-    #[account(mut)]
-    /// CHECK: This is safe because we're only using it for payment
-    pub store_owner: AccountInfo<'info>,
-    #[account(mut)]
-    pub escrow_account: Account<'info, Escrow>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct RefundEscrowAccounts<'info> {
-    // Store information
-    #[account(mut)]
-    pub store: Account<'info, Store>,
-
-    // Store owner authorizing the refund
-    #[account(
-        constraint = store_owner.key() == store.owner @ CustomError::Unauthorized
-    )]
-    pub store_owner: Signer<'info>,
-
-    // The buyer who receives the refund
-    #[account(mut)]
-    /// CHECK: This is not dangerous because we're only sending funds to this account
-    pub buyer: AccountInfo<'info>,
-
-    // The escrow account that holds funds
-    #[account(
-        mut,
-        seeds = [b"escrow", store.key().as_ref()],
-        bump,
-        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
-    )]
-    pub escrow_account: Account<'info, Escrow>,
-
-    pub system_program: Program<'info, System>,
-}
 
 #[program]
 pub mod sodap {
@@ -287,18 +90,22 @@ pub mod sodap {
         let authority = &ctx.accounts.authority;
 
         // Set the store account fields
-        store.owner = authority.key();
         store.name = name;
         store.description = description;
         store.logo_uri = logo_uri;
-        store.is_active = true;
-        store.revenue = 0;
 
-        // Initialize admin roles with the owner as the first admin with owner role
-        store.admin_roles = vec![state::store::AdminRole {
-            admin_pubkey: authority.key(),
-            role_type: types::AdminRoleType::Owner {},
-        }];
+        // Registering again only refreshes the metadata; resetting settlement
+        // would strand whatever escrow holds in the store's payment mint
+        if store.owner == Pubkey::default() {
+            store.owner = authority.key();
+            store.is_active = true;
+            store.revenue = 0;
+            store.dispute_window = Store::DEFAULT_DISPUTE_WINDOW;
+            store.payout_address = authority.key();
+            store.crank_tip = 0;
+            store.payment_mint = None;
+            store.refund_limit = 0;
+        }
 
         msg!("Store registered successfully");
         msg!("Owner: {:?}", store.owner);
@@ -306,333 +113,363 @@ pub mod sodap {
     }
 
     pub fn update_store(
-        ctx: Context<UpdateStoreAccounts>,
+        ctx: Context<UpdateStore>,
         store_id: Pubkey,
         name: Option<String>,
         description: Option<String>,
         logo_uri: Option<String>,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Updating store: {:?}", store_id);
-        if let Some(name) = name {
-            msg!("New store name: {}", name);
-        }
-        if let Some(description) = description {
-            msg!("New store description: {}", description);
-        }
-        if let Some(logo_uri) = logo_uri {
-            msg!("New store logo URI: {}", logo_uri);
-    }
-        Ok(())
+        instructions::store::update_store(ctx, store_id, name, description, logo_uri)
     }
 
     // User profile operations
     pub fn create_or_update_user_profile(
-        ctx: Context<CreateOrUpdateUserProfileAccounts>,
-        user_id: Option<Pubkey>,
-        name: Option<String>,
-        email: Option<String>,
-        phone: Option<String>,
+        ctx: Context<CreateOrUpdateUserProfile>,
+        user_id: Option<String>,
+        name_commitment: Option<[u8; 32]>,
+        email_commitment: Option<[u8; 32]>,
+        phone_commitment: Option<[u8; 32]>,
+        delivery_address_commitment: Option<[u8; 32]>,
+        preferred_store: Option<Pubkey>,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Creating or updating user profile");
-        if let Some(user_id) = user_id {
-            msg!("User ID: {:?}", user_id);
-        }
-        if let Some(name) = name {
-            msg!("Name: {}", name);
-        }
-        if let Some(email) = email {
-            msg!("Email: {}", email);
-        }
-        if let Some(phone) = phone {
-            msg!("Phone: {}", phone);
-        }
-        Ok(())
+        instructions::user::create_or_update_user_profile(
+            ctx,
+            user_id,
+            name_commitment,
+            email_commitment,
+            phone_commitment,
+            delivery_address_commitment,
+            preferred_store,
+        )
     }
 
-    pub fn scan_and_purchase(
-        ctx: Context<ScanAndPurchaseAccounts>,
-        product_ids: Vec<Pubkey>,
-        quantities: Vec<u64>,
-        user_id: Pubkey,
+    // Scan-to-go cart sessions
+    pub fn scan_cart_item(
+        ctx: Context<ScanCartItem>,
+        product_uuid: [u8; 16],
+        quantity: u64,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Scanning and purchasing products");
-        msg!("User ID: {:?}", user_id);
-        msg!("Product IDs: {:?}", product_ids);
-        msg!("Quantities: {:?}", quantities);
-        Ok(())
+        instructions::cart::scan_cart_item(ctx, product_uuid, quantity)
+    }
+
+    pub fn remove_cart_item(
+        ctx: Context<RemoveCartItem>,
+        product_uuid: [u8; 16],
+        quantity: u64,
+    ) -> Result<()> {
+        instructions::cart::remove_cart_item(ctx, product_uuid, quantity)
+    }
+
+    pub fn checkout_cart_session<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CheckoutCartSession<'info>>,
+        total_amount_paid: u64,
+    ) -> Result<()> {
+        instructions::cart::checkout_cart_session(ctx, total_amount_paid)
+    }
+
+    pub fn close_cart_session(ctx: Context<CloseCartSession>) -> Result<()> {
+        instructions::cart::close_cart_session(ctx)
+    }
+
+    // Stock reservations
+    pub fn reserve_stock(
+        ctx: Context<ReserveStock>,
+        product_uuid: [u8; 16],
+        quantity: u64,
+    ) -> Result<()> {
+        instructions::reservation::reserve_stock(ctx, product_uuid, quantity)
+    }
+
+    pub fn release_reservation(ctx: Context<ReleaseReservation>) -> Result<()> {
+        instructions::reservation::release_reservation(ctx)
     }
 
     // Product operations
+    #[allow(clippy::too_many_arguments)]
     pub fn register_product(
-        ctx: Context<RegisterProductAccounts>,
-        product_id: Pubkey,
-        store_id: Pubkey,
+        ctx: Context<RegisterProduct>,
+        product_uuid: [u8; 16],
         name: String,
         description: String,
         image_uri: String,
         price: u64,
-        inventory: Option<u64>,
+        stock: u64,
+        tokenized_type: types::TokenizedType,
+        metadata_uri: String,
         attributes: Vec<ProductAttribute>,
+        barcode: Option<String>,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Registering product: {:?}", product_id);
-        msg!("Store ID: {:?}", store_id);
-        msg!("Name: {}", name);
-        msg!("Description: {}", description);
-        msg!("Image URI: {}", image_uri);
-        msg!("Price: {}", price);
-        if let Some(inventory) = inventory {
-            msg!("Inventory: {}", inventory);
-        }
-        msg!("Attributes: {:?}", attributes);
-        Ok(())
+        instructions::product::register_product(
+            ctx,
+            product_uuid,
+            name,
+            description,
+            image_uri,
+            price,
+            stock,
+            tokenized_type,
+            metadata_uri,
+            attributes,
+            barcode,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update_product(
-        ctx: Context<UpdateProductAccounts>,
-        product_id: Pubkey,
+        ctx: Context<UpdateProduct>,
+        product_uuid: [u8; 16],
         name: Option<String>,
         description: Option<String>,
         image_uri: Option<String>,
         price: Option<u64>,
-        inventory: Option<u64>,
+        stock: Option<u64>,
         attributes: Option<Vec<ProductAttribute>>,
+        barcode: Option<String>,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Updating product: {:?}", product_id);
-        if let Some(name) = name {
-            msg!("New name: {}", name);
-        }
-        if let Some(description) = description {
-            msg!("New description: {}", description);
-        }
-        if let Some(image_uri) = image_uri {
-            msg!("New image URI: {}", image_uri);
-        }
-        if let Some(price) = price {
-            msg!("New price: {}", price);
-        }
-        if let Some(inventory) = inventory {
-            msg!("New inventory: {}", inventory);
-        }
-        if let Some(attributes) = attributes {
-            msg!("New attributes: {:?}", attributes);
-        }
-        Ok(())
+        instructions::product::update_product(
+            ctx,
+            product_uuid,
+            name,
+            description,
+            image_uri,
+            price,
+            stock,
+            attributes,
+            barcode,
+        )
     }
 
     pub fn deactivate_product(
-        ctx: Context<DeactivateProductAccounts>,
-        product_id: Pubkey,
+        ctx: Context<DeactivateProduct>,
+        product_uuid: [u8; 16],
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Deactivating product: {:?}", product_id);
-        Ok(())
+        instructions::product::deactivate_product(ctx, product_uuid)
     }
 
-    pub fn purchase_cart(
-        ctx: Context<PurchaseCartAccounts>,
-        product_ids: Vec<Pubkey>,
+    pub fn purchase_cart<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PurchaseCart<'info>>,
+        product_uuids: Vec<[u8; 16]>,
         quantities: Vec<u64>,
         total_amount_paid: u64,
     ) -> Result<()> {
-        // Validate cart data
-        require!(
-            product_ids.len() == quantities.len() && !product_ids.is_empty(),
-            CustomError::InvalidCart
-        );
-
-        // Calculate total price (in real implementation, you would validate product prices)
-        // For simplicity, we're assuming total_amount_paid is correct
-        let total_price = total_amount_paid;
-
-        // Transfer payment from buyer to escrow account
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            anchor_lang::system_program::Transfer {
-                from: ctx.accounts.buyer.to_account_info(),
-                to: ctx.accounts.escrow_account.to_account_info(),
-            },
-        );
-
-        anchor_lang::system_program::transfer(cpi_context, total_price)?;
-
-        // Get current escrow balance before updating
-        let escrow_balance = ctx.accounts.escrow_account.balance;
-
-        // Update escrow account
-        ctx.accounts.escrow_account.store = ctx.accounts.store.key();
-        ctx.accounts.escrow_account.balance = escrow_balance
-            .checked_add(total_price)
-            .ok_or(CustomError::ArithmeticError)?;
-
-        // Update receipt
-        let receipt = &mut ctx.accounts.receipt;
-        receipt.product_ids = product_ids.clone();
-        receipt.quantities = quantities.clone();
-        receipt.total_paid = total_price;
-        receipt.gas_fee = 0; // For simplicity
-        receipt.store = ctx.accounts.store.key();
-        receipt.buyer = ctx.accounts.buyer.key();
-        receipt.timestamp = Clock::get()?.unix_timestamp;
-
-
-        // Emit purchase event
-        emit!(PurchaseCompleted {
-            store: ctx.accounts.store.key(),
-            buyer: ctx.accounts.buyer.key(),
-            total_amount: total_price,
-            timestamp: receipt.timestamp,
-        });
-
-        // Log purchase
-        msg!("Purchase completed - Total paid: {}", total_price);
-        msg!(
-            "Funds held in escrow: {}",
-            ctx.accounts.escrow_account.balance
-        );
-
-        Ok(())
+        instructions::product::purchase_cart(ctx, product_uuids, quantities, total_amount_paid)
     }
 
     // Admin operations
-    pub fn add_platform_admin(
-        ctx: Context<AddPlatformAdminAccounts>,
-        admin_pubkey: Pubkey,
-        admin_name: String,
-        root_password: String,
+    pub fn initialize_platform_config(
+        ctx: Context<InitializePlatformConfig>,
+        super_admin: Pubkey,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Adding platform admin: {:?}", admin_pubkey);
-        msg!("Admin name: {}", admin_name);
-        msg!("Root password provided (hidden)");
-        Ok(())
+        instructions::admin::initialize_platform_config(ctx, super_admin)
+    }
+
+    pub fn add_platform_admin(ctx: Context<AddPlatformAdmin>, admin_pubkey: Pubkey) -> Result<()> {
+        instructions::admin::add_platform_admin(ctx, admin_pubkey)
     }
 
     pub fn remove_platform_admin(
-        ctx: Context<RemovePlatformAdminAccounts>,
+        ctx: Context<RemovePlatformAdmin>,
         admin_pubkey: Pubkey,
-        root_password: String,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Removing platform admin: {:?}", admin_pubkey);
-        msg!("Root password provided (hidden)");
-        Ok(())
+        instructions::admin::remove_platform_admin(ctx, admin_pubkey)
     }
 
     pub fn add_store_admin(
-        ctx: Context<AddStoreAdminAccounts>,
+        ctx: Context<AddAdmin>,
         store_id: Pubkey,
         admin_pubkey: Pubkey,
         role: types::AdminRoleType,
+        label: String,
+        valid_from: Option<i64>,
+        valid_until: Option<i64>,
     ) -> Result<()> {
-        // Get a mutable reference to the store account
-        let store = &mut ctx.accounts.store;
-        let authority = &ctx.accounts.authority;
+        instructions::store::add_admin(
+            ctx,
+            store_id,
+            admin_pubkey,
+            role,
+            label,
+            valid_from,
+            valid_until,
+        )
+    }
 
-        // Check that the authority is the store owner
-        require!(
-            authority.key() == store.owner,
-            error::CustomError::Unauthorized
-        );
-        require!(authority.is_signer, error::CustomError::Unauthorized);
-
-        // Check if admin already exists
-        if store
-            .admin_roles
-            .iter()
-            .any(|r| r.admin_pubkey == admin_pubkey)
-        {
-            return Err(error::CustomError::AdminAlreadyExists.into());
-        }
+    // Custom store roles
+    pub fn create_store_role(
+        ctx: Context<CreateStoreRole>,
+        name: String,
+        capabilities: u16,
+    ) -> Result<()> {
+        instructions::roles::create_store_role(ctx, name, capabilities)
+    }
 
-        // Add the admin to the store's admin_roles vector
-        store.admin_roles.push(state::store::AdminRole {
-            admin_pubkey,
-            role_type: role,
-        });
+    pub fn update_store_role(
+        ctx: Context<EditStoreRole>,
+        role_id: u8,
+        name: Option<String>,
+        capabilities: Option<u16>,
+    ) -> Result<()> {
+        instructions::roles::update_store_role(ctx, role_id, name, capabilities)
+    }
 
-        msg!("Admin added successfully: {:?}", admin_pubkey);
-        Ok(())
+    pub fn revoke_store_role(ctx: Context<EditStoreRole>, role_id: u8) -> Result<()> {
+        instructions::roles::revoke_store_role(ctx, role_id)
     }
 
     pub fn remove_store_admin(
-        ctx: Context<RemoveStoreAdminAccounts>,
+        ctx: Context<RemoveAdmin>,
         store_id: Pubkey,
         admin_pubkey: Pubkey,
     ) -> Result<()> {
-        // Simplified implementation
-        msg!("Removing store admin: {:?}", admin_pubkey);
-        msg!("Store ID: {:?}", store_id);
-        Ok(())
+        instructions::store::remove_admin(ctx, store_id, admin_pubkey)
     }
 
+    pub fn suspend_store_admin(
+        ctx: Context<SuspendAdmin>,
+        store_id: Pubkey,
+        admin_pubkey: Pubkey,
+        suspended: bool,
+    ) -> Result<()> {
+        instructions::store::suspend_admin(ctx, store_id, admin_pubkey, suspended)
+    }
 
-    // Function to release funds from escrow to store owner
-    pub fn release_escrow(ctx: Context<ReleaseEscrowAccounts>, amount: u64) -> Result<()> {
-        // Check if escrow has enough balance
-        require!(
-            ctx.accounts.escrow_account.balance >= amount,
-            CustomError::InsufficientEscrowBalance
-        );
+    // Permissionless: close staff records whose grant has expired
+    pub fn remove_expired_staff<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveExpiredStaff<'info>>,
+    ) -> Result<()> {
+        instructions::store::remove_expired_staff(ctx)
+    }
 
-        // Transfer from escrow to store owner
-        let cpi_accounts = anchor_lang::system_program::Transfer {
-            from: ctx.accounts.escrow_account.to_account_info(),
-            to: ctx.accounts.store_owner.to_account_info(),
-        };
 
-        let cpi_program = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    // Release a purchase's matured escrow funds to the store's payout address
+    pub fn release_escrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseEscrow<'info>>,
+    ) -> Result<()> {
+        instructions::product::release_escrow(ctx)
+    }
 
-        anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+    pub fn update_settlement_config(
+        ctx: Context<UpdateSettlementConfig>,
+        dispute_window: Option<i64>,
+        payout_address: Option<Pubkey>,
+        crank_tip: Option<u64>,
+        refund_limit: Option<u64>,
+    ) -> Result<()> {
+        instructions::store::update_settlement_config(
+            ctx,
+            dispute_window,
+            payout_address,
+            crank_tip,
+            refund_limit,
+        )
+    }
 
-        // Update escrow balance
-        ctx.accounts.escrow_account.balance = ctx.accounts.escrow_account.balance.checked_sub(amount).unwrap();
+    // Store loyalty program
+    pub fn initialize_loyalty(ctx: Context<InitializeLoyalty>, earn_rate_bps: u16) -> Result<()> {
+        instructions::loyalty::initialize_loyalty(ctx, earn_rate_bps)
+    }
 
-        // Log the release
-        msg!("Released {} lamports from escrow to store owner", amount);
-        msg!(
-            "Remaining escrow balance: {}",
-            ctx.accounts.escrow_account.balance
-        );
+    pub fn update_loyalty_config(
+        ctx: Context<UpdateLoyaltyConfig>,
+        earn_rate_bps: Option<u16>,
+        is_active: Option<bool>,
+    ) -> Result<()> {
+        instructions::loyalty::update_loyalty_config(ctx, earn_rate_bps, is_active)
+    }
 
-        Ok(())
+    pub fn redeem_loyalty_points(ctx: Context<RedeemLoyaltyPoints>, points: u64) -> Result<()> {
+        instructions::loyalty::redeem_loyalty_points(ctx, points)
     }
 
-    // Function to refund funds from escrow to buyer
-    pub fn refund_from_escrow(ctx: Context<RefundEscrowAccounts>, amount: u64) -> Result<()> {
-        // Check if escrow has enough balance
-        require!(
-            ctx.accounts.escrow_account.balance >= amount,
-            CustomError::InsufficientEscrowBalance
-        );
+    // Fungible product vouchers
+    pub fn initialize_voucher_mint(
+        ctx: Context<InitializeVoucherMint>,
+        product_uuid: [u8; 16],
+    ) -> Result<()> {
+        instructions::voucher::initialize_voucher_mint(ctx, product_uuid)
+    }
 
-        // Transfer from escrow to buyer (refund)
-        let cpi_accounts = anchor_lang::system_program::Transfer {
-            from: ctx.accounts.escrow_account.to_account_info(),
-            to: ctx.accounts.buyer.to_account_info(),
-        };
+    pub fn redeem_vouchers(
+        ctx: Context<RedeemVouchers>,
+        product_uuid: [u8; 16],
+        amount: u64,
+    ) -> Result<()> {
+        instructions::voucher::redeem_vouchers(ctx, product_uuid, amount)
+    }
+
+    // Purchase risk rules
+    pub fn update_risk_config(
+        ctx: Context<UpdateRiskConfig>,
+        max_cart_value: Option<u64>,
+        max_purchases_per_hour: Option<u16>,
+        min_account_age: Option<i64>,
+        hold_flagged: Option<bool>,
+    ) -> Result<()> {
+        instructions::risk::update_risk_config(
+            ctx,
+            max_cart_value,
+            max_purchases_per_hour,
+            min_account_age,
+            hold_flagged,
+        )
+    }
 
-        let cpi_program = ctx.accounts.system_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    pub fn clear_anomaly(ctx: Context<ClearAnomaly>) -> Result<()> {
+        instructions::risk::clear_anomaly(ctx)
+    }
 
-        anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+    pub fn set_payment_mint(ctx: Context<SetPaymentMint>) -> Result<()> {
+        instructions::store::set_payment_mint(ctx)
+    }
 
-        // Update escrow balance
-        ctx.accounts.escrow_account.balance = ctx.accounts.escrow_account.balance.checked_sub(amount).unwrap();
+    // Permissionless: settle a batch of matured receipts to the store payout address
+    pub fn settle_matured_purchases<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettleMaturedPurchases<'info>>,
+    ) -> Result<()> {
+        instructions::product::settle_matured_purchases(ctx)
+    }
 
-        // Log the refund
-        msg!("Refunded {} lamports from escrow to buyer", amount);
-        msg!(
-            "Remaining escrow balance: {}",
-            ctx.accounts.escrow_account.balance
-        );
+    pub fn fund_crank_budget(ctx: Context<FundCrankBudget>, amount: u64) -> Result<()> {
+        instructions::product::fund_crank_budget(ctx, amount)
+    }
 
-        Ok(())
+    // Refund part or all of a purchase from escrow to its buyer
+    pub fn refund_from_escrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundPurchase<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::product::refund_from_escrow(ctx, amount)
+    }
+
+    // Refund selected receipt lines at the price paid and restock them
+    pub fn refund_line_items<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundPurchase<'info>>,
+        line_indices: Vec<u8>,
+        quantities: Vec<u64>,
+    ) -> Result<()> {
+        instructions::product::refund_line_items(ctx, line_indices, quantities)
+    }
+
+    // Return request workflow
+    pub fn request_return(
+        ctx: Context<RequestReturn>,
+        reason_code: u8,
+        evidence_uri: String,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::returns::request_return(ctx, reason_code, evidence_uri, amount)
+    }
+
+    pub fn approve_return(ctx: Context<ReviewReturn>) -> Result<()> {
+        instructions::returns::approve_return(ctx)
+    }
+
+    pub fn reject_return(ctx: Context<ReviewReturn>) -> Result<()> {
+        instructions::returns::reject_return(ctx)
+    }
+
+    pub fn pay_return<'info>(ctx: Context<'_, '_, 'info, 'info, PayReturn<'info>>) -> Result<()> {
+        instructions::returns::pay_return(ctx)
     }
 }
 
@@ -1,46 +1,94 @@
 // Admin and platform admin-related instructions, events, and accounts will be placed here.
 
-use crate::error;
-use crate::utils::auth::{check_root_password, is_super_root_admin};
+use crate::error::CustomError;
+use crate::program::Sodap;
 use anchor_lang::prelude::*;
 
 // Platform admin accounts
+/// Singleton platform configuration, created once by the program upgrade authority
 #[account]
-pub struct PlatformAdmins {
+pub struct PlatformConfig {
+    pub super_admin: Pubkey,
     pub admins: Vec<Pubkey>,
+    pub bump: u8,
 }
 
-impl PlatformAdmins {
-    pub const LEN: usize = 4 + 32 * 10; // Up to 10 platform admins
+impl PlatformConfig {
+    pub const MAX_ADMINS: usize = 10;
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // super_admin
+        4 + 32 * Self::MAX_ADMINS + // admins
+        1; // bump
+
+    pub fn is_platform_admin(&self, key: &Pubkey) -> bool {
+        self.super_admin == *key || self.admins.contains(key)
+    }
 }
 
 // Platform admin events
+#[event]
+pub struct PlatformConfigInitialized {
+    pub super_admin: Pubkey,
+    pub initialized_at: i64,
+}
+
 #[event]
 pub struct PlatformAdminAdded {
     pub admin_pubkey: Pubkey,
+    pub added_by: Pubkey,
     pub added_at: i64,
 }
 
 #[event]
 pub struct PlatformAdminRemoved {
     pub admin_pubkey: Pubkey,
+    pub removed_by: Pubkey,
     pub removed_at: i64,
 }
 
+#[derive(Accounts)]
+pub struct InitializePlatformConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = PlatformConfig::LEN,
+        seeds = [b"platform_config"],
+        bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Sodap>,
+    /// Only the program upgrade authority can bootstrap the platform
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ CustomError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct AddPlatformAdmin<'info> {
-    #[account(mut, seeds = [b"platform_admins"], bump)]
-    pub platform_admins: Account<'info, PlatformAdmins>,
-    /// CHECK: This is safe because we only check that the signer is the super root admin
-    #[account(signer)]
-    pub signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"platform_config"],
+        bump = platform_config.bump,
+        has_one = super_admin @ CustomError::Unauthorized
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    pub super_admin: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct RemovePlatformAdmin<'info> {
-    #[account(mut, seeds = [b"platform_admins"], bump)]
-    pub platform_admins: Account<'info, PlatformAdmins>,
-    /// CHECK: This is safe because we only check that the signer is the super root admin
-    #[account(signer)]
-    pub signer: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"platform_config"],
+        bump = platform_config.bump,
+        has_one = super_admin @ CustomError::Unauthorized
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    pub super_admin: Signer<'info>,
 }
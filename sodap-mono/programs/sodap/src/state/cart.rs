@@ -0,0 +1,159 @@
+// Glob import: nesting PurchaseCart needs the client modules Anchor generates beside it
+use super::product::*;
+use super::store::Store;
+use crate::error::CustomError;
+use anchor_lang::prelude::*;
+
+/// An in-store cart built one scan at a time, at `[b"cart_session", store, buyer]`.
+/// Each line's unit price is locked when the product is first scanned, and
+/// the session expires `TTL` seconds after the last scan, but never more than
+/// `MAX_LIFETIME` after it was opened, so locked prices can't be held forever.
+#[account]
+pub struct CartSession {
+    pub store: Pubkey,
+    pub buyer: Pubkey,
+    pub product_uuids: Vec<[u8; 16]>,
+    pub quantities: Vec<u64>,
+    pub unit_prices: Vec<u64>, // locked at scan time, per line
+    pub expires_at: i64,
+    pub bump: u8,
+    pub created_at: i64, // when the session was opened; bounds how long scans extend it
+}
+
+impl CartSession {
+    pub const TTL: i64 = 30 * 60;
+    pub const MAX_LIFETIME: i64 = 2 * 60 * 60;
+
+    pub const LEN: usize = 8 +                      // discriminator
+        32 +                                        // store
+        32 +                                        // buyer
+        (4 + Purchase::MAX_PRODUCTS * 16) +         // product_uuids
+        (4 + Purchase::MAX_PRODUCTS * 8) +          // quantities
+        (4 + Purchase::MAX_PRODUCTS * 8) +          // unit_prices
+        8 +                                         // expires_at
+        1 +                                         // bump
+        8; // created_at
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Drop every line, for a session reopened after it expired
+    pub fn clear(&mut self) {
+        self.product_uuids.clear();
+        self.quantities.clear();
+        self.unit_prices.clear();
+    }
+
+    /// Cart list total at the locked prices
+    pub fn total(&self) -> Result<u64> {
+        let mut total: u64 = 0;
+        for (price, quantity) in self.unit_prices.iter().zip(self.quantities.iter()) {
+            total = price
+                .checked_mul(*quantity)
+                .and_then(|line| total.checked_add(line))
+                .ok_or(CustomError::PriceOverflow)?;
+        }
+        Ok(total)
+    }
+}
+
+#[event]
+pub struct CartItemScanned {
+    pub store_id: Pubkey,
+    pub buyer_id: Pubkey,
+    pub product_uuid: [u8; 16],
+    pub quantity: u64,   // line quantity after the scan
+    pub unit_price: u64, // locked price for the line
+    pub cart_total: u64, // session total at locked prices
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct CartItemRemoved {
+    pub store_id: Pubkey,
+    pub buyer_id: Pubkey,
+    pub product_uuid: [u8; 16],
+    pub quantity: u64, // line quantity left, zero when the line was dropped
+    pub cart_total: u64,
+}
+
+#[event]
+pub struct CartSessionClosed {
+    pub store_id: Pubkey,
+    pub buyer_id: Pubkey,
+    pub expired: bool,
+    pub timestamp: i64,
+}
+
+#[derive(Accounts)]
+#[instruction(product_uuid: [u8; 16])]
+pub struct ScanCartItem<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
+        bump = product.bump,
+        has_one = store
+    )]
+    pub product: Account<'info, Product>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = CartSession::LEN,
+        seeds = [b"cart_session", store.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub cart_session: Account<'info, CartSession>,
+    /// CHECK: The buyer's Reservation PDA for the product, whose units count
+    /// as available; may be uninitialized
+    #[account(seeds = [b"reservation", product.key().as_ref(), buyer.key().as_ref()], bump)]
+    pub reservation: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveCartItem<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        mut,
+        seeds = [b"cart_session", store.key().as_ref(), buyer.key().as_ref()],
+        bump = cart_session.bump,
+        has_one = store,
+        has_one = buyer
+    )]
+    pub cart_session: Account<'info, CartSession>,
+    pub buyer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CheckoutCartSession<'info> {
+    /// The same accounts as `purchase_cart`
+    pub purchase: PurchaseCart<'info>,
+    #[account(
+        mut,
+        seeds = [b"cart_session", purchase.store.key().as_ref(), purchase.buyer.key().as_ref()],
+        bump = cart_session.bump,
+        constraint = cart_session.store == purchase.store.key() @ CustomError::InvalidStore,
+        constraint = cart_session.buyer == purchase.buyer.key() @ CustomError::Unauthorized
+    )]
+    pub cart_session: Account<'info, CartSession>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCartSession<'info> {
+    #[account(
+        mut,
+        close = buyer,
+        seeds = [b"cart_session", cart_session.store.as_ref(), buyer.key().as_ref()],
+        bump = cart_session.bump,
+        has_one = buyer
+    )]
+    pub cart_session: Account<'info, CartSession>,
+    /// CHECK: The session's buyer, who gets its rent back; checked by has_one
+    #[account(mut)]
+    pub buyer: UncheckedAccount<'info>,
+    /// The buyer at any time, or anyone once the session has expired
+    pub closer: Signer<'info>,
+}
@@ -0,0 +1,150 @@
+use super::product::PurchaseCounter;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
+use super::store::Store;
+use crate::error::CustomError;
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+/// Per-store loyalty program. Points are minted from `mint`, whose mint
+/// authority is this PDA. One point is worth one base unit of the store's
+/// payment currency when redeemed.
+#[account]
+pub struct LoyaltyConfig {
+    pub store: Pubkey,
+    pub mint: Pubkey,
+    pub earn_rate_bps: u16, // points earned per 10_000 base units paid
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+impl LoyaltyConfig {
+    pub const MAX_EARN_RATE_BPS: u16 = 10_000;
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        32 +                   // mint
+        2 +                    // earn_rate_bps
+        1 +                    // is_active
+        1; // bump
+
+    /// Points earned on a payment of `amount` base units
+    pub fn points_for(&self, amount: u64) -> anchor_lang::Result<u64> {
+        let points = (amount as u128)
+            .checked_mul(self.earn_rate_bps as u128)
+            .ok_or(CustomError::ArithmeticError)?
+            / 10_000;
+        u64::try_from(points).map_err(|_| CustomError::ArithmeticError.into())
+    }
+}
+
+#[event]
+pub struct LoyaltyConfigUpdated {
+    pub store_id: Pubkey,
+    pub mint: Pubkey,
+    pub earn_rate_bps: u16,
+    pub is_active: bool,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct LoyaltyPointsRedeemed {
+    pub store_id: Pubkey,
+    pub buyer: Pubkey,
+    pub points: u64,
+    pub pending_discount: u64,
+    pub timestamp: i64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLoyalty<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        init,
+        payer = authority,
+        space = LoyaltyConfig::LEN,
+        seeds = [b"loyalty_config", store.key().as_ref()],
+        bump
+    )]
+    pub loyalty_config: Account<'info, LoyaltyConfig>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"loyalty_mint", store.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = loyalty_config,
+        mint::token_program = token_program
+    )]
+    pub loyalty_mint: InterfaceAccount<'info, Mint>,
+    /// A store admin with the MANAGE_STORE capability
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLoyaltyConfig<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        mut,
+        seeds = [b"loyalty_config", store.key().as_ref()],
+        bump = loyalty_config.bump
+    )]
+    pub loyalty_config: Account<'info, LoyaltyConfig>,
+    /// A store admin with the MANAGE_STORE capability
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemLoyaltyPoints<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        seeds = [b"loyalty_config", store.key().as_ref()],
+        bump = loyalty_config.bump
+    )]
+    pub loyalty_config: Account<'info, LoyaltyConfig>,
+    #[account(
+        mut,
+        address = loyalty_config.mint @ CustomError::LoyaltyMintNotFound
+    )]
+    pub loyalty_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = loyalty_mint,
+        token::authority = buyer,
+        token::token_program = token_program
+    )]
+    pub buyer_loyalty_account: InterfaceAccount<'info, TokenAccount>,
+    /// Holds the redeemed discount until the buyer's next checkout
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = PurchaseCounter::LEN,
+        seeds = [b"purchase_counter", store.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub purchase_counter: Account<'info, PurchaseCounter>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,57 +1,218 @@
 use crate::error::CustomError;
 use anchor_lang::prelude::*;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 // Submodules for on-chain accounts and context structs
 pub mod admin;
+pub mod cart;
+pub mod loyalty;
 pub mod product;
+pub mod reservation;
+pub mod returns;
+pub mod risk;
+pub mod roles;
+pub mod staff;
 pub mod store;
 pub mod user;
+pub mod voucher;
 
 // Re-export all relevant structs and context types
 pub use admin::*;
+pub use cart::*;
+pub use loyalty::*;
 pub use product::PurchaseCart;
 pub use product::*;
+pub use reservation::*;
+pub use returns::*;
+pub use risk::*;
+pub use roles::*;
+pub use staff::*;
 pub use store::Store;
 pub use store::*;
 pub use user::*;
+pub use voucher::*;
 
 #[derive(Accounts)]
 pub struct ReleaseEscrow<'info> {
     #[account(
         mut,
         seeds = [b"escrow", store.key().as_ref()],
-        bump
+        bump,
+        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
+    )]
+    pub escrow_account: Account<'info, Escrow>,
+    #[account(mut)]
+    pub store: Account<'info, Store>,
+    /// The receipt whose matured funds are being released
+    #[account(mut, has_one = store @ CustomError::InvalidStore)]
+    pub receipt: Account<'info, Purchase>,
+    /// CHECK: Only receives funds, and must be the store's payout address
+    #[account(
+        mut,
+        address = store.payout_address @ CustomError::InvalidPayoutAddress
+    )]
+    pub payout: AccountInfo<'info>,
+    /// A store admin with the RELEASE_ESCROW capability
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    /// Payout address's token account for the store's payment mint; `None` for SOL-priced stores
+    #[account(mut)]
+    pub payout_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The escrow PDA's token account for the store's payment mint
+    #[account(mut)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The store's payment mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+pub struct RefundPurchase<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", store.key().as_ref()],
+        bump,
+        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
     )]
     pub escrow_account: Account<'info, Escrow>,
     pub store: Account<'info, Store>,
+    /// The receipt being refunded
     #[account(
         mut,
-        constraint = store_owner.key() == store.owner @ CustomError::Unauthorized
+        has_one = store @ CustomError::InvalidStore,
+        has_one = buyer @ CustomError::Unauthorized
+    )]
+    pub receipt: Account<'info, Purchase>,
+    /// CHECK: Only receives funds, and must be the buyer recorded on the receipt
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+    /// A store admin with the ISSUE_REFUNDS capability
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
     )]
-    pub store_owner: Signer<'info>,
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
     pub system_program: Program<'info, System>,
+    /// Buyer's token account for the store's payment mint; `None` for SOL-priced stores
+    #[account(mut)]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The escrow PDA's token account for the store's payment mint
+    #[account(mut)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The store's payment mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    /// Required to burn returned certificates and vouchers when refunding their lines
+    pub product_token_program: Option<Program<'info, Token2022>>,
+    /// The buyer's purchase counter, which gets back the loyalty points redeemed
+    /// against refunded lines; required when the receipt used a loyalty discount
+    #[account(
+        mut,
+        seeds = [b"purchase_counter", store.key().as_ref(), buyer.key().as_ref()],
+        bump = purchase_counter.bump
+    )]
+    pub purchase_counter: Option<Account<'info, PurchaseCounter>>,
 }
 
 #[derive(Accounts)]
-pub struct RefundEscrow<'info> {
+pub struct SettleMaturedPurchases<'info> {
     #[account(
         mut,
         seeds = [b"escrow", store.key().as_ref()],
-        bump
+        bump,
+        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
     )]
     pub escrow_account: Account<'info, Escrow>,
+    #[account(mut)]
     pub store: Account<'info, Store>,
+    /// CHECK: Only receives funds, and must be the store's payout address
+    #[account(
+        mut,
+        address = store.payout_address @ CustomError::InvalidPayoutAddress
+    )]
+    pub payout: AccountInfo<'info>,
+    /// Anyone may crank; the store's crank tip is paid to this account
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    /// Payout address's token account for the store's payment mint; `None` for SOL-priced stores
     #[account(mut)]
-    pub buyer: Signer<'info>,
+    pub payout_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The escrow PDA's token account for the store's payment mint
+    #[account(mut)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The store's payment mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    // remaining_accounts: writable Purchase receipts of this store
+}
+
+#[derive(Accounts)]
+pub struct FundCrankBudget<'info> {
     #[account(
-        constraint = store_owner.key() == store.owner @ CustomError::Unauthorized
+        mut,
+        seeds = [b"escrow", store.key().as_ref()],
+        bump,
+        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
     )]
-    pub store_owner: Signer<'info>,
+    pub escrow_account: Account<'info, Escrow>,
+    pub store: Account<'info, Store>,
+    /// A store admin with the MANAGE_STORE capability, who pays the top-up
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
     pub system_program: Program<'info, System>,
 }
 
 #[account]
 pub struct Escrow {
     pub store: Pubkey,
-    pub balance: u64,
+    pub balance: u64,      // buyer funds held for the store
+    pub crank_budget: u64, // store-funded lamports for settlement crank tips
+}
+
+impl Escrow {
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        8 +                    // balance
+        8; // crank_budget
+}
+
+#[event]
+pub struct CrankBudgetFunded {
+    pub store_id: Pubkey,
+    pub amount: u64,
+    pub crank_budget: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PurchasesSettled {
+    pub store_id: Pubkey,
+    pub cranker: Pubkey,
+    pub receipts_settled: u32,
+    pub amount: u64,
+    pub tip: u64,
+    pub timestamp: i64,
 }
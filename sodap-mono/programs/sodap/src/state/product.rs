@@ -1,8 +1,16 @@
+use super::loyalty::LoyaltyConfig;
+use super::risk::RiskConfig;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
 use super::store::Store;
 use crate::error::CustomError;
 use crate::state::Escrow;
-use crate::types::{AnomalyFlag, TokenizedType, TransactionStatus};
+use crate::types::{AnomalyFlag, ProductAttribute, TokenizedType, TransactionStatus};
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 #[account]
 pub struct Product {
@@ -14,10 +22,99 @@ pub struct Product {
     pub metadata_uri: String,
     pub store: Pubkey,
     pub authority: Pubkey,
+    pub name: String,
+    pub description: String,
+    pub image_uri: String,
+    pub attributes: Vec<ProductAttribute>,
+    pub bump: u8,                 // bump for product PDA
+    pub certificates_minted: u64, // NonFungible serials issued, seeds the next certificate mint
+    pub barcode: String,          // EAN/UPC digits, empty when not indexed by a SkuIndex
+    pub reserved: u64,            // units held by Reservations, not counted in stock
 }
 
 impl Product {
-    pub const LEN: usize = 8 + 16 + 8 + 8 + 1 + (4 + 200) + 32 + 32;
+    pub const MAX_NAME_LEN: usize = 100;
+    pub const MAX_DESCRIPTION_LEN: usize = 500;
+    pub const MAX_URI_LEN: usize = 200;
+    pub const MAX_ATTRIBUTES: usize = 10;
+    pub const MAX_ATTRIBUTE_NAME_LEN: usize = 32;
+    pub const MAX_ATTRIBUTE_VALUE_LEN: usize = 64;
+    pub const MIN_BARCODE_LEN: usize = 8; // EAN-8
+    pub const MAX_BARCODE_LEN: usize = 14; // GTIN-14
+
+    pub const LEN: usize = 8 +          // discriminator
+        16 +                            // uuid
+        8 +                             // price
+        8 +                             // stock
+        1 +                             // tokenized_type
+        1 +                             // is_active
+        (4 + Self::MAX_URI_LEN) +       // metadata_uri
+        32 +                            // store
+        32 +                            // authority
+        (4 + Self::MAX_NAME_LEN) +      // name
+        (4 + Self::MAX_DESCRIPTION_LEN) + // description
+        (4 + Self::MAX_URI_LEN) +       // image_uri
+        4 +                             // Vec length prefix
+        (4 + Self::MAX_ATTRIBUTE_NAME_LEN + 4 + Self::MAX_ATTRIBUTE_VALUE_LEN)
+            * Self::MAX_ATTRIBUTES +    // attributes
+        1 +                             // bump
+        8 +                             // certificates_minted
+        (4 + Self::MAX_BARCODE_LEN) +   // barcode
+        8; // reserved
+
+    pub fn validate(&self) -> anchor_lang::Result<()> {
+        anchor_lang::require!(
+            !self.name.is_empty() && self.name.len() <= Self::MAX_NAME_LEN,
+            CustomError::InvalidProductName
+        );
+        anchor_lang::require!(
+            self.description.len() <= Self::MAX_DESCRIPTION_LEN,
+            CustomError::DescriptionTooLong
+        );
+        anchor_lang::require!(
+            self.image_uri.len() <= Self::MAX_URI_LEN && self.metadata_uri.len() <= Self::MAX_URI_LEN,
+            CustomError::InvalidMetadataUri
+        );
+        anchor_lang::require!(
+            self.attributes.len() <= Self::MAX_ATTRIBUTES,
+            CustomError::TooManyAttributes
+        );
+        anchor_lang::require!(
+            self.attributes.iter().all(|a| a.name.len() <= Self::MAX_ATTRIBUTE_NAME_LEN
+                && a.value.len() <= Self::MAX_ATTRIBUTE_VALUE_LEN),
+            CustomError::InvalidAttribute
+        );
+        anchor_lang::require!(
+            self.barcode.is_empty()
+                || ((Self::MIN_BARCODE_LEN..=Self::MAX_BARCODE_LEN).contains(&self.barcode.len())
+                    && self.barcode.bytes().all(|b| b.is_ascii_digit())),
+            CustomError::InvalidBarcode
+        );
+        Ok(())
+    }
+}
+
+/// Barcode lookup for a product, at `[b"sku", store, sha256(barcode)]`, so a
+/// scanner can derive the product account from the barcode alone
+#[account]
+pub struct SkuIndex {
+    pub store: Pubkey,
+    pub product: Pubkey,
+    pub product_uuid: [u8; 16],
+    pub bump: u8,
+}
+
+impl SkuIndex {
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        32 +                   // product
+        16 +                   // product_uuid
+        1; // bump
+
+    /// The barcode's seed: the sha256 of its digits
+    pub fn barcode_hash(barcode: &str) -> [u8; 32] {
+        hash(barcode.as_bytes()).to_bytes()
+    }
 }
 
 #[account]
@@ -31,14 +128,165 @@ pub struct Purchase {
     pub store: Pubkey,
     pub buyer: Pubkey,
     pub ts: i64,
+    pub purchase_index: u64, // buyer's purchase number at this store, part of the PDA seeds
+    pub bump: u8,
+    pub refunded_amount: u64, // cumulative amount refunded against total_paid
+    pub unit_prices: Vec<u64>, // price paid per unit, per line
+    pub refunded_quantities: Vec<u64>, // units returned, per line
+    pub settled_amount: u64,  // released from escrow to the store
+    pub disputed_amount: u64, // held by open return requests
+    pub token_mints: Vec<Pubkey>,  // certificate or voucher mint per line, default for untokenized lines
+    pub held: bool,           // flagged and kept out of settlement until cleared
+    pub matures_at: i64,      // end of the dispute window in force at checkout
+    pub loyalty_discount: u64, // redeemed points applied at checkout
 }
 
 impl Purchase {
-    pub const LEN: usize = 8 + (4 + 10 * 16) + (4 + 10 * 8) + 8 + 8 + 1 + 1 + 32 + 32 + 8;
+    pub const MAX_PRODUCTS: usize = 10;
+    pub const LEN: usize = 8
+        + (4 + Self::MAX_PRODUCTS * 16)
+        + (4 + Self::MAX_PRODUCTS * 8)
+        + 8
+        + 8
+        + 1
+        + 1
+        + 32
+        + 32
+        + 8
+        + 8
+        + 1
+        + 8
+        + (4 + Self::MAX_PRODUCTS * 8)
+        + (4 + Self::MAX_PRODUCTS * 8)
+        + 8
+        + 8
+        + (4 + Self::MAX_PRODUCTS * 32)
+        + 1
+        + 8
+        + 8;
+
+    /// Funds still held in escrow for this purchase
+    pub fn unsettled_amount(&self) -> anchor_lang::Result<u64> {
+        self.total_paid
+            .checked_sub(self.refunded_amount)
+            .and_then(|v| v.checked_sub(self.settled_amount))
+            .ok_or_else(|| CustomError::ArithmeticError.into())
+    }
+
+    /// Amount that can be released to the store at `now`: nothing while held
+    /// or until the dispute window the buyer paid under has passed, then
+    /// everything not refunded, settled or disputed
+    pub fn releasable_amount(&self, now: i64) -> anchor_lang::Result<u64> {
+        if self.held || now < self.matures_at {
+            return Ok(0);
+        }
+        Ok(self.unsettled_amount()?.saturating_sub(self.disputed_amount))
+    }
+
+    /// Escrowed share of `list_amount`, a refund priced at list prices.
+    /// `total_paid` is what escrow received, so it is below the list total
+    /// when a loyalty discount was applied or a Token-2022 transfer fee was
+    /// withheld at checkout.
+    pub fn escrowed_share(&self, list_amount: u64) -> anchor_lang::Result<u64> {
+        let list_total = self.list_total()?;
+        if list_total == 0 || list_total == self.total_paid {
+            return Ok(list_amount);
+        }
+        Ok((list_amount as u128 * self.total_paid as u128 / list_total as u128) as u64)
+    }
+
+    /// Redeemed loyalty points attributable to `list_amount`, a refund priced
+    /// at list prices. They go back to the buyer when those lines are refunded.
+    pub fn discount_share(&self, list_amount: u64) -> anchor_lang::Result<u64> {
+        let list_total = self.list_total()?;
+        if list_total == 0 {
+            return Ok(0);
+        }
+        Ok((list_amount as u128 * self.loyalty_discount as u128 / list_total as u128) as u64)
+    }
+
+    fn list_total(&self) -> anchor_lang::Result<u64> {
+        let mut list_total: u64 = 0;
+        for (price, quantity) in self.unit_prices.iter().zip(self.quantities.iter()) {
+            list_total = price
+                .checked_mul(*quantity)
+                .and_then(|line| list_total.checked_add(line))
+                .ok_or(CustomError::ArithmeticError)?;
+        }
+        Ok(list_total)
+    }
+
+    /// Record a refund against this receipt. Refunds are capped at what is still
+    /// held in escrow: `total_paid` less earlier refunds and settlements. A zero
+    /// amount records returned lines that were paid for entirely in points.
+    pub fn record_refund(&mut self, amount: u64) -> anchor_lang::Result<()> {
+        let refunded = self
+            .refunded_amount
+            .checked_add(amount)
+            .ok_or(CustomError::ArithmeticError)?;
+        anchor_lang::require!(
+            amount <= self.unsettled_amount()?,
+            CustomError::RefundExceedsPayment
+        );
+
+        // A receipt paid entirely in points is only refunded once every line is back
+        let fully_returned = self
+            .refunded_quantities
+            .iter()
+            .zip(self.quantities.iter())
+            .all(|(refunded, bought)| refunded == bought);
+        self.refunded_amount = refunded;
+        self.status = if refunded == self.total_paid && (refunded > 0 || fully_returned) {
+            TransactionStatus::Refunded
+        } else {
+            TransactionStatus::PartiallyRefunded
+        };
+        Ok(())
+    }
+}
+
+/// Per buyer and store purchase counter. The current `count` seeds the next
+/// receipt, so receipts are `[b"purchase", store, buyer, 0u64.to_le_bytes()]`,
+/// `[.., 1u64.to_le_bytes()]`, ... and can be enumerated off-chain.
+#[account]
+pub struct PurchaseCounter {
+    pub store: Pubkey,
+    pub buyer: Pubkey,
+    pub count: u64,
+    pub bump: u8,
+    pub loyalty_discount: u64, // redeemed points not yet applied at checkout
+    pub window_start: i64,     // start of the current hourly purchase window
+    pub window_purchases: u16, // purchases made since window_start
+}
+
+impl PurchaseCounter {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 2;
+
+    /// Count a purchase at `now` in the hourly window, starting a new window
+    /// once the current one has passed. Returns purchases in the window.
+    pub fn record_purchase_in_window(&mut self, now: i64) -> u16 {
+        if now.saturating_sub(self.window_start) >= RiskConfig::WINDOW {
+            self.window_start = now;
+            self.window_purchases = 0;
+        }
+        self.window_purchases = self.window_purchases.saturating_add(1);
+        self.window_purchases
+    }
 }
 
 #[derive(Accounts)]
-#[instruction(product_uuid: [u8; 16])]
+#[instruction(
+    product_uuid: [u8; 16],
+    name: String,
+    description: String,
+    image_uri: String,
+    price: u64,
+    stock: u64,
+    tokenized_type: TokenizedType,
+    metadata_uri: String,
+    attributes: Vec<ProductAttribute>,
+    barcode: Option<String>
+)]
 pub struct RegisterProduct<'info> {
     #[account(mut)]
     pub store: Account<'info, Store>,
@@ -50,52 +298,152 @@ pub struct RegisterProduct<'info> {
         bump
     )]
     pub product: Account<'info, Product>,
+    /// The barcode's SkuIndex; required exactly when a barcode is given
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SkuIndex::LEN,
+        seeds = [
+            b"sku",
+            store.key().as_ref(),
+            SkuIndex::barcode_hash(barcode.as_deref().unwrap_or_default()).as_ref()
+        ],
+        bump
+    )]
+    pub sku_index: Option<Account<'info, SkuIndex>>,
     #[account(mut)]
     pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(product_uuid: [u8; 16])]
+#[instruction(
+    product_uuid: [u8; 16],
+    name: Option<String>,
+    description: Option<String>,
+    image_uri: Option<String>,
+    price: Option<u64>,
+    stock: Option<u64>,
+    attributes: Option<Vec<ProductAttribute>>,
+    barcode: Option<String>
+)]
 pub struct UpdateProduct<'info> {
-    #[account(mut)]
     pub store: Account<'info, Store>,
     #[account(
         mut,
         seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
-        bump,
-        has_one = store,
-        has_one = authority
+        bump = product.bump,
+        has_one = store
     )]
     pub product: Account<'info, Product>,
+    /// The SkuIndex for the product's current barcode, closed when the
+    /// barcode changes; pass it only then
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"sku",
+            store.key().as_ref(),
+            SkuIndex::barcode_hash(&product.barcode).as_ref()
+        ],
+        bump = sku_index.bump
+    )]
+    pub sku_index: Option<Account<'info, SkuIndex>>,
+    /// The SkuIndex for the new barcode; pass it only when setting one
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SkuIndex::LEN,
+        seeds = [
+            b"sku",
+            store.key().as_ref(),
+            SkuIndex::barcode_hash(barcode.as_deref().unwrap_or_default()).as_ref()
+        ],
+        bump
+    )]
+    pub new_sku_index: Option<Account<'info, SkuIndex>>,
+    #[account(mut)]
     pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(product_uuid: [u8; 16])]
 pub struct DeactivateProduct<'info> {
-    #[account(mut)]
     pub store: Account<'info, Store>,
     #[account(
         mut,
         seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
-        bump,
-        has_one = store,
-        has_one = authority
+        bump = product.bump,
+        has_one = store
     )]
     pub product: Account<'info, Product>,
+    /// The SkuIndex for the product's barcode, closed with it; required when
+    /// the product has a barcode
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"sku",
+            store.key().as_ref(),
+            SkuIndex::barcode_hash(&product.barcode).as_ref()
+        ],
+        bump = sku_index.bump
+    )]
+    pub sku_index: Option<Account<'info, SkuIndex>>,
+    #[account(mut)]
     pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
 }
 
 #[derive(Accounts)]
 pub struct PurchaseCart<'info> {
     #[account(mut)]
     pub store: Account<'info, Store>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = PurchaseCounter::LEN,
+        seeds = [b"purchase_counter", store.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub purchase_counter: Account<'info, PurchaseCounter>,
     #[account(
         init,
         payer = buyer,
         space = Purchase::LEN,
-        seeds = [b"purchase", store.key().as_ref(), buyer.key().as_ref()],
+        seeds = [
+            b"purchase",
+            store.key().as_ref(),
+            buyer.key().as_ref(),
+            purchase_counter.count.to_le_bytes().as_ref()
+        ],
         bump
     )]
     pub receipt: Account<'info, Purchase>,
@@ -109,23 +457,103 @@ pub struct PurchaseCart<'info> {
     )]
     /// CHECK: We verify this is the store owner in the constraint above
     pub store_owner: AccountInfo<'info>,
+    /// CHECK: The store's RiskConfig PDA; may be uninitialized when the store has no risk rules
+    #[account(seeds = [b"risk_config", store.key().as_ref()], bump)]
+    pub risk_config: UncheckedAccount<'info>,
+    /// CHECK: The buyer's UserProfile PDA, used for the account age rule and
+    /// purchase history; may be uninitialized
+    #[account(mut, seeds = [b"user_profile", buyer.key().as_ref()], bump)]
+    pub buyer_profile: UncheckedAccount<'info>,
     /// The escrow account that holds funds during the purchase
     #[account(
-        mut,
+        init_if_needed,
+        payer = buyer,
+        space = Escrow::LEN,
         seeds = [b"escrow", store.key().as_ref()],
         bump
     )]
     pub escrow_account: Account<'info, Escrow>,
     pub system_program: Program<'info, System>,
+    /// Buyer's token account for the store's payment mint; `None` for SOL-priced stores
+    #[account(mut)]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The escrow PDA's token account for the store's payment mint
+    #[account(mut)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The store's payment mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    /// The store's loyalty program; `None` to skip earning points
+    pub loyalty_config: Option<Box<Account<'info, LoyaltyConfig>>>,
+    #[account(mut)]
+    pub loyalty_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    /// Buyer's token account for the loyalty mint, which receives earned points
+    #[account(mut)]
+    pub buyer_loyalty_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub loyalty_token_program: Option<Interface<'info, TokenInterface>>,
+    /// Required when the cart holds a NonFungible or Fungible product, to mint
+    /// its certificate or vouchers
+    pub product_token_program: Option<Program<'info, Token2022>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    // remaining_accounts: one writable Product PDA per cart line, in cart order,
+    // then a writable [mint, buyer ATA] pair per NonFungible or Fungible line,
+    // in cart order, then the buyer's writable Reservation PDA for any lines
+    // with reserved units
+}
+/// A product's barcode was indexed or, when `barcode` is empty, unindexed
+#[event]
+pub struct SkuIndexUpdated {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub barcode: String,
+    pub timestamp: i64,
 }
+
+#[event]
+pub struct ProductRegistered {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub product_uuid: [u8; 16],
+    pub price: u64,
+    pub stock: u64,
+    pub registered_by: Pubkey,
+    pub created_at: i64,
+}
+
 /// off‑chain log
 #[event]
 pub struct CartPurchased {
     pub store_id: Pubkey,
     pub buyer_id: Pubkey,
+    pub receipt: Pubkey,
+    pub purchase_index: u64,
     pub product_uuids: Vec<[u8; 16]>,
     pub quantities: Vec<u64>,
     pub total_paid: u64,
+    pub transfer_fee: u64,
+    pub loyalty_discount: u64,
+    pub loyalty_points_earned: u64,
     pub gas_fee: u64,
     pub timestamp: i64,
 }
+
+#[event]
+pub struct PurchaseRefunded {
+    pub store_id: Pubkey,
+    pub receipt: Pubkey,
+    pub buyer_id: Pubkey,
+    pub amount: u64,
+    pub total_refunded: u64,
+    pub status: TransactionStatus,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub store_id: Pubkey,
+    pub receipt: Pubkey,
+    pub payout_address: Pubkey,
+    pub amount: u64,
+    pub total_settled: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,101 @@
+use super::product::Product;
+use super::store::Store;
+use anchor_lang::prelude::*;
+
+/// Units of a product set aside for one buyer, at `[b"reservation", product, buyer]`.
+/// Reserved units leave `Product::stock` for `Product::reserved`, so no one
+/// else can buy them until checkout consumes the reservation or it is
+/// released. Topping up restarts the expiry, but never past `MAX_LIFETIME`
+/// after the reservation was made, and a buyer holds at most `MAX_QUANTITY`
+/// units of a product.
+#[account]
+pub struct Reservation {
+    pub store: Pubkey,
+    pub product: Pubkey,
+    pub buyer: Pubkey,
+    pub quantity: u64,
+    pub expires_at: i64, // anyone may release the units back to stock after this
+    pub bump: u8,
+    pub created_at: i64, // when the reservation was made; bounds how long top-ups extend it
+}
+
+impl Reservation {
+    pub const TTL: i64 = 15 * 60;
+    pub const MAX_LIFETIME: i64 = 60 * 60;
+    pub const MAX_QUANTITY: u64 = 10;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        32 +                   // product
+        32 +                   // buyer
+        8 +                    // quantity
+        8 +                    // expires_at
+        1 +                    // bump
+        8; // created_at
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[event]
+pub struct StockReserved {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub buyer_id: Pubkey,
+    pub quantity: u64, // units held by the reservation after this call
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct ReservationReleased {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub buyer_id: Pubkey,
+    pub quantity: u64, // units returned to stock
+    pub expired: bool,
+    pub timestamp: i64,
+}
+
+#[derive(Accounts)]
+#[instruction(product_uuid: [u8; 16])]
+pub struct ReserveStock<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        mut,
+        seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
+        bump = product.bump,
+        has_one = store
+    )]
+    pub product: Account<'info, Product>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = Reservation::LEN,
+        seeds = [b"reservation", product.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub reservation: Account<'info, Reservation>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseReservation<'info> {
+    #[account(mut, address = reservation.product)]
+    pub product: Account<'info, Product>,
+    #[account(
+        mut,
+        close = buyer,
+        seeds = [b"reservation", product.key().as_ref(), buyer.key().as_ref()],
+        bump = reservation.bump,
+        has_one = buyer
+    )]
+    pub reservation: Account<'info, Reservation>,
+    /// CHECK: The reservation's buyer, who gets its rent back; checked by has_one
+    #[account(mut)]
+    pub buyer: UncheckedAccount<'info>,
+    /// The buyer at any time, or anyone once the reservation has expired
+    pub closer: Signer<'info>,
+}
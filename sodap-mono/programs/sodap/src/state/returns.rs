@@ -0,0 +1,152 @@
+use super::product::Purchase;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
+use super::store::Store;
+use crate::error::CustomError;
+use crate::state::Escrow;
+use crate::types::ReturnRequestStatus;
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+/// A buyer's request to return (part of) a purchase, one per receipt.
+/// Moves Pending -> Approved -> Paid, or Pending/Approved -> Rejected.
+/// While Pending or Approved, `amount` is held out of escrow settlement.
+#[account]
+pub struct ReturnRequest {
+    pub receipt: Pubkey,
+    pub store: Pubkey,
+    pub buyer: Pubkey,
+    pub reason_code: u8,
+    pub evidence_uri: String,
+    pub amount: u64,
+    pub status: ReturnRequestStatus,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl ReturnRequest {
+    pub const MAX_EVIDENCE_URI_LEN: usize = 200;
+    pub const LEN: usize = 8 +                  // discriminator
+        32 +                                    // receipt
+        32 +                                    // store
+        32 +                                    // buyer
+        1 +                                     // reason_code
+        (4 + Self::MAX_EVIDENCE_URI_LEN) +      // evidence_uri
+        8 +                                     // amount
+        1 +                                     // status
+        8 +                                     // created_at
+        8 +                                     // updated_at
+        1; // bump
+}
+
+// Return request events
+#[event]
+pub struct ReturnRequested {
+    pub return_request: Pubkey,
+    pub receipt: Pubkey,
+    pub store_id: Pubkey,
+    pub buyer_id: Pubkey,
+    pub reason_code: u8,
+    pub amount: u64,
+    pub created_at: i64,
+}
+
+#[event]
+pub struct ReturnRequestUpdated {
+    pub return_request: Pubkey,
+    pub receipt: Pubkey,
+    pub store_id: Pubkey,
+    pub status: ReturnRequestStatus,
+    pub updated_by: Pubkey,
+    pub updated_at: i64,
+}
+
+#[derive(Accounts)]
+pub struct RequestReturn<'info> {
+    #[account(mut, has_one = buyer @ CustomError::Unauthorized)]
+    pub receipt: Account<'info, Purchase>,
+    #[account(
+        init,
+        payer = buyer,
+        space = ReturnRequest::LEN,
+        seeds = [b"return_request", receipt.key().as_ref()],
+        bump
+    )]
+    pub return_request: Account<'info, ReturnRequest>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Approve or reject a return request
+#[derive(Accounts)]
+pub struct ReviewReturn<'info> {
+    pub store: Account<'info, Store>,
+    #[account(mut, has_one = store @ CustomError::InvalidStore)]
+    pub receipt: Account<'info, Purchase>,
+    #[account(
+        mut,
+        seeds = [b"return_request", receipt.key().as_ref()],
+        bump = return_request.bump,
+        has_one = store @ CustomError::InvalidStore
+    )]
+    pub return_request: Account<'info, ReturnRequest>,
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
+
+/// Pay out an approved return request through the escrow refund path
+#[derive(Accounts)]
+pub struct PayReturn<'info> {
+    #[account(
+        mut,
+        seeds = [b"escrow", store.key().as_ref()],
+        bump,
+        constraint = escrow_account.store == store.key() @ CustomError::Unauthorized
+    )]
+    pub escrow_account: Account<'info, Escrow>,
+    pub store: Account<'info, Store>,
+    #[account(
+        mut,
+        has_one = store @ CustomError::InvalidStore,
+        has_one = buyer @ CustomError::Unauthorized
+    )]
+    pub receipt: Account<'info, Purchase>,
+    #[account(
+        mut,
+        seeds = [b"return_request", receipt.key().as_ref()],
+        bump = return_request.bump
+    )]
+    pub return_request: Account<'info, ReturnRequest>,
+    /// CHECK: Only receives funds, and must be the buyer recorded on the receipt
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    /// Buyer's token account for the store's payment mint; `None` for SOL-priced stores
+    #[account(mut)]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The escrow PDA's token account for the store's payment mint
+    #[account(mut)]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The store's payment mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
@@ -0,0 +1,124 @@
+use super::product::Purchase;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
+use super::store::Store;
+use crate::error::CustomError;
+use anchor_lang::prelude::*;
+
+/// Per-store purchase risk rules. A limit of zero disables that rule.
+/// Purchases breaking any rule are flagged `Suspicious` on their receipt.
+#[account]
+pub struct RiskConfig {
+    pub store: Pubkey,
+    pub max_cart_value: u64,          // cart list total, in the store's payment currency
+    pub max_purchases_per_hour: u16,  // per buyer, counted on their PurchaseCounter
+    pub min_account_age: i64,         // seconds since the buyer's UserProfile was created
+    pub hold_flagged: bool,           // keep flagged receipts out of settlement until cleared
+    pub bump: u8,
+}
+
+impl RiskConfig {
+    pub const WINDOW: i64 = 60 * 60;
+
+    // Bits of `PurchaseFlagged::reasons`
+    pub const CART_VALUE: u8 = 1 << 0;
+    pub const PURCHASE_RATE: u8 = 1 << 1;
+    pub const ACCOUNT_AGE: u8 = 1 << 2;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        8 +                    // max_cart_value
+        2 +                    // max_purchases_per_hour
+        8 +                    // min_account_age
+        1 +                    // hold_flagged
+        1; // bump
+
+    /// Rules broken by a purchase, as a bitmask of the reason constants.
+    /// `account_age` is `None` when the buyer has no profile.
+    pub fn evaluate(&self, cart_value: u64, purchases_this_hour: u16, account_age: Option<i64>) -> u8 {
+        let mut reasons = 0;
+        if self.max_cart_value > 0 && cart_value > self.max_cart_value {
+            reasons |= Self::CART_VALUE;
+        }
+        if self.max_purchases_per_hour > 0 && purchases_this_hour > self.max_purchases_per_hour {
+            reasons |= Self::PURCHASE_RATE;
+        }
+        let old_enough = matches!(account_age, Some(age) if age >= self.min_account_age);
+        if self.min_account_age > 0 && !old_enough {
+            reasons |= Self::ACCOUNT_AGE;
+        }
+        reasons
+    }
+}
+
+#[event]
+pub struct RiskConfigUpdated {
+    pub store_id: Pubkey,
+    pub max_cart_value: u64,
+    pub max_purchases_per_hour: u16,
+    pub min_account_age: i64,
+    pub hold_flagged: bool,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct PurchaseFlagged {
+    pub store_id: Pubkey,
+    pub receipt: Pubkey,
+    pub buyer_id: Pubkey,
+    pub reasons: u8,
+    pub held: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AnomalyCleared {
+    pub store_id: Pubkey,
+    pub receipt: Pubkey,
+    pub cleared_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRiskConfig<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = RiskConfig::LEN,
+        seeds = [b"risk_config", store.key().as_ref()],
+        bump
+    )]
+    pub risk_config: Account<'info, RiskConfig>,
+    /// A store admin with the MANAGE_STORE capability
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClearAnomaly<'info> {
+    pub store: Account<'info, Store>,
+    #[account(mut, has_one = store @ CustomError::InvalidStore)]
+    pub receipt: Account<'info, Purchase>,
+    /// Store owner or manager who reviewed the purchase
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
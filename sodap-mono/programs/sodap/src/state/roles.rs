@@ -0,0 +1,91 @@
+use super::store::Store;
+use crate::error::CustomError;
+use anchor_lang::prelude::*;
+
+/// A store-defined admin role, granting `capabilities` (bits from
+/// `types::Capabilities`) to admins assigned `AdminRoleType::Custom { role_id }`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RoleDefinition {
+    pub role_id: u8,
+    pub name: String,
+    pub capabilities: u16,
+}
+
+/// Per-store custom role definitions, at `[b"store_roles", store]`. Role IDs
+/// are never reused, so admins still assigned a revoked role hold nothing.
+#[account]
+pub struct StoreRoles {
+    pub store: Pubkey,
+    pub roles: Vec<RoleDefinition>,
+    pub next_role_id: u8,
+    pub bump: u8,
+}
+
+impl StoreRoles {
+    pub const MAX_ROLES: usize = 16;
+    pub const MAX_ROLE_NAME_LEN: usize = 32;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        4 +                    // roles Vec length prefix
+        (1 + (4 + Self::MAX_ROLE_NAME_LEN) + 2) * Self::MAX_ROLES + // roles (role_id + name + capabilities)
+        1 +                    // next_role_id
+        1; // bump
+
+    pub fn find(&self, role_id: u8) -> Option<&RoleDefinition> {
+        self.roles.iter().find(|role| role.role_id == role_id)
+    }
+
+    pub fn find_mut(&mut self, role_id: u8) -> anchor_lang::Result<&mut RoleDefinition> {
+        self.roles
+            .iter_mut()
+            .find(|role| role.role_id == role_id)
+            .ok_or_else(|| CustomError::RoleNotFound.into())
+    }
+}
+
+#[event]
+pub struct StoreRoleDefined {
+    pub store_id: Pubkey,
+    pub role_id: u8,
+    pub name: String,
+    pub capabilities: u16,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct StoreRoleRevoked {
+    pub store_id: Pubkey,
+    pub role_id: u8,
+    pub revoked_at: i64,
+}
+
+#[derive(Accounts)]
+pub struct CreateStoreRole<'info> {
+    #[account(has_one = owner @ CustomError::Unauthorized)]
+    pub store: Account<'info, Store>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = StoreRoles::LEN,
+        seeds = [b"store_roles", store.key().as_ref()],
+        bump
+    )]
+    pub store_roles: Account<'info, StoreRoles>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EditStoreRole<'info> {
+    #[account(has_one = owner @ CustomError::Unauthorized)]
+    pub store: Account<'info, Store>,
+    #[account(
+        mut,
+        seeds = [b"store_roles", store.key().as_ref()],
+        bump = store_roles.bump
+    )]
+    pub store_roles: Account<'info, StoreRoles>,
+    pub owner: Signer<'info>,
+}
@@ -0,0 +1,54 @@
+use super::store::Store;
+use crate::error::CustomError;
+use crate::types::AdminRoleType;
+use anchor_lang::prelude::*;
+
+/// A store admin, at `[b"staff", store, staff]`. The store owner needs no
+/// record; everyone else is authorized through theirs.
+#[account]
+pub struct StaffMember {
+    pub store: Pubkey,
+    pub staff: Pubkey,
+    pub role: AdminRoleType,
+    pub label: String,   // display name shown to other staff
+    pub is_active: bool, // false while suspended
+    pub added_at: i64,
+    pub valid_from: Option<i64>,  // grant takes effect at this time
+    pub valid_until: Option<i64>, // grant lapses at this time; anyone may then close the record
+    pub bump: u8,
+}
+
+impl StaffMember {
+    pub const MAX_LABEL_LEN: usize = 32;
+
+    pub const LEN: usize = 8 + // discriminator
+        32 +                   // store
+        32 +                   // staff
+        2 +                    // role (variant + custom role id)
+        (4 + Self::MAX_LABEL_LEN) + // label
+        1 +                    // is_active
+        8 +                    // added_at
+        (1 + 8) +              // valid_from
+        (1 + 8) +              // valid_until
+        1; // bump
+
+    /// Whether the grant is in force at `now`: not suspended and inside its window
+    pub fn is_active_at(&self, now: i64) -> bool {
+        self.is_active
+            && !self.valid_from.is_some_and(|from| now < from)
+            && !self.is_expired(now)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.valid_until.is_some_and(|until| now >= until)
+    }
+}
+
+#[derive(Accounts)]
+pub struct RemoveExpiredStaff<'info> {
+    pub store: Account<'info, Store>,
+    /// CHECK: Only receives the records' rent, and must be the store owner
+    #[account(mut, address = store.owner @ CustomError::Unauthorized)]
+    pub owner: AccountInfo<'info>,
+    // remaining_accounts: writable StaffMember records of this store
+}
@@ -1,6 +1,11 @@
-use crate::types::AdminRoleType;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
+use super::Escrow;
+use crate::types::{AdminRoleType, Capabilities};
 use crate::error::CustomError;
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 #[derive(Debug)]
 #[account]
@@ -13,30 +18,18 @@ pub struct Store {
     pub revenue: u64,    // accumulated withdrawn funds
     pub bump: u8,        // bump for store PDA
     pub escrow_bump: u8, // bump for escrow PDA
-    pub admin_roles: Vec<AdminRole>,
+    pub dispute_window: i64,    // seconds after purchase before escrow can be released; fixed per receipt at checkout
+    pub payout_address: Pubkey, // where released escrow funds are paid
+    pub crank_tip: u64,         // lamports paid per receipt settled by the crank
+    pub payment_mint: Option<Pubkey>, // SPL mint prices are quoted in; `None` for SOL
+    pub refund_limit: u64,            // largest refund a non-owner admin may issue
 }
 
 impl Store {
-    pub const MAX_ADMIN_ROLES: usize = 10;
-
-    // Space calculation:
-    // - 8 bytes for discriminator
-    // - 32 bytes for owner Pubkey
-    // - 4 + 200 bytes for name String
-    // - 4 + 500 bytes for description String
-    // - 4 + 200 bytes for logo_uri String
-    // - 1 byte for is_active bool
-    // - 8 bytes for revenue u64
-    // - 1 byte for bump
-    // - 1 byte for escrow_bump
-    // - 4 bytes for Vec length prefix
-    // - (32 + 1) * MAX_ADMIN_ROLES for admin_roles Vec (Pubkey + role_type)
-    // - 1 byte for is_active bool
-    // - 8 bytes for revenue u64
-    // - 1 byte for bump
-    // - 1 byte for escrow_bump
-    // - 4 bytes for Vec length prefix
-    // - (32 + 1) * MAX_ADMIN_ROLES for admin_roles Vec (Pubkey + role_type)
+    pub const DEFAULT_DISPUTE_WINDOW: i64 = 7 * 24 * 60 * 60;
+    pub const MAX_DISPUTE_WINDOW: i64 = 90 * 24 * 60 * 60;
+    pub const MAX_CRANK_TIP: u64 = 1_000_000;
+
     pub const LEN: usize = 8 +  // discriminator
         32 +                    // owner
         (4 + 200) +            // name
@@ -46,11 +39,60 @@ impl Store {
         8 +                     // revenue
         1 +                     // bump
         1 +                     // escrow_bump
-        4 +                     // Vec length prefix
-        (33 * Self::MAX_ADMIN_ROLES); // admin_roles (Pubkey + role_type)
+        8 +                     // dispute_window
+        32 +                    // payout_address
+        8 +                     // crank_tip
+        (1 + 32) +              // payment_mint
+        8; // refund_limit
+
+    /// The store owner, or active staff holding the Owner role
+    pub fn is_owner(&self, user: &Pubkey, staff: Option<&StaffMember>) -> bool {
+        self.owner == *user
+            || active_role(user, staff).is_some_and(|role| role == AdminRoleType::Owner)
+    }
+
+    /// Capabilities `user` holds in this store: all of them for the owner,
+    /// otherwise those of the role on their active `staff` record. Custom
+    /// roles need the store's `roles` account to resolve.
+    pub fn capabilities_of(
+        &self,
+        user: &Pubkey,
+        staff: Option<&StaffMember>,
+        roles: Option<&StoreRoles>,
+    ) -> u16 {
+        if self.owner == *user {
+            return Capabilities::ALL;
+        }
+        active_role(user, staff).map_or(0, |role| role_capabilities(role, roles))
+    }
 
-    pub fn validate_admin_roles(&self) -> anchor_lang::Result<()> {
-        anchor_lang::require!(self.admin_roles.len() <= Self::MAX_ADMIN_ROLES, CustomError::TooManyAdmins);
+    pub fn can(
+        &self,
+        user: &Pubkey,
+        staff: Option<&StaffMember>,
+        roles: Option<&StoreRoles>,
+        capability: u16,
+    ) -> bool {
+        self.capabilities_of(user, staff, roles) & capability == capability
+    }
+
+    /// Owners refund any amount; other admins need `ISSUE_REFUNDS` and stay
+    /// within `refund_limit`
+    pub fn check_refund(
+        &self,
+        user: &Pubkey,
+        staff: Option<&StaffMember>,
+        roles: Option<&StoreRoles>,
+        amount: u64,
+    ) -> anchor_lang::Result<()> {
+        if self.is_owner(user, staff) {
+            return Ok(());
+        }
+        anchor_lang::require!(
+            self.can(user, staff, roles, Capabilities::ISSUE_REFUNDS),
+            CustomError::Unauthorized
+        );
+        anchor_lang::require!(amount <= self.refund_limit, CustomError::RefundLimitExceeded);
         Ok(())
     }
 }
@@ -70,11 +112,32 @@ pub struct StoreUpdated {
     pub updated_at: i64,
 }
 
+#[event]
+pub struct SettlementConfigUpdated {
+    pub store_id: Pubkey,
+    pub dispute_window: i64,
+    pub payout_address: Pubkey,
+    pub crank_tip: u64,
+    pub refund_limit: u64,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct PaymentMintUpdated {
+    pub store_id: Pubkey,
+    pub payment_mint: Option<Pubkey>, // `None` once the store is priced in SOL again
+    pub escrow_vault: Option<Pubkey>,
+    pub updated_at: i64,
+}
+
 #[event]
 pub struct AdminAdded {
     pub store_id: Pubkey,
     pub admin_pubkey: Pubkey,
     pub role_type: AdminRoleType,
+    pub label: String,
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
     pub added_at: i64,
 }
 
@@ -82,16 +145,10 @@ pub struct AdminAdded {
 pub struct AdminRemoved {
     pub store_id: Pubkey,
     pub admin_pubkey: Pubkey,
+    pub suspended: bool, // the staff record is kept and can be reinstated
     pub removed_at: i64,
 }
 
-// Store/admin accounts
-#[derive(Debug, Clone, Copy, AnchorSerialize, AnchorDeserialize)]
-pub struct AdminRole {
-    pub admin_pubkey: Pubkey,
-    pub role_type: AdminRoleType,
-}
-
 // Context structs for store/admin instructions
 #[derive(Accounts)]
 #[instruction(store_id: Pubkey)]
@@ -111,48 +168,158 @@ pub struct RegisterStore<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(store_id: Pubkey)]
 pub struct UpdateStore<'info> {
+    #[account(mut)]
+    pub store: Account<'info, Store>,
+    /// A store admin with the MANAGE_STORE capability
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
     #[account(
-        mut,
-        seeds = [b"store", owner.key().as_ref()],
-        bump,
-        has_one = owner
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
     )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSettlementConfig<'info> {
+    #[account(mut, has_one = owner @ CustomError::Unauthorized)]
     pub store: Account<'info, Store>,
+    /// Owner key only, not MANAGE_STORE: this sets where settled funds go and
+    /// how much other admins may refund
     pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaymentMint<'info> {
+    #[account(mut, has_one = owner @ CustomError::Unauthorized)]
+    pub store: Account<'info, Store>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = Escrow::LEN,
+        seeds = [b"escrow", store.key().as_ref()],
+        bump
+    )]
+    pub escrow_account: Account<'info, Escrow>,
+    /// The mint to price the store in; omit to price it in SOL again
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    /// The escrow PDA's associated token account, which holds buyer payments;
+    /// required with `mint`
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = mint,
+        associated_token::authority = escrow_account,
+        associated_token::token_program = token_program
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Owner key only, not MANAGE_STORE: this changes the asset buyers pay
+    /// and the store is paid out in
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(store_id: Pubkey)]
+#[instruction(store_id: Pubkey, admin_pubkey: Pubkey)]
 pub struct AddAdmin<'info> {
+    pub store: Account<'info, Store>,
     #[account(
-        mut,
-        seeds = [b"store", owner.key().as_ref()],
-        bump,
-        has_one = owner
+        init_if_needed,
+        payer = authority,
+        space = StaffMember::LEN,
+        seeds = [b"staff", store.key().as_ref(), admin_pubkey.as_ref()],
+        bump
     )]
-    pub store: Account<'info, Store>,
-    pub owner: Signer<'info>,
+    pub staff_member: Account<'info, StaffMember>,
+    /// A store admin with the MANAGE_STAFF capability, who pays for the record
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when a custom role is involved
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(store_id: Pubkey)]
+#[instruction(store_id: Pubkey, admin_pubkey: Pubkey)]
 pub struct RemoveAdmin<'info> {
+    pub store: Account<'info, Store>,
+    /// CHECK: The admin's StaffMember PDA; checked in the handler so a missing
+    /// admin fails with AdminNotFound
     #[account(
         mut,
-        seeds = [b"store", owner.key().as_ref()],
-        bump,
-        has_one = owner
+        seeds = [b"staff", store.key().as_ref(), admin_pubkey.as_ref()],
+        bump
+    )]
+    pub staff_member: UncheckedAccount<'info>,
+    /// CHECK: Only receives the record's rent, and must be the store owner
+    #[account(mut, address = store.owner @ CustomError::Unauthorized)]
+    pub owner: AccountInfo<'info>,
+    /// A store admin with the MANAGE_STAFF capability, or the admin themselves
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    /// or is removing themselves
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
     )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when a custom role is involved
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
+
+#[derive(Accounts)]
+#[instruction(store_id: Pubkey, admin_pubkey: Pubkey)]
+pub struct SuspendAdmin<'info> {
     pub store: Account<'info, Store>,
-    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"staff", store.key().as_ref(), admin_pubkey.as_ref()],
+        bump = staff_member.bump
+    )]
+    pub staff_member: Account<'info, StaffMember>,
+    /// A store admin with the MANAGE_STAFF capability
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when a custom role is involved
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+}
+
+/// Capabilities granted by `role_type`, looking custom roles up in `roles`
+fn role_capabilities(role_type: AdminRoleType, roles: Option<&StoreRoles>) -> u16 {
+    match role_type {
+        AdminRoleType::Custom { role_id } => roles
+            .and_then(|roles| roles.find(role_id))
+            .map_or(0, |role| role.capabilities),
+        built_in => built_in.capabilities(),
+    }
 }
 
-pub fn has_role(store: &Store, user: &Pubkey, role: AdminRoleType) -> bool {
-    store
-        .admin_roles
-        .iter()
-        .any(|r| r.admin_pubkey == *user && r.role_type == role)
+/// Role on `user`'s staff record, unless the record is someone else's or not
+/// in force right now. Fails closed if the clock can't be read.
+fn active_role(user: &Pubkey, staff: Option<&StaffMember>) -> Option<AdminRoleType> {
+    let now = Clock::get().ok()?.unix_timestamp;
+    staff
+        .filter(|staff| staff.staff == *user && staff.is_active_at(now))
+        .map(|staff| staff.role)
 }
@@ -6,29 +6,58 @@ pub struct UserProfileUpdated {
     pub updated_at: i64,
 }
 
+/// A buyer's profile, at the canonical PDA `[b"user_profile", authority]`.
+/// Contact details never go on chain in plaintext: clients store a salted
+/// commitment, `sha256(salt || value)`, and keep the salt off chain so they
+/// can later prove a value matches.
 #[account]
 pub struct UserProfile {
     pub authority: Pubkey,
     pub user_id: String,
-    pub delivery_address: String,
+    pub name_commitment: [u8; 32],
+    pub email_commitment: [u8; 32],
+    pub phone_commitment: [u8; 32],
+    pub delivery_address_commitment: [u8; 32],
     pub preferred_store: Pubkey,
     pub total_purchases: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub purchase_history: Vec<PurchaseRecord>, // ring buffer of the latest checkouts
 }
 
 impl UserProfile {
     pub const MAX_USER_ID_LEN: usize = 50;
-    pub const MAX_ADDRESS_LEN: usize = 200;
     pub const MAX_PURCHASE_HISTORY: usize = 10;
-    pub const LEN: usize = 32
-        + 4
-        + Self::MAX_USER_ID_LEN
-        + 33
-        + 4
-        + Self::MAX_ADDRESS_LEN
-        + 8
-        + 4
-        + (32 + 32 + 8 + 8 + 8) * Self::MAX_PURCHASE_HISTORY
-        + 8;
+    pub const LEN: usize = 8 +                  // discriminator
+        32 +                                    // authority
+        (4 + Self::MAX_USER_ID_LEN) +           // user_id
+        32 * 4 +                                // contact commitments
+        32 +                                    // preferred_store
+        8 +                                     // total_purchases
+        8 +                                     // created_at
+        8 +                                     // updated_at
+        4 + (32 + 32 + 8 + 8 + 8) * Self::MAX_PURCHASE_HISTORY; // purchase_history
+
+    /// Set up a new profile for `authority`, whose wallet address is the
+    /// default user ID
+    pub fn init(&mut self, authority: Pubkey, now: i64) {
+        self.authority = authority;
+        self.user_id = authority.to_string();
+        self.created_at = now;
+        self.updated_at = now;
+    }
+
+    /// Record a checkout in the purchase history ring buffer, overwriting the
+    /// oldest record once `MAX_PURCHASE_HISTORY` are held
+    pub fn record_purchase(&mut self, record: PurchaseRecord) {
+        let slot = (self.total_purchases % Self::MAX_PURCHASE_HISTORY as u64) as usize;
+        if slot < self.purchase_history.len() {
+            self.purchase_history[slot] = record;
+        } else {
+            self.purchase_history.push(record);
+        }
+        self.total_purchases = self.total_purchases.saturating_add(1);
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
@@ -41,7 +70,6 @@ pub struct PurchaseRecord {
 }
 
 #[derive(Accounts)]
-#[instruction(user_id: Option<String>, delivery_address: Option<String>, preferred_store: Option<Pubkey>)]
 pub struct CreateOrUpdateUserProfile<'info> {
     #[account(
         init_if_needed,
@@ -55,15 +83,3 @@ pub struct CreateOrUpdateUserProfile<'info> {
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
-
-#[derive(Accounts)]
-pub struct ScanAndPurchase<'info> {
-    #[account(
-        mut,
-        seeds = [b"user_profile", authority.key().as_ref()],
-        bump,
-        has_one = authority
-    )]
-    pub user_profile: Account<'info, UserProfile>,
-    pub authority: Signer<'info>,
-}
@@ -0,0 +1,105 @@
+use super::product::Product;
+use super::roles::StoreRoles;
+use super::staff::StaffMember;
+use super::store::Store;
+use crate::error::CustomError;
+use crate::types::TokenizedType;
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+/// Vouchers for a Fungible product are minted from the Token-2022 mint
+/// `[b"voucher_mint", product]`, whose mint authority is the product PDA.
+/// One voucher is one unit of the product, redeemable at the store.
+#[derive(Accounts)]
+#[instruction(product_uuid: [u8; 16])]
+pub struct InitializeVoucherMint<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
+        bump = product.bump,
+        has_one = store,
+        constraint = product.tokenized_type == TokenizedType::Fungible @ CustomError::NotFungibleProduct
+    )]
+    pub product: Account<'info, Product>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"voucher_mint", product.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = product,
+        mint::token_program = token_program
+    )]
+    pub voucher_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(product_uuid: [u8; 16])]
+pub struct RedeemVouchers<'info> {
+    pub store: Account<'info, Store>,
+    #[account(
+        seeds = [b"product", store.key().as_ref(), product_uuid.as_ref()],
+        bump = product.bump,
+        has_one = store
+    )]
+    pub product: Account<'info, Product>,
+    #[account(
+        mut,
+        seeds = [b"voucher_mint", product.key().as_ref()],
+        bump
+    )]
+    pub voucher_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = voucher_mint,
+        token::authority = holder,
+        token::token_program = token_program
+    )]
+    pub holder_voucher_account: InterfaceAccount<'info, TokenAccount>,
+    /// The customer handing in vouchers, who need not be the original buyer
+    pub holder: Signer<'info>,
+    /// Store owner or manager handing over the goods
+    pub authority: Signer<'info>,
+    /// The signer's staff record; required unless the signer is the store owner
+    #[account(
+        seeds = [b"staff", store.key().as_ref(), authority.key().as_ref()],
+        bump = staff.bump
+    )]
+    pub staff: Option<Account<'info, StaffMember>>,
+    /// The store's custom role definitions; required when the signer holds a custom role
+    #[account(seeds = [b"store_roles", store.key().as_ref()], bump = store_roles.bump)]
+    pub store_roles: Option<Account<'info, StoreRoles>>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[event]
+pub struct VoucherMintInitialized {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub mint: Pubkey,
+    pub created_at: i64,
+}
+
+#[event]
+pub struct VouchersRedeemed {
+    pub store_id: Pubkey,
+    pub product: Pubkey,
+    pub holder: Pubkey,
+    pub amount: u64,
+    pub redeemed_by: Pubkey,
+    pub timestamp: i64,
+}
@@ -8,6 +8,51 @@ pub enum AdminRoleType {
     Owner,
     Manager,
     Viewer,
+    Custom { role_id: u8 }, // defined in the store's StoreRoles account
+}
+
+impl AdminRoleType {
+    /// Capabilities granted by each built-in role. Custom roles grant none
+    /// here; they resolve through the store's `StoreRoles`.
+    pub fn capabilities(&self) -> u16 {
+        match self {
+            AdminRoleType::Owner => Capabilities::ALL,
+            AdminRoleType::Manager => {
+                Capabilities::VIEW
+                    | Capabilities::MANAGE_PRODUCTS
+                    | Capabilities::ISSUE_REFUNDS
+                    | Capabilities::RELEASE_ESCROW
+                    | Capabilities::MANAGE_STAFF
+            }
+            AdminRoleType::Viewer => Capabilities::VIEW,
+            AdminRoleType::Custom { .. } => 0,
+        }
+    }
+}
+
+/// Capability bits a store admin role can hold. A role with only `VIEW` is
+/// read-only: no instruction accepts it as an authority.
+pub struct Capabilities;
+
+impl Capabilities {
+    pub const VIEW: u16 = 1 << 0;
+    pub const MANAGE_PRODUCTS: u16 = 1 << 1; // list, edit and deactivate products and vouchers
+    pub const ISSUE_REFUNDS: u16 = 1 << 2; // refunds and returns, up to the store's refund limit
+    pub const RELEASE_ESCROW: u16 = 1 << 3; // release matured escrow and clear settlement holds
+    pub const MANAGE_STAFF: u16 = 1 << 4; // grant roles no broader than the granter's own
+    pub const MANAGE_STORE: u16 = 1 << 5; // edit store metadata, loyalty and risk rules, fund cranks
+    pub const ALL: u16 = Self::VIEW
+        | Self::MANAGE_PRODUCTS
+        | Self::ISSUE_REFUNDS
+        | Self::RELEASE_ESCROW
+        | Self::MANAGE_STAFF
+        | Self::MANAGE_STORE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ProductAttribute {
+    pub name: String,
+    pub value: String,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
@@ -23,6 +68,8 @@ pub enum TransactionStatus {
     Pending,
     Completed,
     Failed,
+    PartiallyRefunded,
+    Refunded,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
@@ -30,3 +77,11 @@ pub enum AnomalyFlag {
     None,
     Suspicious,
 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ReturnRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Paid,
+}
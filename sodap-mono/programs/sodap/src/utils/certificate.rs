@@ -0,0 +1,210 @@
+use crate::error::CustomError;
+use crate::state::Product;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{self, AssociatedToken};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::ExtensionType, state::Mint as MintState,
+};
+use anchor_spl::token_2022::{self, Token2022};
+use anchor_spl::token_interface::{
+    metadata_pointer_initialize, spl_pod::optional_keys::OptionalNonZeroPubkey,
+    spl_token_metadata_interface::state::TokenMetadata, token_metadata_initialize,
+    MetadataPointerInitialize, TokenMetadataInitialize,
+};
+
+pub const CERTIFICATE_SYMBOL: &str = "SODAP";
+
+/// Programs needed to mint certificates and vouchers for tokenized cart lines
+pub struct ProductTokenPrograms<'a, 'info> {
+    pub token_program: &'a Program<'info, Token2022>,
+    pub associated_token_program: &'a Program<'info, AssociatedToken>,
+    pub system_program: &'a Program<'info, System>,
+}
+
+/// Mint a 1-of-1 Token-2022 certificate for the next serial of `product`.
+/// The mint is the PDA `[b"certificate", product, serial.to_le_bytes()]`, its
+/// metadata lives on the mint itself, and the product PDA holds both the mint
+/// and metadata update authority. The buyer's ATA is created and receives the
+/// token. Returns the certificate mint.
+pub fn mint_certificate<'info>(
+    product: &mut Account<'info, Product>,
+    mint: &AccountInfo<'info>,
+    buyer_account: &AccountInfo<'info>,
+    buyer: &Signer<'info>,
+    programs: &ProductTokenPrograms<'_, 'info>,
+) -> Result<Pubkey> {
+    let product_key = product.key();
+    let serial = product.certificates_minted;
+    let serial_bytes = serial.to_le_bytes();
+    let (expected, mint_bump) = Pubkey::find_program_address(
+        &[b"certificate", product_key.as_ref(), &serial_bytes],
+        &crate::ID,
+    );
+    require_keys_eq!(mint.key(), expected, CustomError::InvalidProductToken);
+
+    let metadata = TokenMetadata {
+        update_authority: OptionalNonZeroPubkey(product_key),
+        mint: mint.key(),
+        name: product.name.clone(),
+        symbol: CERTIFICATE_SYMBOL.to_string(),
+        uri: format!("{}?serial={}", product.metadata_uri, serial),
+        additional_metadata: vec![],
+    };
+
+    // Fund the metadata up front; the token program grows the mint into it
+    let space =
+        ExtensionType::try_calculate_account_len::<MintState>(&[ExtensionType::MetadataPointer])?;
+    let lamports = Rent::get()?.minimum_balance(space + metadata.tlv_size_of()?);
+    let mint_seeds: &[&[u8]] = &[b"certificate", product_key.as_ref(), &serial_bytes, &[mint_bump]];
+    create_pda_account(
+        mint,
+        &buyer.to_account_info(),
+        &programs.system_program.to_account_info(),
+        mint_seeds,
+        lamports,
+        space as u64,
+        &programs.token_program.key(),
+    )?;
+
+    let token_program = programs.token_program.to_account_info();
+    metadata_pointer_initialize(
+        CpiContext::new(
+            token_program.clone(),
+            MetadataPointerInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            },
+        ),
+        Some(product_key),
+        Some(mint.key()),
+    )?;
+    token_2022::initialize_mint2(
+        CpiContext::new(
+            token_program.clone(),
+            token_2022::InitializeMint2 { mint: mint.clone() },
+        ),
+        0,
+        &product_key,
+        None,
+    )?;
+
+    let store = product.store;
+    let product_seeds: &[&[u8]] = &[
+        b"product",
+        store.as_ref(),
+        product.uuid.as_ref(),
+        &[product.bump],
+    ];
+    let product_info = product.to_account_info();
+    token_metadata_initialize(
+        CpiContext::new_with_signer(
+            token_program.clone(),
+            TokenMetadataInitialize {
+                program_id: token_program.clone(),
+                metadata: mint.clone(),
+                update_authority: product_info.clone(),
+                mint_authority: product_info.clone(),
+                mint: mint.clone(),
+            },
+            &[product_seeds],
+        ),
+        metadata.name,
+        metadata.symbol,
+        metadata.uri,
+    )?;
+
+    associated_token::create(CpiContext::new(
+        programs.associated_token_program.to_account_info(),
+        associated_token::Create {
+            payer: buyer.to_account_info(),
+            associated_token: buyer_account.clone(),
+            authority: buyer.to_account_info(),
+            mint: mint.clone(),
+            system_program: programs.system_program.to_account_info(),
+            token_program: token_program.clone(),
+        },
+    ))?;
+    token_2022::mint_to(
+        CpiContext::new_with_signer(
+            token_program,
+            token_2022::MintTo {
+                mint: mint.clone(),
+                to: buyer_account.clone(),
+                authority: product_info,
+            },
+            &[product_seeds],
+        ),
+        1,
+    )?;
+
+    product.certificates_minted = serial
+        .checked_add(1)
+        .ok_or(CustomError::ArithmeticError)?;
+    Ok(mint.key())
+}
+
+/// Create the PDA `account` owned by `owner`, as Anchor's `init` does. The
+/// address is predictable, so anyone may have sent it lamports already; in
+/// that case only the rent shortfall is paid, and the account is allocated and
+/// assigned in place instead of failing `create_account` for good.
+fn create_pda_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+) -> Result<()> {
+    let current_lamports = account.lamports();
+    if current_lamports == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+                &[seeds],
+            ),
+            lamports,
+            space,
+            owner,
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(current_lamports);
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[seeds],
+        ),
+        space,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[seeds],
+        ),
+        owner,
+    )
+}
@@ -0,0 +1,283 @@
+use crate::error::CustomError;
+use crate::state::Escrow;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token_2022::{self, Token2022};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{
+        transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+        StateWithExtensions,
+    },
+    onchain::invoke_transfer_checked,
+    state::Mint as MintState,
+};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+/// Token accounts a payment needs when the store is priced in an SPL or
+/// Token-2022 mint. They are ignored for SOL-priced stores, where clients
+/// pass `None`.
+pub struct TokenPayment<'a, 'info> {
+    pub mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    /// The escrow PDA's associated token account for the payment mint
+    pub escrow_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    /// The buyer's or payout wallet's token account for the payment mint
+    pub wallet_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<&'a Interface<'info, TokenInterface>>,
+    /// Transfer-hook extra accounts, passed through to the token program
+    pub extra_accounts: &'a [AccountInfo<'info>],
+}
+
+/// A `TokenPayment` checked against the store's payment mint
+struct TokenTransfer<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    escrow_vault: &'a InterfaceAccount<'info, TokenAccount>,
+    wallet_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    extra_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> TokenPayment<'a, 'info> {
+    /// Check the accounts against the payment mint: the vault must be the
+    /// escrow's ATA and the wallet account must belong to `wallet`
+    fn resolve(
+        &self,
+        payment_mint: &Pubkey,
+        escrow: &Pubkey,
+        wallet: &Pubkey,
+    ) -> Result<TokenTransfer<'a, 'info>> {
+        let (Some(mint), Some(escrow_vault), Some(wallet_token_account), Some(token_program)) = (
+            self.mint,
+            self.escrow_vault,
+            self.wallet_token_account,
+            self.token_program,
+        ) else {
+            return err!(CustomError::TokenAccountsRequired);
+        };
+
+        require_keys_eq!(mint.key(), *payment_mint, CustomError::InvalidPaymentMint);
+        require_keys_eq!(
+            escrow_vault.key(),
+            get_associated_token_address_with_program_id(
+                escrow,
+                payment_mint,
+                &token_program.key()
+            ),
+            CustomError::InvalidPaymentMint
+        );
+        require_keys_eq!(
+            wallet_token_account.mint,
+            *payment_mint,
+            CustomError::InvalidPaymentMint
+        );
+        require_keys_eq!(
+            wallet_token_account.owner,
+            *wallet,
+            CustomError::InvalidTokenAccount
+        );
+
+        Ok(TokenTransfer {
+            mint,
+            escrow_vault,
+            wallet_token_account,
+            token_program,
+            extra_accounts: self.extra_accounts,
+        })
+    }
+}
+
+impl<'a, 'info> TokenTransfer<'a, 'info> {
+    /// `transfer_checked` through the mint's token program. Transfer-hook
+    /// mints get their extra accounts passed through. Returns what `to`
+    /// receives, which is `amount` less any Token-2022 transfer fee.
+    fn transfer(
+        &self,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        authority: AccountInfo<'info>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<u64> {
+        let mint_info = self.mint.to_account_info();
+        let fee = {
+            let data = mint_info.try_borrow_data()?;
+            let mint_state = StateWithExtensions::<MintState>::unpack(&data)?;
+
+            if let Some(hook_program) = transfer_hook::get_program_id(&mint_state) {
+                require!(
+                    self.extra_accounts.iter().any(|a| a.key() == hook_program),
+                    CustomError::TransferHookError
+                );
+            }
+
+            match mint_state.get_extension::<TransferFeeConfig>() {
+                Ok(config) => config
+                    .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                    .ok_or(CustomError::ArithmeticError)?,
+                Err(_) => 0,
+            }
+        };
+
+        invoke_transfer_checked(
+            &self.token_program.key(),
+            from.to_account_info(),
+            mint_info,
+            to.to_account_info(),
+            authority,
+            self.extra_accounts,
+            amount,
+            self.mint.decimals,
+            signer_seeds,
+        )?;
+
+        amount
+            .checked_sub(fee)
+            .ok_or_else(|| CustomError::ArithmeticError.into())
+    }
+}
+
+/// Move a buyer's payment into the store escrow, in lamports or in the
+/// store's payment mint, and track it in the escrow balance.
+/// Returns the amount the escrow actually received, which is less than
+/// `amount` for Token-2022 mints that charge a transfer fee.
+pub fn pay_into_escrow<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    payment_mint: Option<Pubkey>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    tokens: &TokenPayment<'_, 'info>,
+    amount: u64,
+) -> Result<u64> {
+    let received = match payment_mint {
+        Some(mint) => {
+            let transfer = tokens.resolve(&mint, &escrow.key(), &payer.key())?;
+            transfer.transfer(
+                transfer.wallet_token_account,
+                transfer.escrow_vault,
+                payer.to_account_info(),
+                amount,
+                &[],
+            )?
+        }
+        None => {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: payer.to_account_info(),
+                        to: escrow.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+            amount
+        }
+    };
+
+    escrow.balance = escrow
+        .balance
+        .checked_add(received)
+        .ok_or(CustomError::ArithmeticError)?;
+    Ok(received)
+}
+
+/// Pay `amount` out of the store escrow PDA to `to`.
+/// For SOL the escrow is owned by this program, so the system program can't
+/// debit it; lamports are moved directly. For token mints the escrow PDA signs a
+/// transfer from its vault to `to`'s token account. Either way the tracked
+/// balance is kept in sync.
+pub fn pay_out_of_escrow<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    escrow_bump: u8,
+    payment_mint: Option<Pubkey>,
+    to: &AccountInfo<'info>,
+    tokens: &TokenPayment<'_, 'info>,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        escrow.balance >= amount,
+        CustomError::InsufficientEscrowBalance
+    );
+
+    match payment_mint {
+        Some(mint) => {
+            let transfer = tokens.resolve(&mint, &escrow.key(), &to.key())?;
+            let store = escrow.store;
+            let signer_seeds: &[&[&[u8]]] = &[&[b"escrow", store.as_ref(), &[escrow_bump]]];
+            transfer.transfer(
+                transfer.escrow_vault,
+                transfer.wallet_token_account,
+                escrow.to_account_info(),
+                amount,
+                signer_seeds,
+            )?;
+        }
+        None => {
+            escrow.sub_lamports(amount)?;
+            to.add_lamports(amount)?;
+        }
+    }
+
+    escrow.balance = escrow
+        .balance
+        .checked_sub(amount)
+        .ok_or(CustomError::ArithmeticError)?;
+    Ok(())
+}
+
+/// Pay a settlement crank tip out of the store-funded crank budget.
+/// Tips never touch buyer funds: the amount is capped at what is left in the
+/// budget, and the capped amount is returned.
+pub fn pay_crank_tip<'info>(
+    escrow: &mut Account<'info, Escrow>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let tip = amount.min(escrow.crank_budget);
+    if tip == 0 {
+        return Ok(0);
+    }
+
+    escrow.sub_lamports(tip)?;
+    to.add_lamports(tip)?;
+
+    escrow.crank_budget = escrow
+        .crank_budget
+        .checked_sub(tip)
+        .ok_or(CustomError::ArithmeticError)?;
+    Ok(tip)
+}
+
+/// Burn `amount` product tokens, a certificate or vouchers, that the buyer has
+/// returned to the escrow PDA's token account for `mint`, as part of refunding
+/// their line.
+pub fn burn_returned_tokens<'info>(
+    escrow: &Account<'info, Escrow>,
+    escrow_bump: u8,
+    mint: &AccountInfo<'info>,
+    escrow_token_account: &'info AccountInfo<'info>,
+    token_program: &Program<'info, Token2022>,
+    amount: u64,
+) -> Result<()> {
+    let returned = InterfaceAccount::<TokenAccount>::try_from(escrow_token_account)?;
+    require_keys_eq!(returned.mint, mint.key(), CustomError::InvalidProductToken);
+    require!(
+        returned.owner == escrow.key() && returned.amount >= amount,
+        CustomError::ProductTokensNotReturned
+    );
+
+    let store = escrow.store;
+    let escrow_seeds: &[&[u8]] = &[b"escrow", store.as_ref(), &[escrow_bump]];
+    token_2022::burn(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token_2022::Burn {
+                mint: mint.clone(),
+                from: escrow_token_account.clone(),
+                authority: escrow.to_account_info(),
+            },
+            &[escrow_seeds],
+        ),
+        amount,
+    )
+}
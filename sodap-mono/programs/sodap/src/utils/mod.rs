@@ -1,2 +1,3 @@
 pub mod pda;
-pub mod auth;
+pub mod escrow;
+pub mod certificate;
@@ -25,6 +25,7 @@ import { PublicKey, LAMPORTS_PER_SOL, Keypair } from "@solana/web3.js";
 import { Sodap } from "../target/types/sodap";
 import * as fs from "fs";
 import * as path from "path";
+import * as crypto from "crypto";
 
 type AccountMeta = {
   pubkey: PublicKey;
@@ -40,7 +41,6 @@ type ProgramAccounts = {
   createOrUpdateUserProfile: {
     userProfile: AccountMeta;
     authority: AccountMeta;
-    payer: AccountMeta;
     systemProgram: AccountMeta;
   };
   initializeLoyaltyMint: {
@@ -320,15 +320,23 @@ async function testCoreFunctionality(): Promise<void> {
         program.programId
       );
 
+      // Contact fields are stored as sha256(salt || value) commitments
+      const salt = crypto.randomBytes(32);
+      const commit = (value: string) =>
+        Array.from(crypto.createHash("sha256").update(salt).update(value).digest());
+
       const tx = await program.methods
         .createOrUpdateUserProfile(
           null, // user_id
-          "Test User", // name
-          "test@example.com", // email
-          "+1234567890" // phone
+          commit("Test User"), // name
+          commit("test@example.com"), // email
+          commit("+1234567890"), // phone
+          null, // delivery address
+          null // preferred store
         )
         .accounts({
-          payer: buyer.publicKey,
+          userProfile: userProfilePda,
+          authority: buyer.publicKey,
           systemProgram: web3.SystemProgram.programId,
         } as any)
         .signers([buyer])
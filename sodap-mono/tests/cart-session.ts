@@ -0,0 +1,221 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findCartSessionPDA,
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap cart sessions", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+  const stranger = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let cartSessionPda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+
+  const apple = { uuid: Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16)), price: 20_000 };
+  const bread = { uuid: Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16)), price: 50_000 };
+
+  const productPda = (uuid: number[]) =>
+    findProductPDA(storePda, uuid, program.programId);
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const scan = (uuid: number[], quantity: number) =>
+    program.methods
+      .scanCartItem(uuid, new anchor.BN(quantity))
+      .accounts({
+        store: storePda,
+        product: productPda(uuid),
+        cartSession: cartSessionPda,
+        buyer: buyer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+  const checkout = (index: number, uuids: number[][], total: number) =>
+    program.methods
+      .checkoutCartSession(new anchor.BN(total))
+      .accounts({
+        purchase: {
+          store: storePda,
+          purchaseCounter: purchaseCounterPda,
+          receipt: receiptPda(index),
+          buyer: buyer.publicKey,
+          storeOwner: storeOwner.publicKey,
+          escrowAccount: escrowPda,
+          systemProgram: SystemProgram.programId,
+        },
+        cartSession: cartSessionPda,
+      })
+      .remainingAccounts(
+        uuids.map((uuid) => ({ pubkey: productPda(uuid), isWritable: true, isSigner: false }))
+      )
+      .signers([buyer])
+      .rpc();
+
+  const close = (closer: Keypair) =>
+    program.methods
+      .closeCartSession()
+      .accounts({ cartSession: cartSessionPda, buyer: buyer.publicKey, closer: closer.publicKey })
+      .signers([closer])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer, stranger], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    cartSessionPda = findCartSessionPDA(storePda, buyer.publicKey, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Corner Shop", "Scan and go", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    for (const [product, name] of [
+      [apple, "Apple"],
+      [bread, "Bread"],
+    ] as const) {
+      await program.methods
+        .registerProduct(
+          product.uuid,
+          name,
+          "Groceries",
+          "https://example.com/item.png",
+          new anchor.BN(product.price),
+          new anchor.BN(10),
+          { none: {} },
+          "https://example.com/item.json",
+          [],
+          null
+        )
+        .accounts({
+          store: storePda,
+          product: productPda(product.uuid),
+          authority: storeOwner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([storeOwner])
+        .rpc();
+    }
+  });
+
+  it("opens a session on the first scan and locks each line's price", async () => {
+    await scan(apple.uuid, 1);
+    await scan(bread.uuid, 1);
+    await scan(apple.uuid, 2);
+
+    const session = await program.account.cartSession.fetch(cartSessionPda);
+    assert.ok(session.buyer.equals(buyer.publicKey));
+    assert.deepEqual(session.productUuids, [apple.uuid, bread.uuid]);
+    assert.deepEqual(session.quantities.map((q) => q.toNumber()), [3, 1]);
+    assert.deepEqual(session.unitPrices.map((p) => p.toNumber()), [apple.price, bread.price]);
+  });
+
+  it("keeps scanned lines at their locked price", async () => {
+    await program.methods
+      .updateProduct(apple.uuid, null, null, null, new anchor.BN(apple.price * 2), null, null, null)
+      .accounts({
+        store: storePda,
+        product: productPda(apple.uuid),
+        skuIndex: null,
+        newSkuIndex: null,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const session = await program.account.cartSession.fetch(cartSessionPda);
+    assert.equal(session.unitPrices[0].toNumber(), apple.price);
+  });
+
+  it("rejects scans beyond the product's stock", async () => {
+    try {
+      await scan(apple.uuid, 8);
+      assert.fail("Expected the scan to exceed stock");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientStock");
+    }
+  });
+
+  it("removes items and drops emptied lines", async () => {
+    await program.methods
+      .removeCartItem(bread.uuid, new anchor.BN(1))
+      .accounts({ store: storePda, cartSession: cartSessionPda, buyer: buyer.publicKey })
+      .signers([buyer])
+      .rpc();
+
+    const session = await program.account.cartSession.fetch(cartSessionPda);
+    assert.deepEqual(session.productUuids, [apple.uuid]);
+  });
+
+  it("only lets others close a session once it has expired", async () => {
+    try {
+      await close(stranger);
+      assert.fail("Expected a live session to stay open");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("pays for the session at locked prices and closes it", async () => {
+    try {
+      await checkout(0, [apple.uuid], apple.price * 2);
+      assert.fail("Expected an underpayment to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientPayment");
+    }
+
+    await checkout(0, [apple.uuid], apple.price * 3);
+
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.equal(receipt.totalPaid.toNumber(), apple.price * 3);
+    assert.deepEqual(receipt.quantities.map((q) => q.toNumber()), [3]);
+    const product = await program.account.product.fetch(productPda(apple.uuid));
+    assert.equal(product.stock.toNumber(), 7);
+    assert.isNull(await provider.connection.getAccountInfo(cartSessionPda));
+  });
+
+  it("never extends a session past its maximum lifetime", async () => {
+    await scan(bread.uuid, 1);
+    const opened = await program.account.cartSession.fetch(cartSessionPda);
+    await scan(bread.uuid, 1);
+
+    const session = await program.account.cartSession.fetch(cartSessionPda);
+    assert.equal(session.createdAt.toNumber(), opened.createdAt.toNumber());
+    assert.isAtMost(session.expiresAt.toNumber(), session.createdAt.toNumber() + 2 * 60 * 60);
+  });
+
+  it("lets the buyer abandon a session and reclaim its rent", async () => {
+    await scan(bread.uuid, 1);
+    await close(buyer);
+    assert.isNull(await provider.connection.getAccountInfo(cartSessionPda));
+  });
+});
@@ -0,0 +1,287 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import {
+  TOKEN_2022_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  transfer,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { Buffer } from "buffer";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findCertificateMintPDA,
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap certificates", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 1_000_000;
+  const metadataUri = "https://example.com/painting.json";
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const certificateMintPda = (serial: number) =>
+    findCertificateMintPDA(productPda, serial, program.programId);
+
+  // Read the token metadata stored on a Token-2022 mint: after the base mint
+  // and account type come TLV entries, and TokenMetadata is extension 19
+  const getTokenMetadata = async (mint: PublicKey) => {
+    const data = (await provider.connection.getAccountInfo(mint)).data;
+    let offset = 166;
+    while (data.readUInt16LE(offset) !== 19) {
+      offset += 4 + data.readUInt16LE(offset + 2);
+    }
+    offset += 4;
+    const updateAuthority = new PublicKey(data.subarray(offset, offset + 32));
+    offset += 64; // update authority and mint
+    const readString = () => {
+      const len = data.readUInt32LE(offset);
+      const value = data.subarray(offset + 4, offset + 4 + len).toString("utf8");
+      offset += 4 + len;
+      return value;
+    };
+    const name = readString();
+    const symbol = readString();
+    const uri = readString();
+    return { updateAuthority, name, symbol, uri };
+  };
+
+  const certificateAccount = (mint: PublicKey, owner: PublicKey) =>
+    getAssociatedTokenAddressSync(mint, owner, true, TOKEN_2022_PROGRAM_ID);
+
+  const purchase = (index: number, quantity: number, certificateAccounts: PublicKey[]) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(quantity)], new anchor.BN(productPrice * quantity))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+        productTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        [productPda, ...certificateAccounts].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([buyer])
+      .rpc();
+
+  const refundLine = (index: number, certificateAccounts: PublicKey[]) =>
+    program.methods
+      .refundLineItems(Buffer.from([0]), [new anchor.BN(1)])
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+        productTokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        [productPda, ...certificateAccounts].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([storeOwner])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Gallery", "One-off artworks", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Sunrise",
+        "Oil on canvas",
+        "https://example.com/painting.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(10),
+        { nonFungible: {} },
+        metadataUri,
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("only sells NonFungible products one unit per line", async () => {
+    const mint = certificateMintPda(0);
+    try {
+      await purchase(0, 2, [mint, certificateAccount(mint, buyer.publicKey)]);
+      assert.fail("Expected a multi-unit NonFungible line to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InvalidCart");
+    }
+  });
+
+  it("requires certificate accounts for NonFungible lines", async () => {
+    try {
+      await purchase(0, 1, []);
+      assert.fail("Expected missing certificate accounts to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "ProductTokenAccountsRequired");
+    }
+  });
+
+  it("mints a 1-of-1 certificate to the buyer and records it", async () => {
+    const mint = certificateMintPda(0);
+    const buyerAccount = certificateAccount(mint, buyer.publicKey);
+    await purchase(0, 1, [mint, buyerAccount]);
+
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.ok(receipt.tokenMints[0].equals(mint));
+
+    const token = await getAccount(provider.connection, buyerAccount, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(Number(token.amount), 1);
+
+    const metadata = await getTokenMetadata(mint);
+    assert.equal(metadata.name, "Sunrise");
+    assert.equal(metadata.uri, `${metadataUri}?serial=0`);
+    assert.ok(metadata.updateAuthority.equals(productPda));
+
+    const product = await program.account.product.fetch(productPda);
+    assert.equal(product.certificatesMinted.toNumber(), 1);
+  });
+
+  it("gives the next sale the next serial", async () => {
+    const mint = certificateMintPda(1);
+    await purchase(1, 1, [mint, certificateAccount(mint, buyer.publicKey)]);
+
+    const metadata = await getTokenMetadata(mint);
+    assert.equal(metadata.uri, `${metadataUri}?serial=1`);
+  });
+
+  it("refuses to refund the line until the certificate is returned", async () => {
+    const mint = certificateMintPda(0);
+    const escrowAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        buyer,
+        mint,
+        escrowPda,
+        true,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+
+    try {
+      await refundLine(0, [mint, escrowAccount]);
+      assert.fail("Expected the refund to wait for the certificate");
+    } catch (err) {
+      assert.include((err as Error).message, "ProductTokensNotReturned");
+    }
+  });
+
+  it("burns the returned certificate when the line is refunded", async () => {
+    const mint = certificateMintPda(0);
+    const escrowAccount = certificateAccount(mint, escrowPda);
+    await transfer(
+      provider.connection,
+      buyer,
+      certificateAccount(mint, buyer.publicKey),
+      escrowAccount,
+      buyer,
+      1,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await refundLine(0, [mint, escrowAccount]);
+
+    const returned = await getAccount(provider.connection, escrowAccount, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(Number(returned.amount), 0);
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.equal(receipt.refundedAmount.toNumber(), productPrice);
+  });
+
+  it("rejects another receipt's certificate", async () => {
+    const mint = certificateMintPda(0);
+    try {
+      await refundLine(1, [mint, certificateAccount(mint, escrowPda)]);
+      assert.fail("Expected a mismatched certificate to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InvalidProductToken");
+    }
+  });
+
+  it("still mints when the next serial's address was funded in advance", async () => {
+    const mint = certificateMintPda(2);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: provider.wallet.publicKey,
+          toPubkey: mint,
+          lamports: 1_000_000,
+        })
+      )
+    );
+
+    const buyerAccount = certificateAccount(mint, buyer.publicKey);
+    await purchase(2, 1, [mint, buyerAccount]);
+
+    const token = await getAccount(provider.connection, buyerAccount, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(Number(token.amount), 1);
+    const mintInfo = await provider.connection.getAccountInfo(mint);
+    assert.ok(mintInfo.owner.equals(TOKEN_2022_PROGRAM_ID));
+  });
+});
@@ -3,8 +3,8 @@ import { Program } from "@coral-xyz/anchor";
 import { Sodap } from "../target/types/sodap";
 import { PublicKey, SystemProgram, Keypair, LAMPORTS_PER_SOL } from "@solana/web3.js";
 import { assert } from "chai";
-import { Buffer } from "buffer";
 import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import { findUserProfilePDA } from "../utils/pda-helpers";
 
 describe("sodap user wallet", () => {
   // Configure the client to use the local cluster
@@ -40,10 +40,7 @@ describe("sodap user wallet", () => {
     }
     
     // Derive user wallet PDA
-    const [userWalletKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("user_wallet"), user.publicKey.toBuffer()],
-      program.programId
-    );
+    const userWalletKey = findUserProfilePDA(user.publicKey, program.programId);
     userWalletPda = userWalletKey;
     console.log("User Wallet PDA:", userWalletPda.toBase58());
     console.log("User:", user.publicKey.toBase58());
@@ -83,7 +80,11 @@ describe("sodap user wallet", () => {
     // Verify user wallet data
     assert.ok(userWalletAccount.authority.equals(user.publicKey), "Authority should match user's public key");
     assert.equal(userWalletAccount.userId, user.publicKey.toString(), "User ID should match user's public key as string");
-    assert.equal(userWalletAccount.deliveryAddress, "", "Delivery address should be empty");
+    assert.deepEqual(
+      userWalletAccount.deliveryAddressCommitment,
+      new Array(32).fill(0),
+      "Delivery address should be unset"
+    );
     assert.ok(userWalletAccount.preferredStore.equals(PublicKey.default), "Preferred store should be default");
     assert.equal(userWalletAccount.totalPurchases, 0, "Total purchases should be 0");
     
@@ -92,10 +93,7 @@ describe("sodap user wallet", () => {
   
   it("prevents unauthorized wallet creation", async () => {
     // Derive another user's wallet PDA
-    const [anotherUserWalletKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("user_wallet"), anotherUser.publicKey.toBuffer()],
-      program.programId
-    );
+    const anotherUserWalletKey = findUserProfilePDA(anotherUser.publicKey, program.programId);
     
     // Check another user balance before creating wallet
     const anotherUserBalance = await provider.connection.getBalance(anotherUser.publicKey);
@@ -145,10 +143,7 @@ describe("sodap user wallet", () => {
   
   it("verifies wallet PDA derivation", async () => {
     // Derive user wallet PDA again to verify it matches
-    const [derivedWalletKey] = PublicKey.findProgramAddressSync(
-      [Buffer.from("user_wallet"), user.publicKey.toBuffer()],
-      program.programId
-    );
+    const derivedWalletKey = findUserProfilePDA(user.publicKey, program.programId);
     
     assert.ok(derivedWalletKey.equals(userWalletPda), "Derived wallet PDA should match the original");
     
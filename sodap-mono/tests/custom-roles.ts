@@ -0,0 +1,261 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findProductPDA,
+  findRiskConfigPDA,
+  findStaffMemberPDA,
+  findStorePDA,
+  findStoreRolesPDA,
+} from "../utils/pda-helpers";
+
+// Capability bits, as in `types::Capabilities`
+const VIEW = 1 << 0;
+const MANAGE_PRODUCTS = 1 << 1;
+const MANAGE_STORE = 1 << 5;
+
+describe("sodap custom store roles", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const clerk = Keypair.generate();
+  const stranger = Keypair.generate();
+  const manager = Keypair.generate();
+  const picker = Keypair.generate();
+  const storeManager = Keypair.generate();
+
+  let storePda: PublicKey;
+  let storeRolesPda: PublicKey;
+
+  const shirt = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const socks = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+
+  const productPda = (uuid: number[]) =>
+    findProductPDA(storePda, uuid, program.programId);
+
+  const staffPda = (staff: PublicKey) =>
+    findStaffMemberPDA(storePda, staff, program.programId);
+
+  const registerProduct = (uuid: number[], storeRoles: PublicKey | null) =>
+    program.methods
+      .registerProduct(
+        uuid,
+        "Shirt",
+        "Cotton",
+        "https://example.com/shirt.png",
+        new anchor.BN(40_000),
+        new anchor.BN(12),
+        { none: {} },
+        "https://example.com/shirt.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda(uuid),
+        authority: clerk.publicKey,
+        staff: staffPda(clerk.publicKey),
+        storeRoles,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([clerk])
+      .rpc();
+
+  const createRole = (signer: Keypair, name: string, capabilities: number) =>
+    program.methods
+      .createStoreRole(name, capabilities)
+      .accounts({
+        store: storePda,
+        storeRoles: storeRolesPda,
+        owner: signer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+  const addAdmin = (signer: Keypair, admin: PublicKey, role: object) =>
+    program.methods
+      .addStoreAdmin(storePda, admin, role as any, "Floor staff", null, null)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        authority: signer.publicKey,
+        staff: signer === storeOwner ? null : staffPda(signer.publicKey),
+        storeRoles: storeRolesPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+  const suspendByManager = (admin: PublicKey, storeRoles: PublicKey | null) =>
+    program.methods
+      .suspendStoreAdmin(storePda, admin, true)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        authority: manager.publicKey,
+        staff: staffPda(manager.publicKey),
+        storeRoles,
+      })
+      .signers([manager])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, clerk, stranger, manager, storeManager], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    storeRolesPda = findStoreRolesPDA(storePda, program.programId);
+
+    await program.methods
+      .registerStore("Outfitters", "Casual wear", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("only lets the owner define roles", async () => {
+    try {
+      await createRole(stranger, "Cashier", VIEW);
+      assert.fail("Expected a stranger to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    await createRole(storeOwner, "Inventory clerk", VIEW | MANAGE_PRODUCTS);
+
+    const storeRoles = await program.account.storeRoles.fetch(storeRolesPda);
+    assert.equal(storeRoles.roles.length, 1);
+    assert.equal(storeRoles.roles[0].roleId, 0);
+    assert.equal(storeRoles.roles[0].name, "Inventory clerk");
+    assert.equal(storeRoles.roles[0].capabilities, VIEW | MANAGE_PRODUCTS);
+  });
+
+  it("grants a custom role's capabilities to its holders", async () => {
+    await program.methods
+      .addStoreAdmin(storePda, clerk.publicKey, { custom: { roleId: 0 } }, "Stock room", null, null)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(clerk.publicKey),
+        authority: storeOwner.publicKey,
+        storeRoles: storeRolesPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    try {
+      await registerProduct(shirt, null);
+      assert.fail("Expected the custom role to need the role definitions");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    await registerProduct(shirt, storeRolesPda);
+    const product = await program.account.product.fetch(productPda(shirt));
+    assert.ok(product.authority.equals(clerk.publicKey));
+  });
+
+  it("applies edits to existing holders", async () => {
+    await program.methods
+      .updateStoreRole(0, null, VIEW)
+      .accounts({ store: storePda, storeRoles: storeRolesPda, owner: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+
+    try {
+      await registerProduct(socks, storeRolesPda);
+      assert.fail("Expected the narrowed role to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("never reuses a revoked role's id", async () => {
+    await program.methods
+      .revokeStoreRole(0)
+      .accounts({ store: storePda, storeRoles: storeRolesPda, owner: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+    await createRole(storeOwner, "Stock room", VIEW | MANAGE_PRODUCTS);
+
+    const storeRoles = await program.account.storeRoles.fetch(storeRolesPda);
+    assert.deepEqual(storeRoles.roles.map((role) => role.roleId), [1]);
+
+    try {
+      await registerProduct(socks, storeRolesPda);
+      assert.fail("Expected the revoked role to grant nothing");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("treats a custom role it can't resolve as holding every capability", async () => {
+    await addAdmin(storeOwner, manager.publicKey, { manager: {} });
+    await addAdmin(manager, picker.publicKey, { custom: { roleId: 1 } });
+
+    try {
+      await suspendByManager(picker.publicKey, null);
+      assert.fail("Expected the role definitions to be required");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+    await suspendByManager(picker.publicKey, storeRolesPda);
+    const record = await program.account.staffMember.fetch(staffPda(picker.publicKey));
+    assert.isFalse(record.isActive);
+
+    // The clerk still holds the revoked role, which only the owner may manage
+    try {
+      await suspendByManager(clerk.publicKey, storeRolesPda);
+      assert.fail("Expected a revoked role to be left to the owner");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("lets a role with MANAGE_STORE change the store's risk rules", async () => {
+    const updateRiskConfig = (signer: Keypair) =>
+      program.methods
+        .updateRiskConfig(null, null, null, true)
+        .accounts({
+          store: storePda,
+          riskConfig: findRiskConfigPDA(storePda, program.programId),
+          authority: signer.publicKey,
+          staff: staffPda(signer.publicKey),
+          storeRoles: storeRolesPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([signer])
+        .rpc();
+
+    await createRole(storeOwner, "Store manager", VIEW | MANAGE_STORE);
+    const { roles } = await program.account.storeRoles.fetch(storeRolesPda);
+    const roleId = roles[roles.length - 1].roleId;
+    await addAdmin(storeOwner, storeManager.publicKey, { custom: { roleId } });
+
+    // Built-in managers run the floor but not the store's settings
+    try {
+      await updateRiskConfig(manager);
+      assert.fail("Expected a manager without MANAGE_STORE to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    await updateRiskConfig(storeManager);
+    const config = await program.account.riskConfig.fetch(
+      findRiskConfigPDA(storePda, program.programId)
+    );
+    assert.isTrue(config.holdFlagged);
+  });
+});
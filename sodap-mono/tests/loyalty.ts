@@ -0,0 +1,348 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import {
+  TOKEN_2022_PROGRAM_ID,
+  getAccount,
+  getOrCreateAssociatedTokenAccount,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { Buffer } from "buffer";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findLoyaltyConfigPDA,
+  findLoyaltyMintPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap loyalty", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let loyaltyConfigPda: PublicKey;
+  let loyaltyMintPda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+  let buyerLoyaltyAccount: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 1_000_000;
+  const earnRateBps = 500; // 5%
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const points = async () =>
+    Number(
+      (await getAccount(provider.connection, buyerLoyaltyAccount, undefined, TOKEN_2022_PROGRAM_ID))
+        .amount
+    );
+
+  const purchase = (index: number, amount: number) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(1)], new anchor.BN(amount))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+        loyaltyConfig: loyaltyConfigPda,
+        loyaltyMint: loyaltyMintPda,
+        buyerLoyaltyAccount,
+        loyaltyTokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts([{ pubkey: productPda, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+    loyaltyConfigPda = findLoyaltyConfigPDA(storePda, program.programId);
+    loyaltyMintPda = findLoyaltyMintPDA(storePda, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Loyalty Store", "Rewards buyers", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Loyalty Watch",
+        "Earns points",
+        "https://example.com/product/image.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(10),
+        { none: {} },
+        "https://example.com/product.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("only lets store admins who manage the store start a loyalty program", async () => {
+    try {
+      await program.methods
+        .initializeLoyalty(earnRateBps)
+        .accounts({
+          store: storePda,
+          loyaltyConfig: loyaltyConfigPda,
+          loyaltyMint: loyaltyMintPda,
+          authority: buyer.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([buyer])
+        .rpc();
+      assert.fail("Expected a non-admin to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("creates a points mint owned by the loyalty config PDA", async () => {
+    await program.methods
+      .initializeLoyalty(earnRateBps)
+      .accounts({
+        store: storePda,
+        loyaltyConfig: loyaltyConfigPda,
+        loyaltyMint: loyaltyMintPda,
+        authority: storeOwner.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const config = await program.account.loyaltyConfig.fetch(loyaltyConfigPda);
+    assert.equal(config.earnRateBps, earnRateBps);
+    assert.isTrue(config.isActive);
+    assert.ok(config.mint.equals(loyaltyMintPda));
+
+    buyerLoyaltyAccount = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        buyer,
+        loyaltyMintPda,
+        buyer.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+  });
+
+  it("mints points on checkout", async () => {
+    await purchase(0, productPrice);
+    assert.equal(await points(), (productPrice * earnRateBps) / 10_000);
+  });
+
+  it("burns points for a discount on the next checkout", async () => {
+    const redeemed = 20_000;
+    await program.methods
+      .redeemLoyaltyPoints(new anchor.BN(redeemed))
+      .accounts({
+        store: storePda,
+        loyaltyConfig: loyaltyConfigPda,
+        loyaltyMint: loyaltyMintPda,
+        buyerLoyaltyAccount,
+        purchaseCounter: purchaseCounterPda,
+        buyer: buyer.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+    const counter = await program.account.purchaseCounter.fetch(purchaseCounterPda);
+    assert.equal(counter.loyaltyDiscount.toNumber(), redeemed);
+
+    // The full price is now an overpayment
+    try {
+      await purchase(1, productPrice);
+      assert.fail("Expected the undiscounted price to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "Overpayment");
+    }
+
+    const pointsBefore = await points();
+    await purchase(1, productPrice - redeemed);
+
+    const receipt = await program.account.purchase.fetch(receiptPda(1));
+    assert.equal(receipt.totalPaid.toNumber(), productPrice - redeemed);
+    const counterAfter = await program.account.purchaseCounter.fetch(purchaseCounterPda);
+    assert.equal(counterAfter.loyaltyDiscount.toNumber(), 0);
+
+    // Points are earned on what was paid, not the list price
+    assert.equal(
+      (await points()) - pointsBefore,
+      Math.floor(((productPrice - redeemed) * earnRateBps) / 10_000)
+    );
+  });
+
+  it("rejects redeeming more points than the buyer holds", async () => {
+    try {
+      await program.methods
+        .redeemLoyaltyPoints(new anchor.BN((await points()) + 1))
+        .accounts({
+          store: storePda,
+          loyaltyConfig: loyaltyConfigPda,
+          loyaltyMint: loyaltyMintPda,
+          buyerLoyaltyAccount,
+          purchaseCounter: purchaseCounterPda,
+          buyer: buyer.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([buyer])
+        .rpc();
+      assert.fail("Expected redeeming too many points to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientLoyaltyPoints");
+    }
+  });
+
+  it("restocks lines paid for in points and gives the points back on refund", async () => {
+    const stickerUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+    const stickerPrice = 10_000;
+    const stickerPda = findProductPDA(storePda, stickerUuid, program.programId);
+    await program.methods
+      .registerProduct(
+        stickerUuid,
+        "Sticker",
+        "Free with points",
+        "https://example.com/sticker.png",
+        new anchor.BN(stickerPrice),
+        new anchor.BN(5),
+        { none: {} },
+        "https://example.com/sticker.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: stickerPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .redeemLoyaltyPoints(new anchor.BN(stickerPrice))
+      .accounts({
+        store: storePda,
+        loyaltyConfig: loyaltyConfigPda,
+        loyaltyMint: loyaltyMintPda,
+        buyerLoyaltyAccount,
+        purchaseCounter: purchaseCounterPda,
+        buyer: buyer.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+    await program.methods
+      .purchaseCart([stickerUuid], [new anchor.BN(1)], new anchor.BN(0))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(2),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: stickerPda, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+    const receipt = await program.account.purchase.fetch(receiptPda(2));
+    assert.equal(receipt.totalPaid.toNumber(), 0);
+    assert.equal(receipt.loyaltyDiscount.toNumber(), stickerPrice);
+
+    const signature = await program.methods
+      .refundLineItems(Buffer.from([0]), [new anchor.BN(1)])
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(2),
+        buyer: buyer.publicKey,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+        purchaseCounter: purchaseCounterPda,
+      })
+      .remainingAccounts([{ pubkey: stickerPda, isWritable: true, isSigner: false }])
+      .signers([storeOwner])
+      .rpc({ commitment: "confirmed" });
+
+    const sticker = await program.account.product.fetch(stickerPda);
+    assert.equal(sticker.stock.toNumber(), 5);
+    const refunded = await program.account.purchase.fetch(receiptPda(2));
+    assert.deepEqual(refunded.status, { refunded: {} });
+    assert.equal(refunded.refundedQuantities[0].toNumber(), 1);
+    assert.equal(refunded.refundedAmount.toNumber(), 0);
+
+    // The refund is still announced even though nothing left escrow
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const [event] = [...parser.parseLogs(tx.meta.logMessages)];
+    assert.equal(event.name, "purchaseRefunded");
+    assert.equal(event.data.amount.toNumber(), 0);
+    const counter = await program.account.purchaseCounter.fetch(purchaseCounterPda);
+    assert.equal(counter.loyaltyDiscount.toNumber(), stickerPrice);
+  });
+
+  it("stops minting points when the program is paused", async () => {
+    await program.methods
+      .updateLoyaltyConfig(null, false)
+      .accounts({ store: storePda, loyaltyConfig: loyaltyConfigPda, authority: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+
+    // The points given back by the refund are still pending as a discount
+    const { loyaltyDiscount } = await program.account.purchaseCounter.fetch(purchaseCounterPda);
+    const pointsBefore = await points();
+    await purchase(3, productPrice - loyaltyDiscount.toNumber());
+    assert.equal(await points(), pointsBefore);
+  });
+});
@@ -4,6 +4,15 @@ import { Sodap } from "../target/types/sodap";
 import { PublicKey, SystemProgram, Keypair, LAMPORTS_PER_SOL } from "@solana/web3.js";
 import { assert } from "chai";
 import { Buffer } from "buffer";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findReturnRequestPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
 
 describe("sodap payment", () => {
   // Configure the client to use the local cluster
@@ -11,427 +20,564 @@ describe("sodap payment", () => {
   anchor.setProvider(provider);
 
   const program = anchor.workspace.Sodap as Program<Sodap>;
-  
-  // Use the current wallet for testing
-  const storeOwner = provider.wallet;
-  const buyer = provider.wallet;
-  
-  // PDA for user wallet
-  let userWalletPda: PublicKey;
-  
+
+  const storeOwner = Keypair.generate();
+
   // Store and product variables
   let storePda: PublicKey;
   let escrowPda: PublicKey;
   let productPda: PublicKey;
-  let receiptPda: PublicKey;
-  
+
   // Product details
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
   const productPrice = 1000000; // 0.001 SOL
-  const productName = "Test Product";
-  const productDescription = "Test Product Description";
-  const productImageUri = "https://example.com/product/image.png";
-  
+  const productStock = 10;
+
+  const findPurchaseCounterPda = (buyer: PublicKey) =>
+    findPurchaseCounterPDA(storePda, buyer, program.programId);
+
+  const findReceiptPda = (buyer: PublicKey, index: number) =>
+    findReceiptPDA(storePda, buyer, index, program.programId);
+
+  // The next receipt is seeded by the buyer's purchase counter
+  const nextReceiptPda = async (buyer: PublicKey) => {
+    const counter = await program.account.purchaseCounter.fetchNullable(
+      findPurchaseCounterPda(buyer)
+    );
+    return findReceiptPda(buyer, counter ? counter.count.toNumber() : 0);
+  };
+
+  const newBuyer = async () => {
+    const buyer = Keypair.generate();
+    await fundMultipleTestAccounts(provider, [buyer], 0.05);
+    return buyer;
+  };
+
+  const purchase = async (buyer: Keypair, quantity: number, amount: number, productAccount = productPda) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(quantity)], new anchor.BN(amount))
+      .accounts({
+        store: storePda,
+        purchaseCounter: findPurchaseCounterPda(buyer.publicKey),
+        receipt: await nextReceiptPda(buyer.publicKey),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: productAccount, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
   before(async () => {
-    // Check if we have enough SOL in the wallet
-    const balance = await provider.connection.getBalance(storeOwner.publicKey);
-    console.log("Current wallet balance:", balance / LAMPORTS_PER_SOL, "SOL");
-    
-    if (balance < LAMPORTS_PER_SOL * 0.1) {
-      console.warn("Warning: Wallet balance is low. Tests may fail if balance is insufficient.");
-      console.warn("Consider funding your wallet with at least 0.5 SOL for these tests.");
-    } else {
-      console.log("Wallet has sufficient balance for tests");
-    }
-    
-    // Use hardcoded Store PDA to match other tests
-    // This is the same PDA used in the store and product tests
-    storePda = new PublicKey("BhfGKfh5wAGoHdSDbcNg5DCyaySRmCtw2rsBjFUfcodS");
-  
-    // Use hardcoded Escrow PDA to match other tests
-    escrowPda = new PublicKey("9UrJoXSsxMvruaX4afLDfpPMzy5hLrDy4W2yUoG8NP8E");
-    
-    console.log("Using hardcoded Store PDA:", storePda.toBase58());
-    console.log("Store owner:", storeOwner.publicKey.toBase58());
+    await fundMultipleTestAccounts(provider, [storeOwner], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+
+    console.log("Store PDA:", storePda.toBase58());
     console.log("Escrow PDA:", escrowPda.toBase58());
-    
-    // Register store
-    try {
-      console.log("Registering store with owner:", storeOwner.publicKey.toBase58());
-      console.log("Store PDA:", storePda.toBase58());
-      console.log("Escrow PDA:", escrowPda.toBase58());
-      
-      // Check store owner balance before creating store
-      const storeOwnerBalance = await provider.connection.getBalance(storeOwner.publicKey);
-      console.log("Store owner balance before creating store:", storeOwnerBalance / LAMPORTS_PER_SOL, "SOL");
-      
-      await program.methods
-        .registerStore(
-          "Test Store",
-          "Test Store Description",
-          "https://example.com/store"
-        )
+    console.log("Product PDA:", productPda.toBase58());
+
+    await program.methods
+      .registerStore("Test Store", "Test Store Description", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Test Watch",
+        "Test Product Description",
+        "https://example.com/product/image.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(productStock),
+        { none: {} },
+        "https://example.com/product.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("purchases a product at the on-chain price", async () => {
+    const buyer = await newBuyer();
+    const escrowBalanceBefore = await provider.connection.getBalance(escrowPda);
+
+    await purchase(buyer, 2, productPrice * 2);
+
+    const receipt = await program.account.purchase.fetch(findReceiptPda(buyer.publicKey, 0));
+    assert.equal(receipt.totalPaid.toNumber(), productPrice * 2);
+    assert.equal(receipt.purchaseIndex.toNumber(), 0);
+    assert.equal(receipt.quantities[0].toNumber(), 2);
+    assert.ok(receipt.store.equals(storePda));
+    assert.ok(receipt.buyer.equals(buyer.publicKey));
+
+    const product = await program.account.product.fetch(productPda);
+    assert.equal(product.stock.toNumber(), productStock - 2, "Stock should be decremented");
+
+    const escrowBalanceAfter = await provider.connection.getBalance(escrowPda);
+    assert.isAtLeast(escrowBalanceAfter - escrowBalanceBefore, productPrice * 2);
+  });
+
+  it("gives repeat purchases by the same buyer their own receipts", async () => {
+    const buyer = await newBuyer();
+
+    await purchase(buyer, 1, productPrice);
+    await purchase(buyer, 1, productPrice);
+
+    const counter = await program.account.purchaseCounter.fetch(
+      findPurchaseCounterPda(buyer.publicKey)
+    );
+    assert.equal(counter.count.toNumber(), 2);
+
+    const first = await program.account.purchase.fetch(findReceiptPda(buyer.publicKey, 0));
+    const second = await program.account.purchase.fetch(findReceiptPda(buyer.publicKey, 1));
+    assert.equal(first.purchaseIndex.toNumber(), 0);
+    assert.equal(second.purchaseIndex.toNumber(), 1);
+  });
+
+  describe("refunds", () => {
+    const refund = (receipt: PublicKey, buyer: PublicKey, amount: number) =>
+      program.methods
+        .refundFromEscrow(new anchor.BN(amount))
         .accounts({
+          escrowAccount: escrowPda,
           store: storePda,
-          escrow: escrowPda,
-          owner: storeOwner.publicKey,
-          payer: storeOwner.publicKey,
+          receipt,
+          buyer,
+          authority: storeOwner.publicKey,
           systemProgram: SystemProgram.programId,
         })
+        .signers([storeOwner])
         .rpc();
-      
-      console.log("Store registered successfully");
-    } catch (error) {
-      console.error("Error registering store:", error);
-      throw error;
-    }
-    
-    // Create a new product keypair
-    const productKeypair = Keypair.generate();
-    productPda = productKeypair.publicKey;
-    console.log("Product ID:", productPda.toBase58());
-    
-    // Register product
-    try {
-      console.log("Registering product with ID:", productPda.toBase58());
-      console.log("Store PDA:", storePda.toBase58());
-      
-      await program.methods
-        .registerProduct(
-          productPda,
-          storePda,
-          productName,
-          productDescription,
-          productImageUri,
-          new anchor.BN(productPrice),
-          new anchor.BN(10), // stock
-          [] // attributes
-        )
+
+    let buyer: Keypair;
+    let receiptPda: PublicKey;
+
+    before(async () => {
+      buyer = await newBuyer();
+      receiptPda = await nextReceiptPda(buyer.publicKey);
+      await purchase(buyer, 2, productPrice * 2);
+    });
+
+    it("refunds part of a purchase and tracks the cumulative amount", async () => {
+      const buyerBalanceBefore = await provider.connection.getBalance(buyer.publicKey);
+
+      await refund(receiptPda, buyer.publicKey, productPrice);
+
+      const buyerBalanceAfter = await provider.connection.getBalance(buyer.publicKey);
+      assert.equal(buyerBalanceAfter - buyerBalanceBefore, productPrice);
+
+      const receipt = await program.account.purchase.fetch(receiptPda);
+      assert.equal(receipt.refundedAmount.toNumber(), productPrice);
+      assert.deepEqual(receipt.status, { partiallyRefunded: {} });
+    });
+
+    it("rejects refunds to someone other than the receipt's buyer", async () => {
+      try {
+        await refund(receiptPda, storeOwner.publicKey, 1);
+        assert.fail("Expected refund to the wrong buyer to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "Unauthorized");
+      }
+    });
+
+    it("caps cumulative refunds at the amount paid", async () => {
+      try {
+        await refund(receiptPda, buyer.publicKey, productPrice + 1);
+        assert.fail("Expected over-refund to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "RefundExceedsPayment");
+      }
+
+      await refund(receiptPda, buyer.publicKey, productPrice);
+      const receipt = await program.account.purchase.fetch(receiptPda);
+      assert.equal(receipt.refundedAmount.toNumber(), productPrice * 2);
+      assert.deepEqual(receipt.status, { refunded: {} });
+    });
+  });
+
+  describe("line item refunds", () => {
+    let buyer: Keypair;
+    let receiptPda: PublicKey;
+
+    const refundLines = (lineIndices: number[], quantities: number[]) =>
+      program.methods
+        .refundLineItems(Buffer.from(lineIndices), quantities.map((q) => new anchor.BN(q)))
         .accounts({
-          product: productPda,
+          escrowAccount: escrowPda,
           store: storePda,
+          receipt: receiptPda,
+          buyer: buyer.publicKey,
           authority: storeOwner.publicKey,
-          payer: storeOwner.publicKey,
           systemProgram: SystemProgram.programId,
         })
+        .remainingAccounts(lineIndices.map(() => ({ pubkey: productPda, isWritable: true, isSigner: false })))
+        .signers([storeOwner])
         .rpc();
-      
-      console.log("Product registered successfully");
-    } catch (error) {
-      console.error("Error registering product:", error);
-      throw error;
-    }
-  });
-  
-  it("purchases a product", async () => {
-    // Create a new receipt keypair for this test
-    const receiptKeypair = Keypair.generate();
-    console.log("Purchase Receipt ID:", receiptKeypair.publicKey.toBase58());
-    
-    // Get balances before purchase
-    const buyerBalanceBefore = await provider.connection.getBalance(buyer.publicKey);
-    const escrowBalanceBefore = await provider.connection.getBalance(escrowPda);
-    
-    console.log("Buyer balance before purchase:", buyerBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    console.log("Escrow balance before purchase:", escrowBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    
-    let purchaseSuccessful = false;
-    try {
-      console.log("Purchasing product with ID:", productPda.toBase58());
-      console.log("Receipt ID:", receiptKeypair.publicKey.toBase58());
-      
-      // Check if buyer has enough SOL for the transaction
-      if (buyerBalanceBefore < productPrice * 2) { // Need extra for rent and fees
-        console.log("Warning: Buyer balance may be insufficient for transaction");
-        console.log("Current buyer balance:", buyerBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-        console.log("Required balance:", (productPrice * 2) / LAMPORTS_PER_SOL, "SOL");
+
+    before(async () => {
+      buyer = await newBuyer();
+      receiptPda = await nextReceiptPda(buyer.publicKey);
+      await purchase(buyer, 3, productPrice * 3);
+    });
+
+    it("refunds returned units at the price paid and restocks them", async () => {
+      const stockBefore = (await program.account.product.fetch(productPda)).stock.toNumber();
+      const buyerBalanceBefore = await provider.connection.getBalance(buyer.publicKey);
+
+      await refundLines([0], [1]);
+
+      const buyerBalanceAfter = await provider.connection.getBalance(buyer.publicKey);
+      assert.equal(buyerBalanceAfter - buyerBalanceBefore, productPrice);
+
+      const stockAfter = (await program.account.product.fetch(productPda)).stock.toNumber();
+      assert.equal(stockAfter, stockBefore + 1, "Returned unit should be restocked");
+
+      const receipt = await program.account.purchase.fetch(receiptPda);
+      assert.equal(receipt.refundedQuantities[0].toNumber(), 1);
+      assert.equal(receipt.refundedAmount.toNumber(), productPrice);
+    });
+
+    it("rejects returning more units than were bought", async () => {
+      try {
+        await refundLines([0], [3]);
+        assert.fail("Expected over-return to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "RefundExceedsQuantity");
       }
-      
+    });
+  });
+
+  describe("return requests", () => {
+    let buyer: Keypair;
+    let receiptPda: PublicKey;
+    let returnRequestPda: PublicKey;
+
+    before(async () => {
+      buyer = await newBuyer();
+      receiptPda = await nextReceiptPda(buyer.publicKey);
+      await purchase(buyer, 1, productPrice);
+      returnRequestPda = findReturnRequestPDA(receiptPda, program.programId);
+    });
+
+    it("lets the buyer open a return request", async () => {
       await program.methods
-        .purchaseCart(
-          [productPda],
-          [new anchor.BN(1)],
-          new anchor.BN(productPrice)
-        )
+        .requestReturn(1, "https://example.com/evidence.jpg", new anchor.BN(productPrice))
         .accounts({
-          store: storePda,
-          receipt: receiptKeypair.publicKey,
+          receipt: receiptPda,
+          returnRequest: returnRequestPda,
           buyer: buyer.publicKey,
-          payer: buyer.publicKey,
-          storeOwner: storeOwner.publicKey,
-          escrowAccount: escrowPda,
           systemProgram: SystemProgram.programId,
         })
-        .signers([receiptKeypair])
+        .signers([buyer])
         .rpc();
-      
-      console.log("Product purchased successfully");
-      purchaseSuccessful = true;
-    } catch (error) {
-      console.error("Error making purchase:", error);
-      if (error.toString().includes("insufficient lamports")) {
-        console.log("Insufficient lamports for transaction. Skipping test.");
-        // Skip the test instead of failing
-        return assert.ok(true, "Skipping test due to insufficient lamports");
-      } else {
-        throw error;
-      }
-    }
-    
-    // Only verify receipt if purchase was successful
-    if (purchaseSuccessful) {
+
+      const request = await program.account.returnRequest.fetch(returnRequestPda);
+      assert.deepEqual(request.status, { pending: {} });
+      assert.equal(request.amount.toNumber(), productPrice);
+    });
+
+    it("refuses to pay a request that has not been approved", async () => {
       try {
-        // Try to fetch the receipt data
-        let receiptAccount;
-        try {
-          receiptAccount = await program.account.purchase.fetch(receiptKeypair.publicKey);
-          console.log("Found purchase account:", receiptAccount);
-        } catch (e) {
-          console.log("Could not fetch receipt account as 'purchase'");
-        }
-        
-        if (receiptAccount) {
-          // Check if we have productIds
-          if (receiptAccount.productIds) {
-            assert.equal(receiptAccount.productIds.length, 1, "Receipt should have 1 product");
-            assert.equal(receiptAccount.quantities.length, 1, "Receipt should have 1 quantity");
-            assert.ok(receiptAccount.store.equals(storePda), "Receipt store should match store PDA");
-            assert.ok(receiptAccount.buyer.equals(buyer.publicKey), "Receipt buyer should match buyer public key");
-          } else {
-            console.log("Receipt account doesn't have expected structure");
-          }
-        } else {
-          console.log("Receipt account verification skipped - could not fetch account");
-        }
+        await program.methods
+          .payReturn()
+          .accounts({
+            escrowAccount: escrowPda,
+            store: storePda,
+            receipt: receiptPda,
+            returnRequest: returnRequestPda,
+            buyer: buyer.publicKey,
+            authority: storeOwner.publicKey,
+          })
+          .signers([storeOwner])
+          .rpc();
+        assert.fail("Expected payout of a pending request to fail");
       } catch (err) {
-        console.log("Error verifying receipt:", err);
+        assert.include((err as Error).message, "InvalidReturnStatus");
       }
-      
-      // Verify escrow balance increased
-      const buyerBalanceAfter = await provider.connection.getBalance(buyer.publicKey);
-      const escrowBalanceAfter = await provider.connection.getBalance(escrowPda);
-      
-      console.log("Buyer balance after purchase:", buyerBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-      console.log("Escrow balance after purchase:", escrowBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-      
-      assert.isAbove(
-        escrowBalanceAfter,
-        escrowBalanceBefore,
-        "Escrow balance should increase after purchase"
-      );
-    } else {
-      // Skip verification if purchase was not successful
-      console.log("Skipping receipt verification since purchase was not successful");
-      assert.ok(true, "Skipping verification due to unsuccessful purchase");
-    }
-  });
-  
-  it("releases funds from escrow to store owner", async () => {
-    const storeOwnerBalanceBefore = await provider.connection.getBalance(storeOwner.publicKey);
-    const escrowBalanceBefore = await provider.connection.getBalance(escrowPda);
-    
-    console.log("Store owner balance before release:", storeOwnerBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    console.log("Escrow balance before release:", escrowBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    
-    // First, make another purchase to have funds in escrow
-    const receiptKeypair = Keypair.generate();
-    let purchaseSuccessful = false;
-    try {
-      console.log("Purchasing product with ID:", productPda.toBase58());
-      console.log("Receipt ID:", receiptKeypair.publicKey.toBase58());
-      
-      // Check if buyer has enough SOL for the transaction
-      const buyerBalance = await provider.connection.getBalance(buyer.publicKey);
-      if (buyerBalance < productPrice * 2) { // Need extra for rent and fees
-        console.log("Warning: Buyer balance may be insufficient for transaction");
-        console.log("Current buyer balance:", buyerBalance / LAMPORTS_PER_SOL, "SOL");
-        console.log("Required balance:", (productPrice * 2) / LAMPORTS_PER_SOL, "SOL");
-      }
-      
+    });
+
+    it("approves and pays the request from escrow", async () => {
       await program.methods
-        .purchaseCart(
-          [productPda],
-          [new anchor.BN(1)],
-          new anchor.BN(productPrice)
-        )
+        .approveReturn()
         .accounts({
           store: storePda,
-          receipt: receiptKeypair.publicKey,
-          buyer: buyer.publicKey,
-          payer: buyer.publicKey,
-          storeOwner: storeOwner.publicKey,
-          escrowAccount: escrowPda,
-          systemProgram: SystemProgram.programId,
+          receipt: receiptPda,
+          returnRequest: returnRequestPda,
+          authority: storeOwner.publicKey,
         })
-        .signers([receiptKeypair])
+        .signers([storeOwner])
         .rpc();
-      
-      console.log("Made purchase for release test");
-      purchaseSuccessful = true;
-    } catch (error) {
-      console.error("Error making purchase for release test:", error);
-      if (error.toString().includes("insufficient lamports")) {
-        console.log("Insufficient lamports for transaction. Continuing with test.");
-      } else {
-        console.log("Continuing with test despite purchase error");
-      }
-    }
-    
-    // Skip the release test for now as it requires more complex setup
-    console.log("Skipping release test as it requires more complex setup");
-    
-    console.log("Released funds from escrow");
-    
-    // Verify store owner balance increased
-    const storeOwnerBalanceAfter = await provider.connection.getBalance(storeOwner.publicKey);
-    console.log("Store owner balance after release:", storeOwnerBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-    
-    // Verify escrow balance decreased
-    const escrowBalanceAfter = await provider.connection.getBalance(escrowPda);
-    console.log("Escrow balance after release:", escrowBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-    
-    // Skip verification since we skipped the functionality
-    assert.ok(true, "Release test skipped, no verification needed");
-  });
-  
-  it("handles refunds from escrow", async () => {
-    // Create a new receipt keypair for this test
-    const receiptKeypair = Keypair.generate();
-    console.log("Refund Test Receipt ID:", receiptKeypair.publicKey.toBase58());
-    
-    // First, make another purchase to have funds in escrow
-    let purchaseSuccessful = false;
-    try {
-      console.log("Purchasing product with ID:", productPda.toBase58());
-      console.log("Receipt ID:", receiptKeypair.publicKey.toBase58());
-      
-      // Check if buyer has enough SOL for the transaction
-      const buyerBalance = await provider.connection.getBalance(buyer.publicKey);
-      if (buyerBalance < productPrice * 2) { // Need extra for rent and fees
-        console.log("Warning: Buyer balance may be insufficient for transaction");
-        console.log("Current buyer balance:", buyerBalance / LAMPORTS_PER_SOL, "SOL");
-        console.log("Required balance:", (productPrice * 2) / LAMPORTS_PER_SOL, "SOL");
-      }
-      
+
+      const buyerBalanceBefore = await provider.connection.getBalance(buyer.publicKey);
       await program.methods
-        .purchaseCart(
-          [productPda],
-          [new anchor.BN(1)],
-          new anchor.BN(productPrice)
-        )
+        .payReturn()
         .accounts({
+          escrowAccount: escrowPda,
           store: storePda,
-          receipt: receiptKeypair.publicKey,
+          receipt: receiptPda,
+          returnRequest: returnRequestPda,
           buyer: buyer.publicKey,
-          payer: buyer.publicKey,
-          storeOwner: storeOwner.publicKey,
+          authority: storeOwner.publicKey,
+        })
+        .signers([storeOwner])
+        .rpc();
+      const buyerBalanceAfter = await provider.connection.getBalance(buyer.publicKey);
+      assert.equal(buyerBalanceAfter - buyerBalanceBefore, productPrice);
+
+      const request = await program.account.returnRequest.fetch(returnRequestPda);
+      assert.deepEqual(request.status, { paid: {} });
+      const receipt = await program.account.purchase.fetch(receiptPda);
+      assert.deepEqual(receipt.status, { refunded: {} });
+    });
+  });
+
+  describe("escrow settlement", () => {
+    let buyer: Keypair;
+    let receiptPda: PublicKey;
+
+    const setDisputeWindow = (seconds: number) =>
+      program.methods
+        .updateSettlementConfig(new anchor.BN(seconds), null, null, null)
+        .accounts({ store: storePda, owner: storeOwner.publicKey })
+        .signers([storeOwner])
+        .rpc();
+
+    const release = () =>
+      program.methods
+        .releaseEscrow()
+        .accounts({
           escrowAccount: escrowPda,
-          systemProgram: SystemProgram.programId,
+          store: storePda,
+          receipt: receiptPda,
+          payout: storeOwner.publicKey,
+          authority: storeOwner.publicKey,
         })
-        .signers([receiptKeypair])
+        .signers([storeOwner])
         .rpc();
-      
-      console.log("Product purchased successfully");
-      purchaseSuccessful = true;
-    } catch (error) {
-      console.error("Error making purchase:", error);
-      if (error.toString().includes("insufficient lamports")) {
-        console.log("Insufficient lamports for transaction. Skipping test.");
-        // Skip the test instead of failing
-        return assert.ok(true, "Skipping test due to insufficient lamports");
-      } else {
-        throw error;
+
+    before(async () => {
+      buyer = await newBuyer();
+      receiptPda = await nextReceiptPda(buyer.publicKey);
+      await purchase(buyer, 1, productPrice);
+    });
+
+    it("keeps funds in escrow during the dispute window", async () => {
+      try {
+        await release();
+        assert.fail("Expected release inside the dispute window to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "NothingToRelease");
       }
-    }
-    
-    console.log("Made purchase for refund testing");
-    
-    const buyerBalanceBefore = await provider.connection.getBalance(buyer.publicKey);
-    const escrowBalanceBefore = await provider.connection.getBalance(escrowPda);
-    
-    console.log("Buyer balance before refund:", buyerBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    console.log("Escrow balance before refund:", escrowBalanceBefore / LAMPORTS_PER_SOL, "SOL");
-    
-    // Skip the refund test for now as it requires more complex setup
-    console.log("Skipping refund test as it requires more complex setup");
-    
-    console.log("Refund from escrow successful");
-    
-    // Verify buyer balance increased
-    const buyerBalanceAfter = await provider.connection.getBalance(buyer.publicKey);
-    console.log("Buyer balance after refund:", buyerBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-    
-    // Verify escrow balance decreased
-    const escrowBalanceAfter = await provider.connection.getBalance(escrowPda);
-    console.log("Escrow balance after refund:", escrowBalanceAfter / LAMPORTS_PER_SOL, "SOL");
-    
-    // Skip verification since we skipped the functionality
-    console.log("Skipping balance verification since we skipped the refund functionality");
-    assert.ok(true, "Refund test skipped, no verification needed");
+    });
+
+    it("keeps the dispute window a purchase was made under", async () => {
+      await setDisputeWindow(0);
+      try {
+        await release();
+        assert.fail("Expected a shorter window to leave earlier purchases in escrow");
+      } catch (err) {
+        assert.include((err as Error).message, "NothingToRelease");
+      }
+    });
+
+    it("releases matured funds to the payout address", async () => {
+      buyer = await newBuyer();
+      receiptPda = await nextReceiptPda(buyer.publicKey);
+      await purchase(buyer, 1, productPrice);
+
+      const payoutBalanceBefore = await provider.connection.getBalance(storeOwner.publicKey);
+      await release();
+      const payoutBalanceAfter = await provider.connection.getBalance(storeOwner.publicKey);
+      assert.isAbove(payoutBalanceAfter, payoutBalanceBefore);
+
+      const receipt = await program.account.purchase.fetch(receiptPda);
+      assert.equal(receipt.settledAmount.toNumber(), productPrice);
+
+      const store = await program.account.store.fetch(storePda);
+      assert.isAtLeast(store.revenue.toNumber(), productPrice);
+    });
+
+    it("only pays the store's payout address", async () => {
+      const otherBuyer = await newBuyer();
+      const otherReceipt = await nextReceiptPda(otherBuyer.publicKey);
+      await purchase(otherBuyer, 1, productPrice);
+
+      try {
+        await program.methods
+          .releaseEscrow()
+          .accounts({
+            escrowAccount: escrowPda,
+            store: storePda,
+            receipt: otherReceipt,
+            payout: otherBuyer.publicKey,
+            authority: storeOwner.publicKey,
+          })
+          .signers([storeOwner])
+          .rpc();
+        assert.fail("Expected release to a foreign account to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "InvalidPayoutAddress");
+      }
+    });
+
+    after(async () => {
+      await setDisputeWindow(7 * 24 * 60 * 60);
+    });
   });
-  
-  it("prevents unauthorized escrow operations", async () => {
-    // Create a new receipt keypair for this test
-    const receiptKeypair = Keypair.generate();
-    console.log("Unauthorized Test Receipt ID:", receiptKeypair.publicKey.toBase58());
-    
-    // First, make another purchase to have funds in escrow
-    let purchaseSuccessful = false;
-    try {
-      console.log("Purchasing product with ID:", productPda.toBase58());
-      console.log("Receipt ID:", receiptKeypair.publicKey.toBase58());
-      
-      // Check if buyer has enough SOL for the transaction
-      const buyerBalance = await provider.connection.getBalance(buyer.publicKey);
-      if (buyerBalance < productPrice * 2) { // Need extra for rent and fees
-        console.log("Warning: Buyer balance may be insufficient for transaction");
-        console.log("Current buyer balance:", buyerBalance / LAMPORTS_PER_SOL, "SOL");
-        console.log("Required balance:", (productPrice * 2) / LAMPORTS_PER_SOL, "SOL");
+
+  describe("settlement crank", () => {
+    const crankTip = 10000;
+    const cranker = Keypair.generate();
+    let receipts: PublicKey[] = [];
+
+    const setSettlementConfig = (seconds: number, tip: number | null) =>
+      program.methods
+        .updateSettlementConfig(new anchor.BN(seconds), null, tip === null ? null : new anchor.BN(tip), null)
+        .accounts({ store: storePda, owner: storeOwner.publicKey })
+        .signers([storeOwner])
+        .rpc();
+
+    const crank = () =>
+      program.methods
+        .settleMaturedPurchases()
+        .accounts({
+          escrowAccount: escrowPda,
+          store: storePda,
+          payout: storeOwner.publicKey,
+          cranker: cranker.publicKey,
+        })
+        .remainingAccounts(receipts.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })))
+        .signers([cranker])
+        .rpc();
+
+    before(async () => {
+      await fundMultipleTestAccounts(provider, [cranker], 0.01);
+      for (let i = 0; i < 2; i++) {
+        const buyer = await newBuyer();
+        receipts.push(await nextReceiptPda(buyer.publicKey));
+        await purchase(buyer, 1, productPrice);
       }
-      
+
       await program.methods
-        .purchaseCart(
-          [productPda],
-          [new anchor.BN(1)],
-          new anchor.BN(productPrice)
-        )
+        .fundCrankBudget(new anchor.BN(crankTip * 10))
         .accounts({
-          store: storePda,
-          receipt: receiptKeypair.publicKey,
-          buyer: buyer.publicKey,
-          payer: buyer.publicKey,
-          storeOwner: storeOwner.publicKey,
           escrowAccount: escrowPda,
+          store: storePda,
+          authority: storeOwner.publicKey,
           systemProgram: SystemProgram.programId,
         })
-        .signers([receiptKeypair])
+        .signers([storeOwner])
         .rpc();
-      
-      console.log("Product purchased successfully");
-      purchaseSuccessful = true;
-    } catch (error) {
-      console.error("Error making purchase:", error);
-      if (error.toString().includes("insufficient lamports")) {
-        console.log("Insufficient lamports for transaction. Skipping test.");
-        // Skip the test instead of failing
-        return assert.ok(true, "Skipping test due to insufficient lamports");
-      } else {
-        throw error;
+    });
+
+    it("settles nothing before the dispute window ends", async () => {
+      try {
+        await crank();
+        assert.fail("Expected crank inside the dispute window to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "NothingToRelease");
+      }
+    });
+
+    it("lets anyone settle matured receipts for a tip", async () => {
+      await setSettlementConfig(0, crankTip);
+      receipts = [];
+      for (let i = 0; i < 2; i++) {
+        const buyer = await newBuyer();
+        receipts.push(await nextReceiptPda(buyer.publicKey));
+        await purchase(buyer, 1, productPrice);
       }
+
+      const budgetBefore = (await program.account.escrow.fetch(escrowPda)).crankBudget.toNumber();
+      const payoutBalanceBefore = await provider.connection.getBalance(storeOwner.publicKey);
+      const crankerBalanceBefore = await provider.connection.getBalance(cranker.publicKey);
+
+      await crank();
+
+      for (const receiptPda of receipts) {
+        const receipt = await program.account.purchase.fetch(receiptPda);
+        assert.equal(receipt.settledAmount.toNumber(), productPrice);
+      }
+
+      const payoutBalanceAfter = await provider.connection.getBalance(storeOwner.publicKey);
+      assert.equal(payoutBalanceAfter - payoutBalanceBefore, productPrice * receipts.length);
+
+      // The tip covers the transaction fee
+      const crankerBalanceAfter = await provider.connection.getBalance(cranker.publicKey);
+      assert.isAbove(crankerBalanceAfter, crankerBalanceBefore);
+
+      const budgetAfter = (await program.account.escrow.fetch(escrowPda)).crankBudget.toNumber();
+      assert.equal(budgetBefore - budgetAfter, crankTip * receipts.length);
+    });
+
+    it("does not settle a receipt twice", async () => {
+      try {
+        await crank();
+        assert.fail("Expected a repeated crank to fail");
+      } catch (err) {
+        assert.include((err as Error).message, "NothingToRelease");
+      }
+    });
+
+    after(async () => {
+      await setSettlementConfig(7 * 24 * 60 * 60, 0);
+    });
+  });
+
+  it("rejects underpayment", async () => {
+    const buyer = await newBuyer();
+    try {
+      await purchase(buyer, 1, 1);
+      assert.fail("Expected underpayment to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientPayment");
+    }
+  });
+
+  it("rejects overpayment", async () => {
+    const buyer = await newBuyer();
+    try {
+      await purchase(buyer, 1, productPrice + 1);
+      assert.fail("Expected overpayment to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "Overpayment");
+    }
+  });
+
+  it("rejects buying more than the available stock", async () => {
+    const buyer = await newBuyer();
+    try {
+      await purchase(buyer, productStock + 1, productPrice * (productStock + 1));
+      assert.fail("Expected oversized purchase to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientStock");
+    }
+  });
+
+  it("rejects product accounts that are not the store's product PDA", async () => {
+    const buyer = await newBuyer();
+    try {
+      await purchase(buyer, 1, productPrice, Keypair.generate().publicKey);
+      assert.fail("Expected purchase with a bogus product account to fail");
+    } catch (err) {
+      assert.notInclude((err as Error).message, "Expected purchase");
     }
-    
-    console.log("Made purchase for unauthorized test");
-    
-    // Get escrow balance
-    const escrowBalance = await provider.connection.getBalance(escrowPda);
-    const releaseAmount = escrowBalance > 0 ? escrowBalance : productPrice;
-    
-    // Skip the unauthorized test for now as it requires more complex setup
-    console.log("Skipping unauthorized test as it requires more complex setup");
-    
-    console.log("Cleaned up escrow funds");
-    
-    // Skip verification since we skipped the functionality
-    console.log("Skipping verification since we skipped the unauthorized operations test");
-    assert.ok(true, "Unauthorized test skipped, no verification needed");
   });
 });
@@ -0,0 +1,137 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+  findUserProfilePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap purchase history", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+  const maxHistory = 10;
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let profilePda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 10_000;
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const purchase = (index: number) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(1)], new anchor.BN(productPrice))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        buyerProfile: profilePda,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: productPda, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+    profilePda = findUserProfilePDA(buyer.publicKey, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("History Store", "Remembers checkouts", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Sticker",
+        "Cheap and cheerful",
+        "https://example.com/sticker.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(100),
+        { none: {} },
+        "https://example.com/sticker.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("checks out buyers without a profile", async () => {
+    await purchase(0);
+  });
+
+  it("appends each checkout to the buyer's profile", async () => {
+    await program.methods
+      .createUserWallet()
+      .accounts({
+        userProfile: profilePda,
+        authority: buyer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+    await purchase(1);
+
+    const profile = await program.account.userProfile.fetch(profilePda);
+    assert.equal(profile.totalPurchases.toNumber(), 1);
+    assert.equal(profile.purchaseHistory.length, 1);
+    const [record] = profile.purchaseHistory;
+    assert.ok(record.storeId.equals(storePda));
+    assert.ok(record.transactionId.equals(receiptPda(1)));
+    assert.equal(record.amount.toNumber(), productPrice);
+  });
+
+  it("overwrites the oldest record once the buffer is full", async () => {
+    for (let index = 2; index <= maxHistory + 1; index++) {
+      await purchase(index);
+    }
+
+    const profile = await program.account.userProfile.fetch(profilePda);
+    assert.equal(profile.totalPurchases.toNumber(), maxHistory + 1);
+    assert.equal(profile.purchaseHistory.length, maxHistory);
+    // The eleventh checkout on the profile took the first slot
+    assert.ok(profile.purchaseHistory[0].transactionId.equals(receiptPda(maxHistory + 1)));
+    assert.ok(profile.purchaseHistory[1].transactionId.equals(receiptPda(2)));
+  });
+});
@@ -0,0 +1,204 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findReservationPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap stock reservations", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const alice = Keypair.generate();
+  const bob = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 200_000;
+
+  const reservationPda = (buyer: Keypair) =>
+    findReservationPDA(productPda, buyer.publicKey, program.programId);
+
+  const purchaseCounterPda = (buyer: Keypair) =>
+    findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+  const receiptPda = (buyer: Keypair, index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const reserve = (buyer: Keypair, quantity: number) =>
+    program.methods
+      .reserveStock(productUuid, new anchor.BN(quantity))
+      .accounts({
+        store: storePda,
+        product: productPda,
+        reservation: reservationPda(buyer),
+        buyer: buyer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+  const purchase = (buyer: Keypair, quantity: number, reservations: PublicKey[]) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(quantity)], new anchor.BN(productPrice * quantity))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda(buyer),
+        receipt: receiptPda(buyer, 0),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(
+        [productPda, ...reservations].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([buyer])
+      .rpc();
+
+  const release = (buyer: Keypair, closer: Keypair) =>
+    program.methods
+      .releaseReservation()
+      .accounts({
+        product: productPda,
+        reservation: reservationPda(buyer),
+        buyer: buyer.publicKey,
+        closer: closer.publicKey,
+      })
+      .signers([closer])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, alice, bob], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+
+    await program.methods
+      .registerStore("Watch Shop", "Limited editions", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Watch",
+        "Only three made",
+        "https://example.com/watch.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(3),
+        { none: {} },
+        "https://example.com/watch.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("moves reserved units out of stock", async () => {
+    await reserve(alice, 2);
+
+    const product = await program.account.product.fetch(productPda);
+    assert.equal(product.stock.toNumber(), 1);
+    assert.equal(product.reserved.toNumber(), 2);
+    const reservation = await program.account.reservation.fetch(reservationPda(alice));
+    assert.equal(reservation.quantity.toNumber(), 2);
+  });
+
+  it("never extends a reservation past its maximum lifetime", async () => {
+    const made = await program.account.reservation.fetch(reservationPda(alice));
+    await reserve(alice, 1);
+
+    const reservation = await program.account.reservation.fetch(reservationPda(alice));
+    assert.equal(reservation.quantity.toNumber(), 3);
+    assert.equal(reservation.createdAt.toNumber(), made.createdAt.toNumber());
+    assert.isAtMost(reservation.expiresAt.toNumber(), reservation.createdAt.toNumber() + 60 * 60);
+  });
+
+  it("caps how many units one buyer can reserve", async () => {
+    try {
+      await reserve(bob, 11);
+      assert.fail("Expected the reservation to exceed the per-buyer limit");
+    } catch (err) {
+      assert.include((err as Error).message, "ReservationLimitExceeded");
+    }
+  });
+
+  it("keeps other buyers from buying reserved units", async () => {
+    try {
+      await purchase(bob, 2, []);
+      assert.fail("Expected reserved units to be unavailable");
+    } catch (err) {
+      assert.include((err as Error).message, "InsufficientStock");
+    }
+  });
+
+  it("rejects another buyer's reservation at checkout", async () => {
+    try {
+      await purchase(bob, 1, [reservationPda(alice)]);
+      assert.fail("Expected a foreign reservation to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InvalidReservation");
+    }
+  });
+
+  it("only lets others release a reservation once it has expired", async () => {
+    try {
+      await release(alice, bob);
+      assert.fail("Expected a live reservation to stay put");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("consumes the reservation at checkout and restocks what was not bought", async () => {
+    await purchase(alice, 1, [reservationPda(alice)]);
+
+    assert.isNull(await provider.connection.getAccountInfo(reservationPda(alice)));
+    const product = await program.account.product.fetch(productPda);
+    assert.equal(product.reserved.toNumber(), 0);
+    assert.equal(product.stock.toNumber(), 2);
+  });
+
+  it("lets the buyer cancel a reservation", async () => {
+    await reserve(bob, 1);
+    await release(bob, bob);
+
+    const product = await program.account.product.fetch(productPda);
+    assert.equal(product.stock.toNumber(), 2);
+    assert.equal(product.reserved.toNumber(), 0);
+  });
+});
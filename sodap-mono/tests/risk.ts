@@ -0,0 +1,208 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findRiskConfigPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap risk rules", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let riskConfigPda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 100_000;
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const purchase = (index: number, quantity: number) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(quantity)], new anchor.BN(productPrice * quantity))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: productPda, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+  const updateRiskConfig = (
+    maxCartValue: number | null,
+    maxPurchasesPerHour: number | null,
+    minAccountAge: number | null,
+    holdFlagged: boolean | null
+  ) =>
+    program.methods
+      .updateRiskConfig(
+        maxCartValue === null ? null : new anchor.BN(maxCartValue),
+        maxPurchasesPerHour,
+        minAccountAge === null ? null : new anchor.BN(minAccountAge),
+        holdFlagged
+      )
+      .accounts({
+        store: storePda,
+        riskConfig: riskConfigPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+  const release = (index: number) =>
+    program.methods
+      .releaseEscrow()
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(index),
+        payout: storeOwner.publicKey,
+        authority: storeOwner.publicKey,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+    riskConfigPda = findRiskConfigPDA(storePda, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Risky Store", "Watches its buyers", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Gift Card",
+        "Easy to resell",
+        "https://example.com/card.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(100),
+        { none: {} },
+        "https://example.com/card.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    // Settle immediately so holds are the only thing keeping funds in escrow
+    await program.methods
+      .updateSettlementConfig(new anchor.BN(0), null, null, null)
+      .accounts({ store: storePda, owner: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("does not flag purchases at stores without risk rules", async () => {
+    await purchase(0, 1);
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.deepEqual(receipt.anomaly, { none: {} });
+    assert.isFalse(receipt.held);
+  });
+
+  it("flags carts above the maximum value", async () => {
+    await updateRiskConfig(productPrice * 2, null, null, false);
+
+    await purchase(1, 3);
+    const receipt = await program.account.purchase.fetch(receiptPda(1));
+    assert.deepEqual(receipt.anomaly, { suspicious: {} });
+    assert.isFalse(receipt.held);
+  });
+
+  it("flags buyers over the hourly purchase limit", async () => {
+    await updateRiskConfig(0, 1, null, null);
+
+    // Two purchases already fall in this hour's window
+    await purchase(2, 1);
+    const receipt = await program.account.purchase.fetch(receiptPda(2));
+    assert.deepEqual(receipt.anomaly, { suspicious: {} });
+  });
+
+  it("flags buyers without an old enough profile", async () => {
+    await updateRiskConfig(null, 0, 24 * 60 * 60, null);
+
+    await purchase(3, 1);
+    const receipt = await program.account.purchase.fetch(receiptPda(3));
+    assert.deepEqual(receipt.anomaly, { suspicious: {} });
+  });
+
+  it("holds flagged purchases out of settlement until cleared", async () => {
+    await updateRiskConfig(null, null, null, true);
+
+    await purchase(4, 1);
+    const receipt = await program.account.purchase.fetch(receiptPda(4));
+    assert.isTrue(receipt.held);
+
+    try {
+      await release(4);
+      assert.fail("Expected a held purchase to stay in escrow");
+    } catch (err) {
+      assert.include((err as Error).message, "PurchaseOnHold");
+    }
+
+    try {
+      await program.methods
+        .clearAnomaly()
+        .accounts({ store: storePda, receipt: receiptPda(4), authority: buyer.publicKey })
+        .signers([buyer])
+        .rpc();
+      assert.fail("Expected only store admins to clear flags");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    await program.methods
+      .clearAnomaly()
+      .accounts({ store: storePda, receipt: receiptPda(4), authority: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+
+    const cleared = await program.account.purchase.fetch(receiptPda(4));
+    assert.deepEqual(cleared.anomaly, { none: {} });
+    assert.isFalse(cleared.held);
+    await release(4);
+  });
+});
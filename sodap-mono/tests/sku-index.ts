@@ -0,0 +1,140 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findProductPDA,
+  findSkuIndexPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap sku index", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+
+  let storePda: PublicKey;
+
+  const milk = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const juice = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const milkBarcode = "4006381333931";
+  const newMilkBarcode = "4006381333948";
+
+  const productPda = (uuid: number[]) =>
+    findProductPDA(storePda, uuid, program.programId);
+
+  // Scanners derive this from the barcode alone
+  const skuIndexPda = (barcode: string) =>
+    findSkuIndexPDA(storePda, barcode, program.programId);
+
+  const register = (uuid: number[], barcode: string | null) =>
+    program.methods
+      .registerProduct(
+        uuid,
+        "Milk",
+        "One litre",
+        "https://example.com/milk.png",
+        new anchor.BN(15_000),
+        new anchor.BN(50),
+        { none: {} },
+        "https://example.com/milk.json",
+        [],
+        barcode
+      )
+      .accounts({
+        store: storePda,
+        product: productPda(uuid),
+        skuIndex: barcode === null ? null : skuIndexPda(barcode),
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Dairy", "Fresh every day", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("indexes a product's barcode when it is registered", async () => {
+    await register(milk, milkBarcode);
+
+    const skuIndex = await program.account.skuIndex.fetch(skuIndexPda(milkBarcode));
+    assert.ok(skuIndex.product.equals(productPda(milk)));
+    assert.deepEqual(skuIndex.productUuid, milk);
+    const product = await program.account.product.fetch(productPda(milk));
+    assert.equal(product.barcode, milkBarcode);
+  });
+
+  it("rejects malformed and duplicate barcodes", async () => {
+    try {
+      await register(juice, "12AB5678");
+      assert.fail("Expected a non-numeric barcode to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InvalidBarcode");
+    }
+
+    try {
+      await register(juice, milkBarcode);
+      assert.fail("Expected a duplicate barcode to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "DuplicateBarcode");
+    }
+  });
+
+  it("moves the index when the barcode changes", async () => {
+    await program.methods
+      .updateProduct(milk, null, null, null, null, null, null, newMilkBarcode)
+      .accounts({
+        store: storePda,
+        product: productPda(milk),
+        skuIndex: skuIndexPda(milkBarcode),
+        newSkuIndex: skuIndexPda(newMilkBarcode),
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    assert.isNull(await provider.connection.getAccountInfo(skuIndexPda(milkBarcode)));
+    const skuIndex = await program.account.skuIndex.fetch(skuIndexPda(newMilkBarcode));
+    assert.ok(skuIndex.product.equals(productPda(milk)));
+  });
+
+  it("frees the barcode when the product is deactivated", async () => {
+    await program.methods
+      .deactivateProduct(milk)
+      .accounts({
+        store: storePda,
+        product: productPda(milk),
+        skuIndex: skuIndexPda(newMilkBarcode),
+        authority: storeOwner.publicKey,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    assert.isNull(await provider.connection.getAccountInfo(skuIndexPda(newMilkBarcode)));
+
+    await register(juice, newMilkBarcode);
+    const skuIndex = await program.account.skuIndex.fetch(skuIndexPda(newMilkBarcode));
+    assert.ok(skuIndex.product.equals(productPda(juice)));
+  });
+});
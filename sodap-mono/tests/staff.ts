@@ -0,0 +1,271 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findProductPDA,
+  findStaffMemberPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap store staff", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const floorStaff = Array.from({ length: 12 }, () => Keypair.generate());
+  const supervisor = floorStaff[0];
+  const temp = Keypair.generate();
+  const seasonal = Keypair.generate();
+
+  let storePda: PublicKey;
+
+  const staffPda = (staff: PublicKey) =>
+    findStaffMemberPDA(storePda, staff, program.programId);
+
+  const addAdmin = (
+    admin: PublicKey,
+    role: object,
+    label: string,
+    validFrom: number | null = null,
+    validUntil: number | null = null
+  ) =>
+    program.methods
+      .addStoreAdmin(
+        storePda,
+        admin,
+        role as any,
+        label,
+        validFrom === null ? null : new anchor.BN(validFrom),
+        validUntil === null ? null : new anchor.BN(validUntil)
+      )
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+  const suspend = (admin: PublicKey, suspended: boolean) =>
+    program.methods
+      .suspendStoreAdmin(storePda, admin, suspended)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        authority: storeOwner.publicKey,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+  const removeAdmin = (signer: Keypair, admin: PublicKey) =>
+    program.methods
+      .removeStoreAdmin(storePda, admin)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        owner: storeOwner.publicKey,
+        authority: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+
+  const listProduct = (signer: Keypair) => {
+    const uuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+    return program.methods
+      .registerProduct(
+        uuid,
+        "Mug",
+        "Ceramic",
+        "https://example.com/mug.png",
+        new anchor.BN(25_000),
+        new anchor.BN(4),
+        { none: {} },
+        "https://example.com/mug.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: findProductPDA(storePda, uuid, program.programId),
+        authority: signer.publicKey,
+        staff: staffPda(signer.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  const chainTime = async () =>
+    (await provider.connection.getBlockTime(await provider.connection.getSlot()))!;
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, supervisor, temp, seasonal], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+
+    await program.methods
+      .registerStore("Flagship", "Open late", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("keeps one staff record per admin with no cap on headcount", async () => {
+    await addAdmin(supervisor.publicKey, { manager: {} }, "Supervisor");
+    for (const [i, staff] of floorStaff.slice(1).entries()) {
+      await addAdmin(staff.publicKey, { viewer: {} }, `Till ${i + 1}`);
+    }
+
+    const records = await program.account.staffMember.all([
+      { memcmp: { offset: 8, bytes: storePda.toBase58() } },
+    ]);
+    assert.equal(records.length, floorStaff.length);
+
+    const record = await program.account.staffMember.fetch(staffPda(supervisor.publicKey));
+    assert.ok(record.staff.equals(supervisor.publicKey));
+    assert.equal(record.label, "Supervisor");
+    assert.ok(record.isActive);
+    assert.isAbove(record.addedAt.toNumber(), 0);
+  });
+
+  it("rejects adding the same admin twice", async () => {
+    try {
+      await addAdmin(supervisor.publicKey, { viewer: {} }, "Again");
+      assert.fail("Expected a duplicate admin to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "AdminAlreadyExists");
+    }
+  });
+
+  it("withholds capabilities while an admin is suspended", async () => {
+    await suspend(supervisor.publicKey, true);
+    try {
+      await listProduct(supervisor);
+      assert.fail("Expected a suspended admin to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    await suspend(supervisor.publicKey, false);
+    await listProduct(supervisor);
+  });
+
+  it("closes the staff record on removal", async () => {
+    const signature = await removeAdmin(storeOwner, supervisor.publicKey);
+
+    assert.isNull(await provider.connection.getAccountInfo(staffPda(supervisor.publicKey)));
+
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const [event] = [...parser.parseLogs(tx.meta.logMessages)];
+    assert.equal(event.name, "adminRemoved");
+    assert.ok(event.data.adminPubkey.equals(supervisor.publicKey));
+    assert.isFalse(event.data.suspended);
+  });
+
+  it("fails to remove an admin who does not exist", async () => {
+    try {
+      await removeAdmin(storeOwner, supervisor.publicKey);
+      assert.fail("Expected removing a missing admin to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "AdminNotFound");
+    }
+  });
+
+  it("never removes the store owner", async () => {
+    try {
+      await removeAdmin(storeOwner, storeOwner.publicKey);
+      assert.fail("Expected the store owner to be kept");
+    } catch (err) {
+      assert.include((err as Error).message, "CannotRemoveOwner");
+    }
+  });
+
+  it("lets staff remove themselves", async () => {
+    const leaver = floorStaff[1];
+    const ownerBalance = await provider.connection.getBalance(storeOwner.publicKey);
+
+    await removeAdmin(leaver, leaver.publicKey);
+
+    assert.isNull(await provider.connection.getAccountInfo(staffPda(leaver.publicKey)));
+    assert.isAbove(await provider.connection.getBalance(storeOwner.publicKey), ownerBalance);
+  });
+
+  it("keeps viewers from removing other staff", async () => {
+    try {
+      await removeAdmin(floorStaff[2], floorStaff[3].publicKey);
+      assert.fail("Expected a viewer to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("honours a grant only inside its window", async () => {
+    const now = await chainTime();
+    await addAdmin(temp.publicKey, { manager: {} }, "Temp", null, now + 5);
+    await addAdmin(seasonal.publicKey, { manager: {} }, "Seasonal", now + 3600, null);
+
+    await listProduct(temp);
+    try {
+      await listProduct(seasonal);
+      assert.fail("Expected a grant that has not started to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    while ((await chainTime()) < now + 5) {
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+    }
+    try {
+      await listProduct(temp);
+      assert.fail("Expected an expired grant to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("rejects a grant that ends before it can start", async () => {
+    const now = await chainTime();
+    try {
+      await addAdmin(Keypair.generate().publicKey, { viewer: {} }, "Never", now + 60, now + 30);
+      assert.fail("Expected an empty window to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "InvalidParameters");
+    }
+  });
+
+  it("lets anyone close expired staff records, refunding the store owner", async () => {
+    const ownerBalance = await provider.connection.getBalance(storeOwner.publicKey);
+
+    await program.methods
+      .removeExpiredStaff()
+      .accounts({ store: storePda, owner: storeOwner.publicKey })
+      .remainingAccounts(
+        [temp, seasonal].map((staff) => ({
+          pubkey: staffPda(staff.publicKey),
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .rpc();
+
+    assert.isNull(await provider.connection.getAccountInfo(staffPda(temp.publicKey)));
+    assert.isNotNull(await provider.connection.getAccountInfo(staffPda(seasonal.publicKey)));
+    assert.isAbove(await provider.connection.getBalance(storeOwner.publicKey), ownerBalance);
+  });
+});
@@ -0,0 +1,199 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStaffMemberPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap store roles", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const manager = Keypair.generate();
+  const viewer = Keypair.generate();
+  const buyer = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 300_000;
+  const refundLimit = 100_000;
+
+  const productPda = () =>
+    findProductPDA(storePda, productUuid, program.programId);
+
+  const staffPda = (staff: PublicKey) =>
+    findStaffMemberPDA(storePda, staff, program.programId);
+
+  // The owner signs without a staff record; everyone else passes theirs
+  const staffOf = (signer: Keypair) =>
+    signer === storeOwner ? null : staffPda(signer.publicKey);
+
+  const addAdmin = (authority: Keypair, admin: PublicKey, role: object) =>
+    program.methods
+      .addStoreAdmin(storePda, admin, role as any, "Floor staff", null, null)
+      .accounts({
+        store: storePda,
+        staffMember: staffPda(admin),
+        authority: authority.publicKey,
+        staff: staffOf(authority),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+  const registerProduct = (authority: Keypair) =>
+    program.methods
+      .registerProduct(
+        productUuid,
+        "Scarf",
+        "Wool",
+        "https://example.com/scarf.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(5),
+        { none: {} },
+        "https://example.com/scarf.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda(),
+        authority: authority.publicKey,
+        staff: staffOf(authority),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+  const refund = (receipt: PublicKey, authority: Keypair, amount: number) =>
+    program.methods
+      .refundFromEscrow(new anchor.BN(amount))
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt,
+        buyer: buyer.publicKey,
+        authority: authority.publicKey,
+        staff: staffOf(authority),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, manager, viewer, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+
+    await program.methods
+      .registerStore("Boutique", "Knitwear", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("lets the owner add a manager and a viewer", async () => {
+    await addAdmin(storeOwner, manager.publicKey, { manager: {} });
+    await addAdmin(storeOwner, viewer.publicKey, { viewer: {} });
+
+    const managerStaff = await program.account.staffMember.fetch(staffPda(manager.publicKey));
+    assert.ok("manager" in managerStaff.role);
+    assert.ok(managerStaff.isActive);
+    const viewerStaff = await program.account.staffMember.fetch(staffPda(viewer.publicKey));
+    assert.ok("viewer" in viewerStaff.role);
+  });
+
+  it("keeps viewers read-only", async () => {
+    try {
+      await registerProduct(viewer);
+      assert.fail("Expected a viewer to be refused");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("lets a manager list products without the owner's wallet", async () => {
+    await registerProduct(manager);
+
+    const product = await program.account.product.fetch(productPda());
+    assert.ok(product.authority.equals(manager.publicKey));
+  });
+
+  it("stops a manager from granting owner or editing the store", async () => {
+    try {
+      await addAdmin(manager, buyer.publicKey, { owner: {} });
+      assert.fail("Expected a manager to be unable to grant owner");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+
+    try {
+      await program.methods
+        .updateStore(storePda, "Taken over", null, null)
+        .accounts({ store: storePda, authority: manager.publicKey, staff: staffPda(manager.publicKey) })
+        .signers([manager])
+        .rpc();
+      assert.fail("Expected a manager to be unable to edit the store");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("caps manager refunds at the store's refund limit", async () => {
+    await program.methods
+      .updateSettlementConfig(null, null, null, new anchor.BN(refundLimit))
+      .accounts({ store: storePda, owner: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+
+    const purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+    const receiptPda = findReceiptPDA(storePda, buyer.publicKey, 0, program.programId);
+    await program.methods
+      .purchaseCart([productUuid], [new anchor.BN(1)], new anchor.BN(productPrice))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda,
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: productPda(), isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+    try {
+      await refund(receiptPda, manager, refundLimit + 1);
+      assert.fail("Expected the refund to exceed the manager's limit");
+    } catch (err) {
+      assert.include((err as Error).message, "RefundLimitExceeded");
+    }
+
+    await refund(receiptPda, manager, refundLimit);
+    await refund(receiptPda, storeOwner, productPrice - refundLimit);
+
+    const receipt = await program.account.purchase.fetch(receiptPda);
+    assert.equal(receipt.refundedAmount.toNumber(), productPrice);
+  });
+});
@@ -111,13 +111,6 @@ describe("sodap store", () => {
     // Assert that revenue is either 0 or 1 (both are valid for our test)
     const revenue = storeAccount.revenue.toNumber();
     assert.ok(revenue === 0 || revenue === 1, `Revenue should be 0 or 1, got ${revenue}`);
-
-    // For a hardcoded PDA, the admin roles might vary between test runs
-    console.log("Admin roles length:", storeAccount.adminRoles.length);
-    // Assert that admin roles is either 0 or 1 (both are valid for our test)
-    const adminRolesLength = storeAccount.adminRoles.length;
-    assert.ok(adminRolesLength === 0 || adminRolesLength === 1, 
-      `Admin roles length should be 0 or 1, got ${adminRolesLength}`);
   });
 
   it("updates store metadata", async () => {
@@ -172,9 +165,7 @@ describe("sodap store", () => {
         )
         .accounts({
           store: storePda,
-          owner: unauthorizedUser.publicKey,
-          payer: unauthorizedUser.publicKey, // Explicitly add payer
-          systemProgram: SystemProgram.programId,
+          authority: unauthorizedUser.publicKey,
         })
         .signers([unauthorizedUser])
         .rpc();
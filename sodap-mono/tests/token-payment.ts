@@ -0,0 +1,478 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair, Transaction, sendAndConfirmTransaction } from "@solana/web3.js";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  getMintLen,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+} from "../utils/pda-helpers";
+
+describe("sodap token payment", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let mint: PublicKey;
+  let escrowVault: PublicKey;
+  let buyerTokenAccount: PublicKey;
+  let ownerTokenAccount: PublicKey;
+
+  // A USDC-like mint with 6 decimals
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 250_000_000; // 250 tokens
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+  const purchaseCounterPda = () =>
+    findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+
+  const tokenBalance = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account)).amount);
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+
+    mint = await createMint(provider.connection, storeOwner, storeOwner.publicKey, null, 6);
+    escrowVault = getAssociatedTokenAddressSync(mint, escrowPda, true);
+    buyerTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, buyer, mint, buyer.publicKey)
+    ).address;
+    ownerTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, storeOwner, mint, storeOwner.publicKey)
+    ).address;
+    await mintTo(provider.connection, storeOwner, mint, buyerTokenAccount, storeOwner, productPrice * 4);
+
+    await program.methods
+      .registerStore("Token Store", "Priced in tokens", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Token Watch",
+        "Priced in tokens",
+        "https://example.com/product/image.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(10),
+        { none: {} },
+        "https://example.com/product.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    // Settle immediately so receipts can be released as soon as they are paid
+    await program.methods
+      .updateSettlementConfig(new anchor.BN(0), null, null, null)
+      .accounts({ store: storePda, owner: storeOwner.publicKey })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  const purchase = (index: number) =>
+    program.methods
+      .purchaseCart([productUuid], [new anchor.BN(1)], new anchor.BN(productPrice))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda(),
+        receipt: receiptPda(index),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+        buyerTokenAccount,
+        escrowVault,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([{ pubkey: productPda, isWritable: true, isSigner: false }])
+      .signers([buyer])
+      .rpc();
+
+  it("prices the store in an SPL mint", async () => {
+    await program.methods
+      .setPaymentMint()
+      .accounts({
+        store: storePda,
+        escrowAccount: escrowPda,
+        mint,
+        escrowVault,
+        owner: storeOwner.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const store = await program.account.store.fetch(storePda);
+    assert.ok(store.paymentMint.equals(mint));
+    assert.equal(await tokenBalance(escrowVault), 0);
+  });
+
+  it("escrows token payments", async () => {
+    const buyerBefore = await tokenBalance(buyerTokenAccount);
+    await purchase(0);
+
+    assert.equal(buyerBefore - (await tokenBalance(buyerTokenAccount)), productPrice);
+    assert.equal(await tokenBalance(escrowVault), productPrice);
+
+    const escrow = await program.account.escrow.fetch(escrowPda);
+    assert.equal(escrow.balance.toNumber(), productPrice);
+  });
+
+  it("requires token accounts for token-priced stores", async () => {
+    try {
+      await program.methods
+        .purchaseCart([productUuid], [new anchor.BN(1)], new anchor.BN(productPrice))
+        .accounts({
+          store: storePda,
+          purchaseCounter: purchaseCounterPda(),
+          receipt: receiptPda(1),
+          buyer: buyer.publicKey,
+          storeOwner: storeOwner.publicKey,
+          escrowAccount: escrowPda,
+          systemProgram: SystemProgram.programId,
+          buyerTokenAccount: null,
+          escrowVault: null,
+          mint: null,
+          tokenProgram: null,
+        })
+        .remainingAccounts([{ pubkey: productPda, isWritable: true, isSigner: false }])
+        .signers([buyer])
+        .rpc();
+      assert.fail("Expected a lamport payment to a token-priced store to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "TokenAccountsRequired");
+    }
+  });
+
+  it("refunds in the payment mint", async () => {
+    const refund = productPrice / 5;
+    const buyerBefore = await tokenBalance(buyerTokenAccount);
+
+    await program.methods
+      .refundFromEscrow(new anchor.BN(refund))
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(0),
+        buyer: buyer.publicKey,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+        buyerTokenAccount,
+        escrowVault,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    assert.equal((await tokenBalance(buyerTokenAccount)) - buyerBefore, refund);
+    assert.equal(await tokenBalance(escrowVault), productPrice - refund);
+  });
+
+  it("releases to the payout address in the payment mint", async () => {
+    const ownerBefore = await tokenBalance(ownerTokenAccount);
+    await program.methods
+      .releaseEscrow()
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(0),
+        payout: storeOwner.publicKey,
+        authority: storeOwner.publicKey,
+        payoutTokenAccount: ownerTokenAccount,
+        escrowVault,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const released = productPrice - productPrice / 5;
+    assert.equal((await tokenBalance(ownerTokenAccount)) - ownerBefore, released);
+    assert.equal(await tokenBalance(escrowVault), 0);
+  });
+
+  it("cannot change the payment mint while escrow holds funds", async () => {
+    await purchase(1);
+    const otherMint = await createMint(provider.connection, storeOwner, storeOwner.publicKey, null, 6);
+
+    try {
+      await program.methods
+        .setPaymentMint()
+        .accounts({
+          store: storePda,
+          escrowAccount: escrowPda,
+          mint: otherMint,
+          escrowVault: getAssociatedTokenAddressSync(otherMint, escrowPda, true),
+          owner: storeOwner.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([storeOwner])
+        .rpc();
+      assert.fail("Expected changing the mint with funds in escrow to fail");
+    } catch (err) {
+      assert.include((err as Error).message, "EscrowNotEmpty");
+    }
+  });
+
+  it("keeps the payment mint and settlement config when the store registers again", async () => {
+    await program.methods
+      .registerStore("Token Store", "Still priced in tokens", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const store = await program.account.store.fetch(storePda);
+    assert.equal(store.description, "Still priced in tokens");
+    assert.ok(store.paymentMint.equals(mint));
+    assert.equal(store.disputeWindow.toNumber(), 0);
+    assert.equal(await tokenBalance(escrowVault), productPrice);
+  });
+
+  it("prices the store in SOL again once escrow is empty", async () => {
+    await program.methods
+      .releaseEscrow()
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(1),
+        payout: storeOwner.publicKey,
+        authority: storeOwner.publicKey,
+        payoutTokenAccount: ownerTokenAccount,
+        escrowVault,
+        mint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .setPaymentMint()
+      .accounts({
+        store: storePda,
+        escrowAccount: escrowPda,
+        mint: null,
+        escrowVault: null,
+        owner: storeOwner.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const store = await program.account.store.fetch(storePda);
+    assert.isNull(store.paymentMint);
+  });
+
+  describe("Token-2022 transfer fees", () => {
+    const feeOwner = Keypair.generate();
+    const feeBuyer = Keypair.generate();
+    const feeBasisPoints = 100; // 1%
+    const feeProductUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+
+    let feeStorePda: PublicKey;
+    let feeEscrowPda: PublicKey;
+    let feeProductPda: PublicKey;
+    let feeMint: PublicKey;
+    let feeVault: PublicKey;
+    let feeBuyerTokenAccount: PublicKey;
+
+    before(async () => {
+      await fundMultipleTestAccounts(provider, [feeOwner, feeBuyer], 0.1);
+
+      feeStorePda = findStorePDA(feeOwner.publicKey, program.programId);
+      feeEscrowPda = findEscrowPDA(feeStorePda, program.programId);
+      feeProductPda = findProductPDA(feeStorePda, feeProductUuid, program.programId);
+
+      // A Token-2022 stablecoin-style mint that withholds 1% per transfer
+      const mintKeypair = Keypair.generate();
+      feeMint = mintKeypair.publicKey;
+      const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+      const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+      await sendAndConfirmTransaction(
+        provider.connection,
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: feeOwner.publicKey,
+            newAccountPubkey: feeMint,
+            space: mintLen,
+            lamports,
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeTransferFeeConfigInstruction(
+            feeMint,
+            feeOwner.publicKey,
+            feeOwner.publicKey,
+            feeBasisPoints,
+            BigInt(1_000_000_000_000),
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(feeMint, 6, feeOwner.publicKey, null, TOKEN_2022_PROGRAM_ID)
+        ),
+        [feeOwner, mintKeypair]
+      );
+
+      feeVault = getAssociatedTokenAddressSync(feeMint, feeEscrowPda, true, TOKEN_2022_PROGRAM_ID);
+      feeBuyerTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          feeBuyer,
+          feeMint,
+          feeBuyer.publicKey,
+          false,
+          undefined,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ).address;
+      await mintTo(
+        provider.connection,
+        feeOwner,
+        feeMint,
+        feeBuyerTokenAccount,
+        feeOwner,
+        productPrice * 2,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      await program.methods
+        .registerStore("Fee Store", "Priced in a Token-2022 mint", "https://example.com/store")
+        .accounts({
+          store: feeStorePda,
+          authority: feeOwner.publicKey,
+          payer: feeOwner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([feeOwner])
+        .rpc();
+
+      await program.methods
+        .registerProduct(
+          feeProductUuid,
+          "Fee Watch",
+          "Priced in a Token-2022 mint",
+          "https://example.com/product/image.png",
+          new anchor.BN(productPrice),
+          new anchor.BN(10),
+          { none: {} },
+          "https://example.com/product.json",
+          [],
+          null
+        )
+        .accounts({
+          store: feeStorePda,
+          product: feeProductPda,
+          authority: feeOwner.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([feeOwner])
+        .rpc();
+
+      await program.methods
+        .setPaymentMint()
+        .accounts({
+          store: feeStorePda,
+          escrowAccount: feeEscrowPda,
+          mint: feeMint,
+          escrowVault: feeVault,
+          owner: feeOwner.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([feeOwner])
+        .rpc();
+    });
+
+    it("records the net amount escrow received", async () => {
+      const counterPda = findPurchaseCounterPDA(feeStorePda, feeBuyer.publicKey, program.programId);
+      const feeReceiptPda = findReceiptPDA(feeStorePda, feeBuyer.publicKey, 0, program.programId);
+
+      await program.methods
+        .purchaseCart([feeProductUuid], [new anchor.BN(1)], new anchor.BN(productPrice))
+        .accounts({
+          store: feeStorePda,
+          purchaseCounter: counterPda,
+          receipt: feeReceiptPda,
+          buyer: feeBuyer.publicKey,
+          storeOwner: feeOwner.publicKey,
+          escrowAccount: feeEscrowPda,
+          systemProgram: SystemProgram.programId,
+          buyerTokenAccount: feeBuyerTokenAccount,
+          escrowVault: feeVault,
+          mint: feeMint,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .remainingAccounts([{ pubkey: feeProductPda, isWritable: true, isSigner: false }])
+        .signers([feeBuyer])
+        .rpc();
+
+      const net = productPrice - (productPrice * feeBasisPoints) / 10_000;
+      const vault = await getAccount(provider.connection, feeVault, undefined, TOKEN_2022_PROGRAM_ID);
+      assert.equal(Number(vault.amount), net);
+
+      const receipt = await program.account.purchase.fetch(feeReceiptPda);
+      assert.equal(receipt.totalPaid.toNumber(), net);
+
+      const escrow = await program.account.escrow.fetch(feeEscrowPda);
+      assert.equal(escrow.balance.toNumber(), net);
+    });
+  });
+});
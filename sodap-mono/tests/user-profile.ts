@@ -0,0 +1,90 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import { assert } from "chai";
+import { createHash, randomBytes } from "crypto";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import { findUserProfilePDA } from "../utils/pda-helpers";
+
+describe("sodap user profile", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const user = Keypair.generate();
+  const salt = randomBytes(32);
+  const zero = new Array(32).fill(0);
+
+  let profilePda: PublicKey;
+
+  const commit = (value: string) =>
+    Array.from(createHash("sha256").update(salt).update(value).digest());
+
+  const update = (
+    userId: string | null,
+    name: number[] | null,
+    email: number[] | null,
+    phone: number[] | null,
+    deliveryAddress: number[] | null
+  ) =>
+    program.methods
+      .createOrUpdateUserProfile(userId, name, email, phone, deliveryAddress, null)
+      .accounts({
+        userProfile: profilePda,
+        authority: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [user], 0.1);
+
+    profilePda = findUserProfilePDA(user.publicKey, program.programId);
+  });
+
+  it("creates the profile with committed contact fields", async () => {
+    await update(null, commit("Ada"), commit("ada@example.com"), null, null);
+
+    const profile = await program.account.userProfile.fetch(profilePda);
+    assert.ok(profile.authority.equals(user.publicKey));
+    assert.equal(profile.userId, user.publicKey.toBase58());
+    assert.deepEqual(profile.nameCommitment, commit("Ada"));
+    assert.deepEqual(profile.emailCommitment, commit("ada@example.com"));
+    assert.deepEqual(profile.phoneCommitment, zero);
+    assert.isAbove(profile.createdAt.toNumber(), 0);
+  });
+
+  it("only changes the fields passed and emits one update", async () => {
+    const signature = await update("ada", null, zero, null, commit("1 Main St"));
+
+    const profile = await program.account.userProfile.fetch(profilePda);
+    assert.equal(profile.userId, "ada");
+    assert.deepEqual(profile.nameCommitment, commit("Ada"));
+    assert.deepEqual(profile.emailCommitment, zero);
+    assert.deepEqual(profile.deliveryAddressCommitment, commit("1 Main St"));
+
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const events = [...parser.parseLogs(tx.meta.logMessages)];
+    assert.equal(events.length, 1);
+    assert.equal(events[0].name, "userProfileUpdated");
+  });
+
+  it("rejects empty and over-long user ids", async () => {
+    for (const userId of ["", "x".repeat(51)]) {
+      try {
+        await update(userId, null, null, null, null);
+        assert.fail("Expected the user id to be rejected");
+      } catch (err) {
+        assert.include((err as Error).message, "InvalidProfileField");
+      }
+    }
+  });
+});
@@ -0,0 +1,256 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Sodap } from "../target/types/sodap";
+import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
+import {
+  TOKEN_2022_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getOrCreateAssociatedTokenAccount,
+  transfer,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { Buffer } from "buffer";
+import { fundMultipleTestAccounts } from "./utils/devnet-utils";
+import {
+  findEscrowPDA,
+  findProductPDA,
+  findPurchaseCounterPDA,
+  findReceiptPDA,
+  findStorePDA,
+  findVoucherMintPDA,
+} from "../utils/pda-helpers";
+
+describe("sodap vouchers", () => {
+  // Configure the client to use the local cluster
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.Sodap as Program<Sodap>;
+
+  const storeOwner = Keypair.generate();
+  const buyer = Keypair.generate();
+  const friend = Keypair.generate();
+
+  let storePda: PublicKey;
+  let escrowPda: PublicKey;
+  let productPda: PublicKey;
+  let voucherMintPda: PublicKey;
+  let purchaseCounterPda: PublicKey;
+  let buyerVouchers: PublicKey;
+
+  const productUuid = Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+  const productPrice = 100_000;
+
+  const receiptPda = (index: number) =>
+    findReceiptPDA(storePda, buyer.publicKey, index, program.programId);
+
+  const vouchers = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account, undefined, TOKEN_2022_PROGRAM_ID)).amount);
+
+  const redeem = (holder: Keypair, holderVoucherAccount: PublicKey, authority: Keypair, amount: number) =>
+    program.methods
+      .redeemVouchers(productUuid, new anchor.BN(amount))
+      .accounts({
+        store: storePda,
+        product: productPda,
+        voucherMint: voucherMintPda,
+        holderVoucherAccount,
+        holder: holder.publicKey,
+        authority: authority.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([holder, authority])
+      .rpc();
+
+  before(async () => {
+    await fundMultipleTestAccounts(provider, [storeOwner, buyer, friend], 0.1);
+
+    storePda = findStorePDA(storeOwner.publicKey, program.programId);
+    escrowPda = findEscrowPDA(storePda, program.programId);
+    productPda = findProductPDA(storePda, productUuid, program.programId);
+    voucherMintPda = findVoucherMintPDA(productPda, program.programId);
+    purchaseCounterPda = findPurchaseCounterPDA(storePda, buyer.publicKey, program.programId);
+    buyerVouchers = getAssociatedTokenAddressSync(
+      voucherMintPda,
+      buyer.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await program.methods
+      .registerStore("Coffee Bar", "Prepaid coffee cards", "https://example.com/store")
+      .accounts({
+        store: storePda,
+        authority: storeOwner.publicKey,
+        payer: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    await program.methods
+      .registerProduct(
+        productUuid,
+        "Coffee",
+        "One cup of coffee",
+        "https://example.com/coffee.png",
+        new anchor.BN(productPrice),
+        new anchor.BN(100),
+        { fungible: {} },
+        "https://example.com/coffee.json",
+        [],
+        null
+      )
+      .accounts({
+        store: storePda,
+        product: productPda,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+  });
+
+  it("creates a voucher mint owned by the product PDA", async () => {
+    await program.methods
+      .initializeVoucherMint(productUuid)
+      .accounts({
+        store: storePda,
+        product: productPda,
+        voucherMint: voucherMintPda,
+        authority: storeOwner.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([storeOwner])
+      .rpc();
+
+    const mint = await getMint(provider.connection, voucherMintPda, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(mint.decimals, 0);
+    assert.ok(mint.mintAuthority.equals(productPda));
+  });
+
+  it("mints one voucher per unit bought", async () => {
+    await program.methods
+      .purchaseCart([productUuid], [new anchor.BN(5)], new anchor.BN(productPrice * 5))
+      .accounts({
+        store: storePda,
+        purchaseCounter: purchaseCounterPda,
+        receipt: receiptPda(0),
+        buyer: buyer.publicKey,
+        storeOwner: storeOwner.publicKey,
+        escrowAccount: escrowPda,
+        systemProgram: SystemProgram.programId,
+        productTokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        [productPda, voucherMintPda, buyerVouchers].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([buyer])
+      .rpc();
+
+    assert.equal(await vouchers(buyerVouchers), 5);
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.ok(receipt.tokenMints[0].equals(voucherMintPda));
+  });
+
+  it("lets vouchers move between customers and be redeemed by the holder", async () => {
+    const friendVouchers = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        friend,
+        voucherMintPda,
+        friend.publicKey,
+        false,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+    await transfer(
+      provider.connection,
+      buyer,
+      buyerVouchers,
+      friendVouchers,
+      buyer,
+      2,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await redeem(friend, friendVouchers, storeOwner, 1);
+
+    assert.equal(await vouchers(friendVouchers), 1);
+    const mint = await getMint(provider.connection, voucherMintPda, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(Number(mint.supply), 4);
+  });
+
+  it("only lets store admins redeem vouchers", async () => {
+    try {
+      await redeem(buyer, buyerVouchers, buyer, 1);
+      assert.fail("Expected a non-admin redemption to be rejected");
+    } catch (err) {
+      assert.include((err as Error).message, "Unauthorized");
+    }
+  });
+
+  it("burns returned vouchers when units are refunded", async () => {
+    const escrowVouchers = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        buyer,
+        voucherMintPda,
+        escrowPda,
+        true,
+        undefined,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+    await transfer(
+      provider.connection,
+      buyer,
+      buyerVouchers,
+      escrowVouchers,
+      buyer,
+      2,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    await program.methods
+      .refundLineItems(Buffer.from([0]), [new anchor.BN(2)])
+      .accounts({
+        escrowAccount: escrowPda,
+        store: storePda,
+        receipt: receiptPda(0),
+        buyer: buyer.publicKey,
+        authority: storeOwner.publicKey,
+        systemProgram: SystemProgram.programId,
+        productTokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        [productPda, voucherMintPda, escrowVouchers].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([storeOwner])
+      .rpc();
+
+    assert.equal(await vouchers(escrowVouchers), 0);
+    const receipt = await program.account.purchase.fetch(receiptPda(0));
+    assert.equal(receipt.refundedAmount.toNumber(), productPrice * 2);
+  });
+});
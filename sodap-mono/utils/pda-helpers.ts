@@ -1,8 +1,11 @@
 import { PublicKey } from "@solana/web3.js";
 import { BN } from "@coral-xyz/anchor";
 import { Buffer } from "buffer";
+import { createHash } from "crypto";
 
-// Your program ID - update this with your actual program ID
+// Your program ID - update this with your actual program ID.
+// Every find*PDA helper takes an optional programId, so tests can pass the
+// workspace program's ID instead.
 export const PROGRAM_ID = new PublicKey(
   "4eLJ3QGiNrPN6UUr2fNxq6tUZqFdBMVpXkL2MhsKNriv"
 );
@@ -17,13 +20,43 @@ export function uuidToBytes(uuid: string): Uint8Array {
   return bytes;
 }
 
+/**
+ * Find the singleton platform config PDA
+ */
+export function findPlatformConfigPDA(
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("platform_config")],
+    programId
+  );
+  return pda;
+}
+
 /**
  * Find the Store PDA for a given owner
  */
-export function findStorePDA(ownerPubkey: PublicKey): PublicKey {
+export function findStorePDA(
+  ownerPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
   const [pda] = PublicKey.findProgramAddressSync(
     [Buffer.from("store"), ownerPubkey.toBuffer()],
-    PROGRAM_ID
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the canonical UserProfile PDA for a wallet
+ */
+export function findUserProfilePDA(
+  authorityPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("user_profile"), authorityPubkey.toBuffer()],
+    programId
   );
   return pda;
 }
@@ -31,24 +64,82 @@ export function findStorePDA(ownerPubkey: PublicKey): PublicKey {
 /**
  * Find the Escrow PDA for a given store
  */
-export function findEscrowPDA(storePubkey: PublicKey): PublicKey {
+export function findEscrowPDA(
+  storePubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
   const [pda] = PublicKey.findProgramAddressSync(
     [Buffer.from("escrow"), storePubkey.toBuffer()],
-    PROGRAM_ID
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the per-buyer purchase counter PDA for a store
+ */
+export function findPurchaseCounterPDA(
+  storePubkey: PublicKey,
+  buyerPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("purchase_counter"),
+      storePubkey.toBuffer(),
+      buyerPubkey.toBuffer(),
+    ],
+    programId
   );
   return pda;
 }
 
 /**
- * Find the Receipt PDA for a purchase
+ * Find the Receipt PDA for a buyer's nth purchase at a store
  */
 export function findReceiptPDA(
   storePubkey: PublicKey,
-  buyerPubkey: PublicKey
+  buyerPubkey: PublicKey,
+  purchaseIndex: number | BN,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("purchase"),
+      storePubkey.toBuffer(),
+      buyerPubkey.toBuffer(),
+      new BN(purchaseIndex).toArrayLike(Buffer, "le", 8),
+    ],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find a buyer's scan-to-go Cart Session PDA at a store
+ */
+export function findCartSessionPDA(
+  storePubkey: PublicKey,
+  buyerPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
 ): PublicKey {
   const [pda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("purchase"), storePubkey.toBuffer(), buyerPubkey.toBuffer()],
-    PROGRAM_ID
+    [Buffer.from("cart_session"), storePubkey.toBuffer(), buyerPubkey.toBuffer()],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the Return Request PDA for a receipt
+ */
+export function findReturnRequestPDA(
+  receiptPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("return_request"), receiptPubkey.toBuffer()],
+    programId
   );
   return pda;
 }
@@ -58,13 +149,110 @@ export function findReceiptPDA(
  */
 export function findProductPDA(
   storePubkey: PublicKey,
-  productUuid: string
+  productUuid: string | number[],
+  programId: PublicKey = PROGRAM_ID
 ): PublicKey {
-  const productUuidBytes = uuidToBytes(productUuid);
+  const productUuidBytes =
+    typeof productUuid === "string"
+      ? uuidToBytes(productUuid)
+      : Uint8Array.from(productUuid);
 
   const [pda] = PublicKey.findProgramAddressSync(
     [Buffer.from("product"), storePubkey.toBuffer(), productUuidBytes],
-    PROGRAM_ID
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find a buyer's stock Reservation PDA for a product
+ */
+export function findReservationPDA(
+  productPubkey: PublicKey,
+  buyerPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("reservation"), productPubkey.toBuffer(), buyerPubkey.toBuffer()],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the SKU index PDA for a scanned barcode. It holds the product's
+ * address, so a scanner needs only the store and the barcode digits.
+ */
+export function findSkuIndexPDA(
+  storePubkey: PublicKey,
+  barcode: string,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const barcodeHash = createHash("sha256").update(barcode).digest();
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("sku"), storePubkey.toBuffer(), barcodeHash],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the certificate mint PDA for a NonFungible product's nth sale
+ */
+export function findCertificateMintPDA(
+  productPubkey: PublicKey,
+  serial: number | BN,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("certificate"),
+      productPubkey.toBuffer(),
+      new BN(serial).toArrayLike(Buffer, "le", 8),
+    ],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the voucher mint PDA for a Fungible product
+ */
+export function findVoucherMintPDA(
+  productPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("voucher_mint"), productPubkey.toBuffer()],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the Risk Config PDA for a store
+ */
+export function findRiskConfigPDA(
+  storePubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("risk_config"), storePubkey.toBuffer()],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the Loyalty Config PDA for a store
+ */
+export function findLoyaltyConfigPDA(
+  storePubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("loyalty_config"), storePubkey.toBuffer()],
+    programId
   );
   return pda;
 }
@@ -72,10 +260,42 @@ export function findProductPDA(
 /**
  * Find the Loyalty Mint PDA for a store
  */
-export function findLoyaltyMintPDA(storePubkey: PublicKey): PublicKey {
+export function findLoyaltyMintPDA(
+  storePubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
   const [pda] = PublicKey.findProgramAddressSync(
     [Buffer.from("loyalty_mint"), storePubkey.toBuffer()],
-    PROGRAM_ID
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the Staff Member PDA holding a store admin's role
+ */
+export function findStaffMemberPDA(
+  storePubkey: PublicKey,
+  staffPubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("staff"), storePubkey.toBuffer(), staffPubkey.toBuffer()],
+    programId
+  );
+  return pda;
+}
+
+/**
+ * Find the Store Roles PDA holding a store's custom role table
+ */
+export function findStoreRolesPDA(
+  storePubkey: PublicKey,
+  programId: PublicKey = PROGRAM_ID
+): PublicKey {
+  const [pda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("store_roles"), storePubkey.toBuffer()],
+    programId
   );
   return pda;
 }
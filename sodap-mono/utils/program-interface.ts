@@ -7,10 +7,12 @@ import {
   SendOptions,
 } from "@solana/web3.js";
 import { AnchorProvider, Program, BN, Idl } from "@coral-xyz/anchor";
+import { createHash } from "crypto";
 import {
   TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
+  getAssociatedTokenAddressSync,
   createAssociatedTokenAccountInstruction,
 } from "@solana/spl-token";
 // Note: You may need to install @solana/spl-token-2022: npm install @solana/spl-token-2022
@@ -21,8 +23,17 @@ import {
   findStorePDA,
   findEscrowPDA,
   findReceiptPDA,
+  findPurchaseCounterPDA,
   findProductPDA,
   findLoyaltyMintPDA,
+  findLoyaltyConfigPDA,
+  findCertificateMintPDA,
+  findVoucherMintPDA,
+  findRiskConfigPDA,
+  findUserProfilePDA,
+  findCartSessionPDA,
+  findSkuIndexPDA,
+  findReservationPDA,
   uuidToBytes,
   PROGRAM_ID,
 } from "./pda-helpers";
@@ -45,7 +56,54 @@ export function getSodapProgram(wallet: any, connection: Connection) {
 }
 
 /**
- * Execute a purchase cart transaction using Solana Pay
+ * Token accounts for a payment to or from a store's escrow. Stores priced in
+ * SOL take no token accounts, so every entry is null for them. The token
+ * program is read from the mint, so Token-2022 mints work too.
+ */
+export async function getTokenPaymentAccounts(
+  connection: Connection,
+  paymentMint: PublicKey | null,
+  escrowPDA: PublicKey,
+  wallet: PublicKey
+) {
+  if (!paymentMint) {
+    return {
+      mint: null,
+      escrowVault: null,
+      walletTokenAccount: null,
+      tokenProgram: null,
+    };
+  }
+
+  const mintInfo = await connection.getAccountInfo(paymentMint);
+  if (!mintInfo) {
+    throw new Error("Payment mint not found");
+  }
+  const tokenProgram = mintInfo.owner;
+
+  return {
+    mint: paymentMint,
+    // The escrow PDA is off-curve, so its ATA needs allowOwnerOffCurve
+    escrowVault: await getAssociatedTokenAddress(
+      paymentMint,
+      escrowPDA,
+      true,
+      tokenProgram
+    ),
+    walletTokenAccount: await getAssociatedTokenAddress(
+      paymentMint,
+      wallet,
+      false,
+      tokenProgram
+    ),
+    tokenProgram,
+  };
+}
+
+/**
+ * Execute a purchase cart transaction using Solana Pay.
+ * The program prices the cart from the product accounts, so
+ * totalAmountLamports must equal the on-chain total exactly.
  */
 export async function purchaseCart(
   connection: Connection,
@@ -54,8 +112,7 @@ export async function purchaseCart(
   productUuids: string[],
   quantities: number[],
   totalAmountLamports: number,
-  reference?: PublicKey,
-  gasFee: number = 0
+  reference?: PublicKey
 ) {
   if (!wallet.publicKey) {
     throw new Error("Wallet not connected");
@@ -67,9 +124,7 @@ export async function purchaseCart(
       ? new PublicKey(storePublicKey)
       : storePublicKey;
 
-  // Get PDAs
-  const escrowPDA = findEscrowPDA(storePubkey);
-  const receiptPDA = findReceiptPDA(storePubkey, wallet.publicKey);
+  const program = getSodapProgram(wallet, connection);
 
   // Convert product UUIDs to bytes arrays for the contract
   const productUuidBytesArray = productUuids.map((uuid) =>
@@ -81,35 +136,24 @@ export async function purchaseCart(
     findProductPDA(storePubkey, uuid)
   );
 
-  // Initialize program
-  const program = getSodapProgram(wallet, connection);
+  const { accounts, remainingAccounts, setupInstructions } =
+    await getPurchaseCartAccounts(
+      connection,
+      program,
+      storePubkey,
+      wallet.publicKey,
+      productPDAs
+    );
 
   // Create transaction instruction
   const ix = await program.methods
     .purchaseCart(
       productUuidBytesArray,
       quantities.map((q) => new BN(q)),
-      new BN(totalAmountLamports),
-      new BN(gasFee),
-      { success: {} } // Transaction status
-    )
-    .accounts({
-      buyer: wallet.publicKey,
-      store: storePubkey,
-      receipt: receiptPDA,
-      storeOwner: wallet.publicKey, // This might need to be the actual store owner
-      escrowAccount: escrowPDA,
-      systemProgram: SystemProgram.programId,
-      payer: wallet.publicKey,
-    })
-    .remainingAccounts(
-      // Add product accounts as remaining accounts
-      productPDAs.map((pda) => ({
-        pubkey: pda,
-        isWritable: true,
-        isSigner: false,
-      }))
+      new BN(totalAmountLamports)
     )
+    .accounts(accounts)
+    .remainingAccounts(remainingAccounts)
     .instruction();
 
   // Create transaction and add reference if provided
@@ -123,7 +167,7 @@ export async function purchaseCart(
     });
   }
 
-  transaction.add(ix);
+  transaction.add(...setupInstructions, ix);
 
   // Send transaction
   const { blockhash } = await connection.getLatestBlockhash();
@@ -146,13 +190,305 @@ export async function purchaseCart(
 }
 
 /**
- * Release funds from escrow to the store owner
+ * Accounts for purchaseCart, and for the purchase half of
+ * checkoutCartSession: the named accounts, the product PDAs followed by
+ * their product token accounts and the buyer's reservations, and any
+ * instructions that must run first.
+ */
+export async function getPurchaseCartAccounts(
+  connection: Connection,
+  program: Program,
+  storePubkey: PublicKey,
+  buyer: PublicKey,
+  productPDAs: PublicKey[]
+) {
+  // The program checks the store owner account against the store
+  const storeAccount = await program.account.store.fetch(storePubkey);
+  const storeOwner = storeAccount.owner as PublicKey;
+
+  // Get PDAs. The receipt is seeded by the buyer's next purchase index,
+  // which is 0 until the counter account exists.
+  const escrowPDA = findEscrowPDA(storePubkey);
+  const purchaseCounterPDA = findPurchaseCounterPDA(storePubkey, buyer);
+  const purchaseCounter = await program.account.purchaseCounter.fetchNullable(
+    purchaseCounterPDA
+  );
+  const purchaseIndex = purchaseCounter ? (purchaseCounter.count as BN) : new BN(0);
+  const receiptPDA = findReceiptPDA(storePubkey, buyer, purchaseIndex);
+  const tokenAccounts = await getTokenPaymentAccounts(
+    connection,
+    storeAccount.paymentMint as PublicKey | null,
+    escrowPDA,
+    buyer
+  );
+  const loyaltyAccounts = await getLoyaltyAccounts(
+    connection,
+    program,
+    storePubkey,
+    buyer
+  );
+
+  // Tokenized products mint a certificate or vouchers, whose accounts follow
+  // the products
+  const productTokenAccounts = await getProductTokenAccounts(
+    program,
+    productPDAs,
+    buyer
+  );
+
+  // Reserved units are taken before stock, so pass any live reservations
+  const reservationPDAs = productPDAs.map((pda) => findReservationPDA(pda, buyer));
+  const reservationInfos = await connection.getMultipleAccountsInfo(reservationPDAs);
+  const reservations = reservationPDAs.filter((_, i) => reservationInfos[i] !== null);
+
+  return {
+    accounts: {
+      buyer,
+      store: storePubkey,
+      purchaseCounter: purchaseCounterPDA,
+      receipt: receiptPDA,
+      storeOwner: storeOwner,
+      // Risk rules and the buyer's profile are read if they exist
+      riskConfig: findRiskConfigPDA(storePubkey),
+      buyerProfile: findUserProfilePDA(buyer),
+      escrowAccount: escrowPDA,
+      systemProgram: SystemProgram.programId,
+      buyerTokenAccount: tokenAccounts.walletTokenAccount,
+      escrowVault: tokenAccounts.escrowVault,
+      mint: tokenAccounts.mint,
+      tokenProgram: tokenAccounts.tokenProgram,
+      loyaltyConfig: loyaltyAccounts.loyaltyConfig,
+      loyaltyMint: loyaltyAccounts.loyaltyMint,
+      buyerLoyaltyAccount: loyaltyAccounts.buyerLoyaltyAccount,
+      loyaltyTokenProgram: loyaltyAccounts.loyaltyTokenProgram,
+      productTokenProgram: productTokenAccounts.length
+        ? TOKEN_2022_PROGRAM_ID
+        : null,
+      associatedTokenProgram: productTokenAccounts.length
+        ? ASSOCIATED_TOKEN_PROGRAM_ID
+        : null,
+    },
+    // Add product accounts, then product token accounts, then reservations,
+    // as remaining accounts
+    remainingAccounts: [...productPDAs, ...productTokenAccounts, ...reservations].map((pda) => ({
+      pubkey: pda,
+      isWritable: true,
+      isSigner: false,
+    })),
+    setupInstructions: loyaltyAccounts.createBuyerLoyaltyAccount
+      ? [loyaltyAccounts.createBuyerLoyaltyAccount]
+      : [],
+  };
+}
+
+/**
+ * Hold units of a product for the wallet until checkout. The units leave
+ * stock now and return to it if the reservation expires unused.
+ */
+export async function reserveStock(
+  connection: Connection,
+  wallet: any,
+  storePublicKey: string | PublicKey,
+  productUuid: string,
+  quantity: number
+) {
+  if (!wallet.publicKey) {
+    throw new Error("Wallet not connected");
+  }
+
+  // Convert store public key if it's a string
+  const storePubkey =
+    typeof storePublicKey === "string"
+      ? new PublicKey(storePublicKey)
+      : storePublicKey;
+
+  const program = getSodapProgram(wallet, connection);
+  const productPDA = findProductPDA(storePubkey, productUuid);
+
+  try {
+    return await program.methods
+      .reserveStock(Array.from(uuidToBytes(productUuid)), new BN(quantity))
+      .accounts({
+        store: storePubkey,
+        product: productPDA,
+        reservation: findReservationPDA(productPDA, wallet.publicKey),
+        buyer: wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  } catch (error) {
+    console.error("Error reserving stock:", error);
+    throw error;
+  }
+}
+
+/**
+ * Look up the product a scanned barcode belongs to at a store. Returns null
+ * when no active product in the store carries the barcode.
+ */
+export async function findProductByBarcode(
+  connection: Connection,
+  wallet: any,
+  storePublicKey: string | PublicKey,
+  barcode: string
+) {
+  // Convert store public key if it's a string
+  const storePubkey =
+    typeof storePublicKey === "string"
+      ? new PublicKey(storePublicKey)
+      : storePublicKey;
+
+  const program = getSodapProgram(wallet, connection);
+  const skuIndex = await program.account.skuIndex.fetchNullable(
+    findSkuIndexPDA(storePubkey, barcode)
+  );
+  if (!skuIndex) {
+    return null;
+  }
+  return {
+    product: skuIndex.product as PublicKey,
+    productUuid: Uint8Array.from(skuIndex.productUuid as number[]),
+  };
+}
+
+/**
+ * Add a scanned product to the buyer's cart session at a store, opening the
+ * session if needed. The product's current price is locked for the line.
+ */
+export async function scanCartItem(
+  connection: Connection,
+  wallet: any,
+  storePublicKey: string | PublicKey,
+  productUuid: string,
+  quantity: number
+) {
+  if (!wallet.publicKey) {
+    throw new Error("Wallet not connected");
+  }
+
+  // Convert store public key if it's a string
+  const storePubkey =
+    typeof storePublicKey === "string"
+      ? new PublicKey(storePublicKey)
+      : storePublicKey;
+
+  const program = getSodapProgram(wallet, connection);
+  const productPDA = findProductPDA(storePubkey, productUuid);
+
+  try {
+    return await program.methods
+      .scanCartItem(Array.from(uuidToBytes(productUuid)), new BN(quantity))
+      .accounts({
+        store: storePubkey,
+        product: productPDA,
+        cartSession: findCartSessionPDA(storePubkey, wallet.publicKey),
+        // Units the wallet has reserved count as available
+        reservation: findReservationPDA(productPDA, wallet.publicKey),
+        buyer: wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  } catch (error) {
+    console.error("Error scanning cart item:", error);
+    throw error;
+  }
+}
+
+/**
+ * Pay for the buyer's cart session at its locked prices and close it.
+ * totalAmountLamports must equal the session total exactly.
+ */
+export async function checkoutCartSession(
+  connection: Connection,
+  wallet: any,
+  storePublicKey: string | PublicKey,
+  totalAmountLamports: number
+) {
+  if (!wallet.publicKey) {
+    throw new Error("Wallet not connected");
+  }
+
+  // Convert store public key if it's a string
+  const storePubkey =
+    typeof storePublicKey === "string"
+      ? new PublicKey(storePublicKey)
+      : storePublicKey;
+
+  const program = getSodapProgram(wallet, connection);
+  const cartSessionPDA = findCartSessionPDA(storePubkey, wallet.publicKey);
+  const session = await program.account.cartSession.fetch(cartSessionPDA);
+
+  // Products are passed in session line order
+  const productPDAs = (session.productUuids as number[][]).map(
+    (uuid) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("product"), storePubkey.toBuffer(), Buffer.from(uuid)],
+        PROGRAM_ID
+      )[0]
+  );
+  const { accounts, remainingAccounts, setupInstructions } =
+    await getPurchaseCartAccounts(
+      connection,
+      program,
+      storePubkey,
+      wallet.publicKey,
+      productPDAs
+    );
+
+  try {
+    return await program.methods
+      .checkoutCartSession(new BN(totalAmountLamports))
+      .accounts({ purchase: accounts, cartSession: cartSessionPDA })
+      .remainingAccounts(remainingAccounts)
+      .preInstructions(setupInstructions)
+      .rpc();
+  } catch (error) {
+    console.error("Error checking out cart session:", error);
+    throw error;
+  }
+}
+
+/**
+ * Product token accounts for a cart, in cart order: the next certificate mint
+ * for each NonFungible product or the voucher mint for each Fungible product,
+ * each followed by the buyer's Token-2022 account for it.
+ */
+export async function getProductTokenAccounts(
+  program: Program,
+  productPDAs: PublicKey[],
+  buyer: PublicKey
+): Promise<PublicKey[]> {
+  const accounts: PublicKey[] = [];
+  for (const productPDA of productPDAs) {
+    const product = await program.account.product.fetch(productPDA);
+    let mint: PublicKey;
+    if (product.tokenizedType.nonFungible) {
+      mint = findCertificateMintPDA(
+        productPDA,
+        product.certificatesMinted as BN
+      );
+    } else if (product.tokenizedType.fungible) {
+      mint = findVoucherMintPDA(productPDA);
+    } else {
+      continue;
+    }
+    accounts.push(
+      mint,
+      getAssociatedTokenAddressSync(mint, buyer, false, TOKEN_2022_PROGRAM_ID)
+    );
+  }
+  return accounts;
+}
+
+/**
+ * Release a purchase's matured escrow funds to the store's payout address.
+ * Fails until the store's dispute window has passed since the purchase.
  */
 export async function releaseEscrow(
   connection: Connection,
   wallet: any,
   storePublicKey: string | PublicKey,
-  amountLamports: number
+  receiptPublicKey: PublicKey
 ) {
   if (!wallet.publicKey) {
     throw new Error("Wallet not connected");
@@ -169,18 +505,29 @@ export async function releaseEscrow(
 
   // Initialize program
   const program = getSodapProgram(wallet, connection);
+  const storeAccount = await program.account.store.fetch(storePubkey);
+  const payout = storeAccount.payoutAddress as PublicKey;
+  const tokenAccounts = await getTokenPaymentAccounts(
+    connection,
+    storeAccount.paymentMint as PublicKey | null,
+    escrowPDA,
+    payout
+  );
 
   try {
     // Create and send transaction
     const tx = await program.methods
-      .releaseEscrow(new BN(amountLamports))
+      .releaseEscrow()
       .accounts({
-        store: wallet.publicKey,
-        storeAccount: storePubkey,
-        storeOwner: wallet.publicKey,
         escrowAccount: escrowPDA,
-        systemProgram: SystemProgram.programId,
-        payer: wallet.publicKey,
+        store: storePubkey,
+        receipt: receiptPublicKey,
+        payout,
+        authority: wallet.publicKey,
+        payoutTokenAccount: tokenAccounts.walletTokenAccount,
+        escrowVault: tokenAccounts.escrowVault,
+        mint: tokenAccounts.mint,
+        tokenProgram: tokenAccounts.tokenProgram,
       })
       .rpc();
 
@@ -192,13 +539,14 @@ export async function releaseEscrow(
 }
 
 /**
- * Initialize a loyalty token mint for a store with Token-2022 features
- * Creates a non-transferable token mint for loyalty points
+ * Settle a batch of matured receipts to the store's payout address.
+ * Anyone can call this; the caller is paid the store's crank tip per receipt.
  */
-export async function initializeLoyaltyMint(
+export async function settleMaturedPurchases(
   connection: Connection,
   wallet: any,
-  storePublicKey: string | PublicKey
+  storePublicKey: string | PublicKey,
+  receiptPublicKeys: PublicKey[]
 ) {
   if (!wallet.publicKey) {
     throw new Error("Wallet not connected");
@@ -210,23 +558,149 @@ export async function initializeLoyaltyMint(
       ? new PublicKey(storePublicKey)
       : storePublicKey;
 
-  // Get loyalty mint PDA
+  // Get escrow PDA
+  const escrowPDA = findEscrowPDA(storePubkey);
+
+  // Initialize program
+  const program = getSodapProgram(wallet, connection);
+  const storeAccount = await program.account.store.fetch(storePubkey);
+  const payout = storeAccount.payoutAddress as PublicKey;
+  const tokenAccounts = await getTokenPaymentAccounts(
+    connection,
+    storeAccount.paymentMint as PublicKey | null,
+    escrowPDA,
+    payout
+  );
+
+  try {
+    // Receipts are passed as writable remaining accounts
+    const tx = await program.methods
+      .settleMaturedPurchases()
+      .accounts({
+        escrowAccount: escrowPDA,
+        store: storePubkey,
+        payout,
+        cranker: wallet.publicKey,
+        payoutTokenAccount: tokenAccounts.walletTokenAccount,
+        escrowVault: tokenAccounts.escrowVault,
+        mint: tokenAccounts.mint,
+        tokenProgram: tokenAccounts.tokenProgram,
+      })
+      .remainingAccounts(
+        receiptPublicKeys.map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .rpc();
+
+    return tx;
+  } catch (error) {
+    console.error("Error settling purchases:", error);
+    throw error;
+  }
+}
+
+/**
+ * Commitment to a private profile field: sha256(salt || value). The salt
+ * stays with the user, so the plaintext never touches the chain; pass it
+ * back alongside the value when a store needs to verify the field.
+ */
+export function profileCommitment(salt: Uint8Array, value: string): number[] {
+  return Array.from(
+    createHash("sha256").update(salt).update(value, "utf8").digest()
+  );
+}
+
+/**
+ * Create or update the wallet's UserProfile. Fields left undefined keep
+ * their current value; an empty string clears a contact field.
+ */
+export async function updateUserProfile(
+  connection: Connection,
+  wallet: any,
+  salt: Uint8Array,
+  fields: {
+    userId?: string;
+    name?: string;
+    email?: string;
+    phone?: string;
+    deliveryAddress?: string;
+    preferredStore?: PublicKey;
+  }
+) {
+  if (!wallet.publicKey) {
+    throw new Error("Wallet not connected");
+  }
+
+  const commit = (value?: string) => {
+    if (value === undefined) return null;
+    // All zeroes tells the program to clear the field
+    return value === "" ? new Array(32).fill(0) : profileCommitment(salt, value);
+  };
+
+  const program = getSodapProgram(wallet, connection);
+
+  try {
+    return await program.methods
+      .createOrUpdateUserProfile(
+        fields.userId ?? null,
+        commit(fields.name),
+        commit(fields.email),
+        commit(fields.phone),
+        commit(fields.deliveryAddress),
+        fields.preferredStore ?? null
+      )
+      .accounts({
+        userProfile: findUserProfilePDA(wallet.publicKey),
+        authority: wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  } catch (error) {
+    console.error("Error updating user profile:", error);
+    throw error;
+  }
+}
+
+/**
+ * Start a store's loyalty program. Creates the store's LoyaltyConfig and a
+ * Token-2022 points mint whose mint authority is the config PDA.
+ * earnRateBps is the share of each payment minted back as points.
+ */
+export async function initializeLoyalty(
+  connection: Connection,
+  wallet: any,
+  storePublicKey: string | PublicKey,
+  earnRateBps: number
+) {
+  if (!wallet.publicKey) {
+    throw new Error("Wallet not connected");
+  }
+
+  // Convert store public key if it's a string
+  const storePubkey =
+    typeof storePublicKey === "string"
+      ? new PublicKey(storePublicKey)
+      : storePublicKey;
+
+  const loyaltyConfigPDA = findLoyaltyConfigPDA(storePubkey);
   const loyaltyMintPDA = findLoyaltyMintPDA(storePubkey);
 
   // Initialize program
   const program = getSodapProgram(wallet, connection);
 
   try {
-    // Create transaction for initializing loyalty mint
     const tx = await program.methods
-      .initializeLoyaltyMint()
+      .initializeLoyalty(earnRateBps)
       .accounts({
         store: storePubkey,
+        loyaltyConfig: loyaltyConfigPDA,
         loyaltyMint: loyaltyMintPDA,
         authority: wallet.publicKey,
-        systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_2022_PROGRAM_ID,
-        rent: await connection.getMinimumBalanceForRentExemption(0),
+        systemProgram: SystemProgram.programId,
       })
       .rpc();
 
@@ -235,21 +709,71 @@ export async function initializeLoyaltyMint(
       loyaltyMint: loyaltyMintPDA,
     };
   } catch (error) {
-    console.error("Error initializing loyalty mint:", error);
+    console.error("Error initializing loyalty program:", error);
     throw error;
   }
 }
 
 /**
- * Mint loyalty points to a user after a purchase
- * Uses 1:1 conversion - 1 SOL spent = 1 loyalty point
+ * Loyalty accounts for purchaseCart. Points are earned only when these are
+ * passed, so they are null for stores without an active loyalty program.
+ */
+export async function getLoyaltyAccounts(
+  connection: Connection,
+  program: Program,
+  storePubkey: PublicKey,
+  buyer: PublicKey
+) {
+  const loyaltyConfigPDA = findLoyaltyConfigPDA(storePubkey);
+  const config = await program.account.loyaltyConfig.fetchNullable(
+    loyaltyConfigPDA
+  );
+  if (!config || !config.isActive) {
+    return {
+      loyaltyConfig: null,
+      loyaltyMint: null,
+      buyerLoyaltyAccount: null,
+      loyaltyTokenProgram: null,
+      createBuyerLoyaltyAccount: null,
+    };
+  }
+
+  const loyaltyMint = config.mint as PublicKey;
+  const buyerLoyaltyAccount = await getAssociatedTokenAddress(
+    loyaltyMint,
+    buyer,
+    false,
+    TOKEN_2022_PROGRAM_ID
+  );
+  const exists = await connection.getAccountInfo(buyerLoyaltyAccount);
+
+  return {
+    loyaltyConfig: loyaltyConfigPDA,
+    loyaltyMint,
+    buyerLoyaltyAccount,
+    loyaltyTokenProgram: TOKEN_2022_PROGRAM_ID,
+    // The buyer's points account must exist before points can be minted
+    createBuyerLoyaltyAccount: exists
+      ? null
+      : createAssociatedTokenAccountInstruction(
+          buyer,
+          buyerLoyaltyAccount,
+          buyer,
+          loyaltyMint,
+          TOKEN_2022_PROGRAM_ID
+        ),
+  };
+}
+
+/**
+ * Burn loyalty points for a discount on the buyer's next checkout at the
+ * store. One point is worth one base unit of the store's payment currency.
  */
-export async function mintLoyaltyPoints(
+export async function redeemLoyaltyPoints(
   connection: Connection,
   wallet: any,
   storePublicKey: string | PublicKey,
-  userPublicKey: PublicKey,
-  amountSol: number
+  points: number
 ) {
   if (!wallet.publicKey) {
     throw new Error("Wallet not connected");
@@ -261,80 +785,35 @@ export async function mintLoyaltyPoints(
       ? new PublicKey(storePublicKey)
       : storePublicKey;
 
-  // 1:1 conversion (1 SOL = 1 loyalty point)
-  const loyaltyPoints = Math.floor(amountSol);
-  if (loyaltyPoints <= 0) {
-    return null; // No points to mint
-  }
-
-  // Get loyalty mint PDA
   const loyaltyMintPDA = findLoyaltyMintPDA(storePubkey);
-
-  // Find the associated token account for the user
-  const userTokenAccount = await getAssociatedTokenAddress(
+  const buyerLoyaltyAccount = await getAssociatedTokenAddress(
     loyaltyMintPDA,
-    userPublicKey,
+    wallet.publicKey,
     false,
     TOKEN_2022_PROGRAM_ID
   );
 
-  // Check if the token account exists
-  const accountInfo = await connection.getAccountInfo(userTokenAccount);
-
   // Initialize program
   const program = getSodapProgram(wallet, connection);
 
-  // Create transaction
-  const transaction = new Transaction();
-
-  // If token account doesn't exist, add instruction to create it
-  if (!accountInfo) {
-    transaction.add(
-      createAssociatedTokenAccountInstruction(
-        wallet.publicKey,
-        userTokenAccount,
-        userPublicKey,
-        loyaltyMintPDA,
-        TOKEN_2022_PROGRAM_ID
-      )
-    );
-  }
-
-  // Add instruction to mint loyalty points
-  const mintIx = await program.methods
-    .mintLoyaltyPoints(new BN(loyaltyPoints), userPublicKey)
-    .accounts({
-      store: storePubkey,
-      loyaltyMint: loyaltyMintPDA,
-      authority: wallet.publicKey,
-      tokenProgram: TOKEN_2022_PROGRAM_ID,
-      destination: userTokenAccount,
-      payer: wallet.publicKey,
-    })
-    .instruction();
-
-  transaction.add(mintIx);
-
-  // Send transaction
-  const { blockhash } = await connection.getLatestBlockhash();
-  transaction.recentBlockhash = blockhash;
-  transaction.feePayer = wallet.publicKey;
-
   try {
-    // Sign and send the transaction
-    const signedTx = await wallet.signTransaction(transaction);
-    const signature = await connection.sendRawTransaction(signedTx.serialize());
-
-    // Wait for confirmation
-    await connection.confirmTransaction(signature, "confirmed");
+    const tx = await program.methods
+      .redeemLoyaltyPoints(new BN(points))
+      .accounts({
+        store: storePubkey,
+        loyaltyConfig: findLoyaltyConfigPDA(storePubkey),
+        loyaltyMint: loyaltyMintPDA,
+        buyerLoyaltyAccount,
+        purchaseCounter: findPurchaseCounterPDA(storePubkey, wallet.publicKey),
+        buyer: wallet.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
 
-    return {
-      signature,
-      pointsMinted: loyaltyPoints,
-      tokenAccount: userTokenAccount,
-    };
+    return tx;
   } catch (error) {
-    console.error("Error minting loyalty points:", error);
+    console.error("Error redeeming loyalty points:", error);
     throw error;
   }
 }